@@ -1,4 +1,5 @@
 use pinout::parser::csv::parse_csv_file;
+use pinout::parser::report;
 use pinout::parser::types::Command;
 use std::error::Error;
 
@@ -62,34 +63,8 @@ fn main() -> Result<(), Box<dyn Error>> {
         println!("{:?}", box_cmd);
     }
 
-    // Count different types of commands
-    let label_count = commands
-        .iter()
-        .filter(|cmd| matches!(cmd, Command::Labels { .. }))
-        .count();
-    let pin_count = commands
-        .iter()
-        .filter(|cmd| matches!(cmd, Command::Pin { .. }))
-        .count();
-    let pintext_count = commands
-        .iter()
-        .filter(|cmd| matches!(cmd, Command::PinText { .. }))
-        .count();
-    let text_count = commands
-        .iter()
-        .filter(|cmd| matches!(cmd, Command::Text { .. }))
-        .count();
-    let box_count = commands
-        .iter()
-        .filter(|cmd| matches!(cmd, Command::Box { .. }))
-        .count();
-
-    println!("\n--- Command Statistics ---");
-    println!("Label commands: {}", label_count);
-    println!("Pin commands: {}", pin_count);
-    println!("PinText commands: {}", pintext_count);
-    println!("Text commands: {}", text_count);
-    println!("Box commands: {}", box_count);
+    // Command counts, themes and pins
+    println!("\n{}", report(&commands));
 
     Ok(())
 }
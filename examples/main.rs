@@ -1,7 +1,7 @@
 // src/main.rs
 use clap::{Arg, Command};
 use pinout::parser::csv::parse_csv_file;
-use pinout::renderer::svg::generate_svg;
+use pinout::renderer::svg::SvgRenderer;
 use std::path::Path;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -27,6 +27,19 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .short('o')
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("png")
+                .help("Also write a PNG next to the SVG (needs the png feature)")
+                .long("png")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("expect_pins")
+                .help("Fail unless the diagram has exactly this many pins")
+                .long("expect-pins")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize)),
+        )
         .get_matches();
 
     let csv_path = matches.get_one::<String>("csv_file").unwrap();
@@ -58,10 +71,44 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Parse the CSV file
     let commands = parse_csv_file(csv_path)?;
 
-    // Generate the SVG from the commands
-    generate_svg(&commands, &svg_path)?;
+    // Generate the SVG from the commands, PINOUT_PAGE and PINOUT_DPI set the defaults
+    let mut renderer = SvgRenderer::from_env()?;
+    renderer.process_commands(&commands)?;
+
+    // Catch data errors before anything is written
+    if let Some(&expected) = matches.get_one::<usize>("expect_pins") {
+        if renderer.pin_count() != expected {
+            return Err(format!(
+                "Expected {} pins but {} has {}",
+                expected,
+                csv_path,
+                renderer.pin_count()
+            )
+            .into());
+        }
+    }
+
+    renderer.save_to_file(&svg_path)?;
 
     println!("Successfully generated SVG: {}", svg_path);
 
+    if matches.get_flag("png") {
+        let png_path = Path::new(&svg_path).with_extension("png");
+        let png_path = png_path.to_str().unwrap();
+        write_png(&renderer, png_path)?;
+        println!("Successfully generated PNG: {}", png_path);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "png")]
+fn write_png(renderer: &SvgRenderer, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    renderer.save_png(path, 1.0)?;
     Ok(())
 }
+
+#[cfg(not(feature = "png"))]
+fn write_png(_renderer: &SvgRenderer, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    Err(format!("Cannot write {}, build with the png feature", path).into())
+}
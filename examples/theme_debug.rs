@@ -34,6 +34,12 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Generate SVG with theme debugging enabled
     generate_svg_with_debug(&commands, "output_debug.svg", true)?;
 
+    // And one with every box and pin labelled with its coordinates
+    let mut renderer = SvgRenderer::new();
+    renderer.set_debug_labels(true);
+    renderer.process_commands(&commands)?;
+    renderer.save_to_file("output_debug_labels.svg")?;
+
     println!("SVG generated successfully!");
 
     Ok(())
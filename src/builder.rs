@@ -0,0 +1,157 @@
+use crate::parser::{
+    csv::ParserError,
+    document::Document,
+    types::{Command, JustifyX, JustifyY, PinType, Side, WireType},
+};
+
+/// Builds the commands of a diagram in code, in place of a CSV file. Every command is
+/// checked against the phase it is added in, like a file's rows.
+///
+/// ```
+/// use pinout::builder::Diagram;
+/// use pinout::svg::generate_svg;
+/// use pinout::types::{PinType, Side, WireType};
+///
+/// let commands = Diagram::new()
+///     .labels(&["Name", "GPIO"])?
+///     .draw()?
+///     .anchor(400.0, 100.0)?
+///     .pin_set(Side::Left)?
+///     .pin(Some(WireType::Digital), Some(PinType::IO), None, &["D1", "GPIO5"])?
+///     .pin(Some(WireType::Power), None, None, &["3V3"])?
+///     .build();
+///
+/// let path = std::env::temp_dir().join("pinout_builder_doc.svg");
+/// generate_svg(&commands, path.to_str().unwrap())?;
+/// # std::fs::remove_file(path)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Default)]
+pub struct Diagram {
+    document: Document,
+}
+
+impl Diagram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add any command, failing with `InvalidPhase` when it doesn't belong to the current
+    /// phase, e.g. a draw command before `draw()`
+    pub fn command(mut self, command: Command) -> Result<Self, ParserError> {
+        self.document.add_command(command)?;
+        Ok(self)
+    }
+
+    /// Name the pin attribute columns, themed by the DEFAULT, TYPE and GROUP themes
+    pub fn labels(self, labels: &[&str]) -> Result<Self, ParserError> {
+        self.command(Command::Labels {
+            default: "DEFAULT".to_string(),
+            pin_type: Some("TYPE".to_string()),
+            group: Some("GROUP".to_string()),
+            labels: labels.iter().map(|label| label.to_string()).collect(),
+        })
+    }
+
+    pub fn page(self, page_name: &str) -> Result<Self, ParserError> {
+        self.command(Command::Page {
+            page_name: page_name.to_string(),
+            size: None,
+        })
+    }
+
+    pub fn dpi(self, dpi: u32) -> Result<Self, ParserError> {
+        self.command(Command::Dpi { dpi })
+    }
+
+    /// End the setup phase, the commands after this draw
+    pub fn draw(self) -> Result<Self, ParserError> {
+        self.command(Command::Draw)
+    }
+
+    pub fn anchor(self, x: f32, y: f32) -> Result<Self, ParserError> {
+        self.command(Command::Anchor { x, y })
+    }
+
+    /// Start a packed, centred pin set on `side`, rows 25 apart with 40 pixel leaders.
+    /// Add a `Command::PinSet` for any other layout.
+    pub fn pin_set(self, side: Side) -> Result<Self, ParserError> {
+        self.command(Command::PinSet {
+            side,
+            packed: true,
+            justify_x: JustifyX::Center,
+            justify_y: JustifyY::Center,
+            line_step: 25.0,
+            pin_width: 10.0,
+            group_width: 20.0,
+            leader_offset: 40.0,
+            column_gap: 5.0,
+            column_gap_percent: false,
+            leader_h_step: 0.0,
+            angle: None,
+            units: None,
+            advance: None,
+            first_number: None,
+            box_gap: None,
+            frame: None,
+            title: None,
+        })
+    }
+
+    /// Add a pin row to the current pin set, one attribute per label
+    pub fn pin(
+        self,
+        wire: Option<WireType>,
+        pin_type: Option<PinType>,
+        group: Option<&str>,
+        attributes: &[&str],
+    ) -> Result<Self, ParserError> {
+        self.command(Command::Pin {
+            wire,
+            pin_type,
+            group: group.map(|group| group.to_string()),
+            attributes: attributes
+                .iter()
+                .map(|attribute| attribute.to_string())
+                .collect(),
+            leader: None,
+        })
+    }
+
+    /// The commands added so far, ready for `generate_svg`
+    pub fn build(self) -> Vec<Command> {
+        self.document.commands
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagram_phases() {
+        let commands = Diagram::new()
+            .dpi(150)
+            .and_then(|diagram| diagram.draw())
+            .and_then(|diagram| diagram.anchor(10.0, 20.0))
+            .unwrap()
+            .build();
+        assert_eq!(
+            commands,
+            vec![
+                Command::Dpi { dpi: 150 },
+                Command::Draw,
+                Command::Anchor { x: 10.0, y: 20.0 },
+            ]
+        );
+
+        assert!(matches!(
+            Diagram::new().anchor(10.0, 20.0),
+            Err(ParserError::InvalidPhase)
+        ));
+        assert!(matches!(
+            Diagram::new().draw().and_then(|diagram| diagram.dpi(150)),
+            Err(ParserError::InvalidPhase)
+        ));
+    }
+}
@@ -1,9 +1,12 @@
 use csv::{ReaderBuilder, StringRecord};
+use regex::Regex;
+use std::io::Read;
+use std::path::Path;
 use thiserror::Error;
 
 use super::types::{
-    Command, FontBoldness, FontSlant, FontStretch, JustifyX, JustifyY, Phase, PinType, Side,
-    WireType,
+    BoxShape, Command, Corner, FontBoldness, FontSlant, FontStretch, GroupShape, JustifyX,
+    JustifyY, Phase, PinType, Side, Terminal, Unit, WireType,
 };
 
 #[derive(Debug, Error)]
@@ -17,16 +20,106 @@ pub enum ParserError {
     #[error("Invalid phase for command")]
     InvalidPhase,
 
+    #[error("Unknown command: {0}")]
+    UnknownCommand(String),
+
+    #[error("Input limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("Line {line}: {source}")]
+    AtLine { line: u64, source: Box<ParserError> },
+
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
 }
 
+/// Options controlling how a CSV file is read
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseOptions {
+    /// Field delimiter, `;` is common in locales that use a decimal comma
+    pub delimiter: u8,
+    /// Decimal separator of numeric fields, either `.` or `,`
+    pub decimal_separator: char,
+    /// Most commands a file may hold, to bound the work done on untrusted input
+    pub max_commands: Option<usize>,
+    /// Draw command names passed on as `Command::Custom` for handlers registered on the
+    /// renderer, any other unknown name is an error
+    pub custom_commands: Vec<String>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            delimiter: b',',
+            decimal_separator: '.',
+            max_commands: None,
+            custom_commands: Vec::new(),
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Options for CSV exports using `;` between fields and `,` as the decimal separator
+    pub fn comma_decimal() -> Self {
+        ParseOptions {
+            delimiter: b';',
+            decimal_separator: ',',
+            ..Default::default()
+        }
+    }
+}
+
 /// Parses a CSV file into a list of commands
 pub fn parse_csv_file(path: &str) -> Result<Vec<Command>, ParserError> {
+    parse_csv_file_with_options(path, &ParseOptions::default())
+}
+
+/// Parses a CSV file into a list of commands, using the given number and field format
+pub fn parse_csv_file_with_options(
+    path: &str,
+    options: &ParseOptions,
+) -> Result<Vec<Command>, ParserError> {
+    let file = std::fs::File::open(path)?;
+    parse_records(file, options, path, Path::new(path).parent())
+}
+
+/// Parses CSV text from any reader into a list of commands
+pub fn parse_csv_reader<R: Read>(reader: R) -> Result<Vec<Command>, ParserError> {
+    parse_csv_reader_with_options(reader, &ParseOptions::default())
+}
+
+/// Parses CSV text from any reader into a list of commands, using the given number and
+/// field format
+pub fn parse_csv_reader_with_options<R: Read>(
+    reader: R,
+    options: &ParseOptions,
+) -> Result<Vec<Command>, ParserError> {
+    parse_records(reader, options, "Input", None)
+}
+
+/// Parses CSV text held in memory into a list of commands
+pub fn parse_csv_str(text: &str) -> Result<Vec<Command>, ParserError> {
+    parse_csv_reader(text.as_bytes())
+}
+
+/// The record loop shared by every parse function, `source` names the input in errors and
+/// relative PINDATA paths are taken from `base_dir`
+fn parse_records<R: Read>(
+    input: R,
+    options: &ParseOptions,
+    source: &str,
+    base_dir: Option<&Path>,
+) -> Result<Vec<Command>, ParserError> {
+    // Fields starting with " may hold delimiters and line breaks, "" inside them is a literal "
     let mut reader = ReaderBuilder::new()
         .has_headers(false)
         .flexible(true)
-        .from_path(path)?;
+        .delimiter(options.delimiter)
+        .quoting(true)
+        .quote(b'"')
+        .double_quote(true)
+        .escape(None)
+        .from_reader(input);
 
     let mut commands = Vec::new();
     let mut phase = Phase::Setup;
@@ -34,14 +127,36 @@ pub fn parse_csv_file(path: &str) -> Result<Vec<Command>, ParserError> {
     for result in reader.records() {
         let record = result?;
 
-        if record.is_empty()
-            || record
-                .get(0)
-                .map_or(true, |s| s.trim().is_empty() || s.trim().starts_with('#'))
+        // "#@" comments in the draw phase are notes to show, any other "#" row is skipped
+        let note = match phase {
+            Phase::Draw => parse_note(&record, options.delimiter),
+            Phase::Setup => None,
+        };
+        if note.is_none()
+            && (record.is_empty()
+                || record
+                    .get(0)
+                    .is_none_or(|s| s.trim().is_empty() || s.trim().starts_with('#')))
         {
             continue;
         }
 
+        if options
+            .max_commands
+            .is_some_and(|max| commands.len() >= max)
+        {
+            return Err(ParserError::LimitExceeded(format!(
+                "{} has more than {} commands",
+                source,
+                options.max_commands.unwrap()
+            )));
+        }
+
+        if let Some(note) = note {
+            commands.push(note);
+            continue;
+        }
+
         let command_name = record.get(0).unwrap().trim().to_uppercase();
 
         // Check for phase transition
@@ -51,80 +166,209 @@ pub fn parse_csv_file(path: &str) -> Result<Vec<Command>, ParserError> {
             continue;
         }
 
-        let command = parse_command(command_name, &record, phase)?;
+        // Say which row went wrong, the file may have hundreds
+        let mut command =
+            match parse_command(command_name, &record, phase, options.decimal_separator) {
+                // Built-in names keep their meaning even when also listed as custom
+                Err(ParserError::UnknownCommand(name))
+                    if phase == Phase::Draw
+                        && options
+                            .custom_commands
+                            .iter()
+                            .any(|custom| custom.trim().eq_ignore_ascii_case(&name)) =>
+                {
+                    Ok(parse_custom_command(name, &record))
+                }
+                result => result,
+            }
+            .map_err(|e| match record.position() {
+                Some(position) => ParserError::AtLine {
+                    line: position.line(),
+                    source: Box::new(e),
+                },
+                None => e,
+            })?;
+
+        // Pin data files sit beside this one, and are read the same way and to the same limit
+        if let Command::PinData {
+            path,
+            delimiter,
+            decimal_separator,
+            max_rows,
+        } = &mut command
+        {
+            if let Some(dir) = base_dir.filter(|_| Path::new(path.as_str()).is_relative()) {
+                *path = dir.join(path.as_str()).to_string_lossy().into_owned();
+            }
+            *delimiter = options.delimiter;
+            *decimal_separator = options.decimal_separator;
+            *max_rows = options.max_commands;
+        }
         commands.push(command);
     }
 
     Ok(commands)
 }
 
+/// Reads a "#@" comment row as a note, its fields joined back into one line of text
+fn parse_note(record: &StringRecord, delimiter: u8) -> Option<Command> {
+    let first = record.get(0)?.trim_start().strip_prefix("#@")?;
+    let separator = format!("{} ", delimiter as char);
+    let text = std::iter::once(first)
+        .chain(record.iter().skip(1))
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join(&separator)
+        .trim_end_matches([delimiter as char, ' '])
+        .to_string();
+
+    Some(Command::Note { text })
+}
+
+/// Reads the fields of one command, given the record and the decimal separator
+type CommandParser = fn(&StringRecord, char) -> Result<Command, ParserError>;
+
+/// Every built-in command with the phase it belongs to, BOX is the one name used in both
+const COMMANDS: &[(&str, Phase, CommandParser)] = &[
+    // Setup Phase Commands
+    ("LABELS", Phase::Setup, |r, _| parse_label_command(r)),
+    ("BORDER COLOR", Phase::Setup, |r, _| {
+        parse_border_color_command(r)
+    }),
+    ("BORDER WIDTH", Phase::Setup, |r, _| {
+        parse_border_width_command(r)
+    }),
+    ("BORDER OPACITY", Phase::Setup, parse_border_opacity_command),
+    ("FILL COLOR", Phase::Setup, |r, _| {
+        parse_fill_color_command(r)
+    }),
+    ("OPACITY", Phase::Setup, parse_opacity_command),
+    ("FONT", Phase::Setup, |r, _| parse_font_command(r)),
+    ("FONT SIZE", Phase::Setup, parse_font_size_command),
+    ("FONT COLOR", Phase::Setup, |r, _| {
+        parse_font_color_command(r)
+    }),
+    ("FONT SLANT", Phase::Setup, |r, _| {
+        parse_font_slant_command(r)
+    }),
+    ("FONT BOLD", Phase::Setup, |r, _| parse_font_bold_command(r)),
+    ("FONT STRETCH", Phase::Setup, |r, _| {
+        parse_font_stretch_command(r)
+    }),
+    ("FONT OUTLINE", Phase::Setup, |r, _| {
+        parse_font_outline_command(r)
+    }),
+    (
+        "FONT OUTLINE THICKNESS",
+        Phase::Setup,
+        parse_font_outline_thickness_command,
+    ),
+    ("TYPE", Phase::Setup, parse_type_command),
+    ("WIRE", Phase::Setup, parse_wire_command),
+    ("GROUP", Phase::Setup, parse_group_command),
+    ("COLORSCALE", Phase::Setup, parse_color_scale_command),
+    ("MATCH", Phase::Setup, |r, _| parse_match_command(r)),
+    ("BOX", Phase::Setup, parse_box_theme_command),
+    ("TEXT FONT", Phase::Setup, parse_text_font_command),
+    ("PAGE", Phase::Setup, parse_page_command),
+    ("AUTO ORIENT", Phase::Setup, |_, _| Ok(Command::AutoOrient)),
+    ("DPI", Phase::Setup, |r, _| parse_dpi_command(r)),
+    // Draw Phase Commands
+    ("GOOGLEFONT", Phase::Draw, |r, _| {
+        parse_google_font_command(r)
+    }),
+    ("IMAGE", Phase::Draw, parse_image_command),
+    ("ICON", Phase::Draw, parse_icon_command),
+    ("ANCHOR", Phase::Draw, parse_anchor_command),
+    ("PINSET", Phase::Draw, parse_pinset_command),
+    ("PIN", Phase::Draw, parse_pin_command),
+    ("PINDATA", Phase::Draw, |r, _| parse_pin_data_command(r)),
+    ("PINTEXT", Phase::Draw, |r, _| parse_pin_text_command(r)),
+    ("BOX", Phase::Draw, parse_box_command),
+    ("MESSAGE", Phase::Draw, parse_message_command),
+    ("TEXT", Phase::Draw, |r, _| parse_text_command(r)),
+    ("END MESSAGE", Phase::Draw, |_, _| Ok(Command::EndMessage)),
+    ("PAGEBREAK", Phase::Draw, |_, _| Ok(Command::PageBreak)),
+    ("SHEETINFO", Phase::Draw, parse_sheet_info_command),
+    ("SCALEBAR", Phase::Draw, parse_scale_bar_command),
+    ("GROUP TABLE", Phase::Draw, parse_group_table_command),
+    ("LAYER", Phase::Draw, |r, _| {
+        Ok(Command::Layer {
+            name: optional_field(r, 1),
+        })
+    }),
+    ("ZEBRA", Phase::Draw, |r, _| parse_zebra_command(r)),
+    ("HEADER", Phase::Draw, |r, _| {
+        Ok(Command::Header {
+            theme: optional_field(r, 1),
+        })
+    }),
+    ("SHELL", Phase::Draw, parse_shell_command),
+    ("MIRROR", Phase::Draw, parse_mirror_command),
+    ("WATERMARK", Phase::Draw, parse_watermark_command),
+];
+
+/// A trimmed field, or None when it is missing or empty
+fn optional_field(record: &StringRecord, index: usize) -> Option<String> {
+    record
+        .get(index)
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
 /// Parses a single command from a CSV record
-fn parse_command(
+pub(crate) fn parse_command(
     command_name: String,
     record: &StringRecord,
     phase: Phase,
+    decimal: char,
 ) -> Result<Command, ParserError> {
-    match (command_name.as_str(), phase) {
-        // Setup Phase Commands
-        ("LABELS", Phase::Setup) => parse_label_command(record),
-        ("BORDER COLOR", Phase::Setup) => parse_border_color_command(record),
-        ("BORDER WIDTH", Phase::Setup) => parse_border_width_command(record),
-        ("BORDER OPACITY", Phase::Setup) => parse_border_opacity_command(record),
-        ("FILL COLOR", Phase::Setup) => parse_fill_color_command(record),
-        ("OPACITY", Phase::Setup) => parse_opacity_command(record),
-        ("FONT", Phase::Setup) => parse_font_command(record),
-        ("FONT SIZE", Phase::Setup) => parse_font_size_command(record),
-        ("FONT COLOR", Phase::Setup) => parse_font_color_command(record),
-        ("FONT SLANT", Phase::Setup) => parse_font_slant_command(record),
-        ("FONT BOLD", Phase::Setup) => parse_font_bold_command(record),
-        ("FONT STRETCH", Phase::Setup) => parse_font_stretch_command(record),
-        ("FONT OUTLINE", Phase::Setup) => parse_font_outline_command(record),
-        ("FONT OUTLINE THICKNESS", Phase::Setup) => parse_font_outline_thickness_command(record),
-        ("TYPE", Phase::Setup) => parse_type_command(record),
-        ("WIRE", Phase::Setup) => parse_wire_command(record),
-        ("GROUP", Phase::Setup) => parse_group_command(record),
-        ("BOX", Phase::Setup) => parse_box_theme_command(record),
-        ("TEXT FONT", Phase::Setup) => parse_text_font_command(record),
-        ("PAGE", Phase::Setup) => parse_page_command(record),
-        ("DPI", Phase::Setup) => parse_dpi_command(record),
-
-        // Draw Phase Commands
-        ("GOOGLEFONT", Phase::Draw) => parse_google_font_command(record),
-        ("IMAGE", Phase::Draw) => parse_image_command(record),
-        ("ICON", Phase::Draw) => parse_icon_command(record),
-        ("ANCHOR", Phase::Draw) => parse_anchor_command(record),
-        ("PINSET", Phase::Draw) => parse_pinset_command(record),
-        ("PIN", Phase::Draw) => parse_pin_command(record),
-        ("PINTEXT", Phase::Draw) => parse_pin_text_command(record),
-        ("BOX", Phase::Draw) => parse_box_command(record),
-        ("MESSAGE", Phase::Draw) => parse_message_command(record),
-        ("TEXT", Phase::Draw) => parse_text_command(record),
-        ("END MESSAGE", Phase::Draw) => Ok(Command::EndMessage),
-
-        // Invalid phase for command
-        _ => {
-            println!("{}", command_name);
-            Err(ParserError::InvalidPhase)
-        }
+    let mut known = COMMANDS
+        .iter()
+        .filter(|(name, ..)| *name == command_name)
+        .peekable();
+    if known.peek().is_none() {
+        return Err(ParserError::UnknownCommand(command_name));
+    }
+
+    match known.find(|(_, command_phase, _)| *command_phase == phase) {
+        Some((_, _, parse)) => parse(record, decimal),
+        None => Err(ParserError::InvalidPhase),
+    }
+}
+
+/// Keeps a command the caller named in `ParseOptions::custom_commands` for its own handler
+fn parse_custom_command(command_name: String, record: &StringRecord) -> Command {
+    Command::Custom {
+        name: command_name,
+        fields: record
+            .iter()
+            .skip(1)
+            .map(|s| s.trim().to_string())
+            .collect(),
     }
 }
 
-fn parse_font_outline_thickness_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_font_outline_thickness_command(
+    record: &StringRecord,
+    decimal: char,
+) -> Result<Command, ParserError> {
     if record.len() < 2 {
         return Err(ParserError::ParseError(
             "FONT OUTLINE THICKNESS command requires at least a default value".to_string(),
         ));
     }
 
-    let default = parse_f32(record.get(1).unwrap())?;
-    let pin_type = record.get(2).map(|s| parse_f32(s).ok()).flatten();
-    let group = record.get(3).map(|s| parse_f32(s).ok()).flatten();
+    let default = parse_f32(record.get(1).unwrap(), decimal)?;
+    let pin_type = record.get(2).and_then(|s| parse_f32(s, decimal).ok());
+    let group = record.get(3).and_then(|s| parse_f32(s, decimal).ok());
 
     let mut thickness = Vec::new();
     for i in 4..record.len() {
         if let Some(thickness_str) = record.get(i) {
             if !thickness_str.is_empty() {
-                let size = parse_f32(thickness_str)?;
+                let size = parse_f32(thickness_str, decimal)?;
                 thickness.push(size);
             }
         }
@@ -164,20 +408,17 @@ fn parse_font_outline_command(record: &StringRecord) -> Result<Command, ParserEr
     })
 }
 
-fn parse_border_opacity_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_border_opacity_command(
+    record: &StringRecord,
+    decimal: char,
+) -> Result<Command, ParserError> {
     if record.len() < 2 {
         return Err(ParserError::ParseError(
             "BORDER OPACITY command requires at least a default value".to_string(),
         ));
     }
 
-    let opacity_str = record.get(1).unwrap();
-    let opacity = opacity_str.trim().parse::<f32>().map_err(|_| {
-        ParserError::ParseError(format!(
-            "Failed to parse border opacity '{}' as f32",
-            opacity_str
-        ))
-    })?;
+    let opacity = parse_opacity(record.get(1).unwrap(), decimal)?;
 
     Ok(Command::BorderOpacity { opacity })
 }
@@ -256,14 +497,23 @@ fn parse_border_color_command(record: &StringRecord) -> Result<Command, ParserEr
     })
 }
 
-fn parse_pin_command(record: &StringRecord) -> Result<Command, ParserError> {
+pub(crate) fn parse_pin_command(
+    record: &StringRecord,
+    decimal: char,
+) -> Result<Command, ParserError> {
     if record.len() < 2 {
         return Err(ParserError::ParseError(
             "PIN command requires at least one attribute".to_string(),
         ));
     }
 
-    let wire = record.get(1).and_then(|s| {
+    // A wire may carry its own leader length, e.g. ANALOG:60
+    let (wire_name, leader) = match record.get(1).and_then(|s| s.split_once(':')) {
+        Some((wire_name, leader)) => (Some(wire_name), Some(parse_f32(leader, decimal)?)),
+        None => (record.get(1), None),
+    };
+
+    let wire = wire_name.and_then(|s| {
         let s = s.trim();
         if s.is_empty() {
             None
@@ -274,6 +524,8 @@ fn parse_pin_command(record: &StringRecord) -> Result<Command, ParserError> {
                 "ANALOG" => Some(WireType::Analog),
                 "HS-ANALOG" => Some(WireType::HsAnalog),
                 "POWER" => Some(WireType::Power),
+                "BUS" => Some(WireType::Bus),
+                "GND" => Some(WireType::Gnd),
                 _ => None,
             }
         }
@@ -314,12 +566,61 @@ fn parse_pin_command(record: &StringRecord) -> Result<Command, ParserError> {
         pin_type,
         group,
         attributes,
+        leader,
     })
 }
 
 // Helper functions for parsing values
-// Helper functions for parsing values
-fn parse_f32(value: &str) -> Result<f32, ParserError> {
+/// Rewrites a comma decimal number such as `1.234,5` as `1234.5`, leaving anything else alone
+fn comma_decimal_to_point(field: &str) -> String {
+    let value = field.trim();
+    let digits = value.strip_prefix('-').unwrap_or(value);
+
+    let is_number = !digits.is_empty()
+        && digits
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+        && digits.matches(',').count() <= 1
+        && digits.starts_with(|c: char| c.is_ascii_digit());
+
+    if is_number {
+        value.replace('.', "").replace(',', ".")
+    } else {
+        field.to_string()
+    }
+}
+
+fn parse_pin_data_command(record: &StringRecord) -> Result<Command, ParserError> {
+    if record.len() < 2 {
+        return Err(ParserError::ParseError(
+            "PINDATA command requires a path".to_string(),
+        ));
+    }
+
+    Ok(Command::PinData {
+        path: record.get(1).unwrap().trim().to_string(),
+        delimiter: b',',
+        decimal_separator: '.',
+        max_rows: None,
+    })
+}
+
+/// Parses an opacity, a fraction from 0 to 1 or a percentage with an explicit `%`
+fn parse_opacity(value: &str, decimal: char) -> Result<f32, ParserError> {
+    match value.trim().strip_suffix('%') {
+        Some(percent) => Ok(parse_f32(percent, decimal)? / 100.0),
+        None => parse_f32(value, decimal),
+    }
+}
+
+/// Parses a number written with `decimal` as its decimal separator
+fn parse_f32(value: &str, decimal: char) -> Result<f32, ParserError> {
+    let value = if decimal == ',' {
+        comma_decimal_to_point(value)
+    } else {
+        value.to_string()
+    };
+
     // First, try to parse as f32 directly
     let value = value.trim(); // Trim the input
 
@@ -402,22 +703,22 @@ fn parse_fill_color_command(record: &StringRecord) -> Result<Command, ParserErro
     })
 }
 
-fn parse_opacity_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_opacity_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 2 {
         return Err(ParserError::ParseError(
             "OPACITY command requires at least a default value".to_string(),
         ));
     }
 
-    let default = parse_f32(record.get(1).unwrap())?;
-    let pin_type = record.get(2).map(|s| parse_f32(s).ok()).flatten();
-    let group = record.get(3).map(|s| parse_f32(s).ok()).flatten();
+    let default = parse_opacity(record.get(1).unwrap(), decimal)?;
+    let pin_type = record.get(2).and_then(|s| parse_opacity(s, decimal).ok());
+    let group = record.get(3).and_then(|s| parse_opacity(s, decimal).ok());
 
     let mut opacities = Vec::new();
     for i in 4..record.len() {
         if let Some(opacity_str) = record.get(i) {
             if !opacity_str.is_empty() {
-                let opacity = parse_f32(opacity_str)?;
+                let opacity = parse_opacity(opacity_str, decimal)?;
                 opacities.push(opacity);
             }
         }
@@ -457,22 +758,22 @@ fn parse_font_command(record: &StringRecord) -> Result<Command, ParserError> {
     })
 }
 
-fn parse_font_size_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_font_size_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 2 {
         return Err(ParserError::ParseError(
             "FONT SIZE command requires at least a default value".to_string(),
         ));
     }
 
-    let default = parse_f32(record.get(1).unwrap())?;
-    let pin_type = record.get(2).map(|s| parse_f32(s).ok()).flatten();
-    let group = record.get(3).map(|s| parse_f32(s).ok()).flatten();
+    let default = parse_f32(record.get(1).unwrap(), decimal)?;
+    let pin_type = record.get(2).and_then(|s| parse_f32(s, decimal).ok());
+    let group = record.get(3).and_then(|s| parse_f32(s, decimal).ok());
 
     let mut sizes = Vec::new();
     for i in 4..record.len() {
         if let Some(size_str) = record.get(i) {
             if !size_str.is_empty() {
-                let size = parse_f32(size_str)?;
+                let size = parse_f32(size_str, decimal)?;
                 sizes.push(size);
             }
         }
@@ -520,14 +821,8 @@ fn parse_font_slant_command(record: &StringRecord) -> Result<Command, ParserErro
     }
 
     let default = parse_font_slant(record.get(1).unwrap().trim())?;
-    let pin_type = record
-        .get(2)
-        .map(|s| parse_font_slant(s.trim()).ok())
-        .flatten();
-    let group = record
-        .get(3)
-        .map(|s| parse_font_slant(s.trim()).ok())
-        .flatten();
+    let pin_type = record.get(2).and_then(|s| parse_font_slant(s.trim()).ok());
+    let group = record.get(3).and_then(|s| parse_font_slant(s.trim()).ok());
 
     let mut slants = Vec::new();
     for i in 4..record.len() {
@@ -557,12 +852,10 @@ fn parse_font_bold_command(record: &StringRecord) -> Result<Command, ParserError
     let default = parse_font_boldness(record.get(1).unwrap().trim())?;
     let pin_type = record
         .get(2)
-        .map(|s| parse_font_boldness(s.trim()).ok())
-        .flatten();
+        .and_then(|s| parse_font_boldness(s.trim()).ok());
     let group = record
         .get(3)
-        .map(|s| parse_font_boldness(s.trim()).ok())
-        .flatten();
+        .and_then(|s| parse_font_boldness(s.trim()).ok());
 
     let mut boldness = Vec::new();
     for i in 4..record.len() {
@@ -592,12 +885,10 @@ fn parse_font_stretch_command(record: &StringRecord) -> Result<Command, ParserEr
     let default = parse_font_stretch(record.get(1).unwrap().trim())?;
     let pin_type = record
         .get(2)
-        .map(|s| parse_font_stretch(s.trim()).ok())
-        .flatten();
+        .and_then(|s| parse_font_stretch(s.trim()).ok());
     let group = record
         .get(3)
-        .map(|s| parse_font_stretch(s.trim()).ok())
-        .flatten();
+        .and_then(|s| parse_font_stretch(s.trim()).ok());
 
     let mut stretches = Vec::new();
     for i in 4..record.len() {
@@ -617,7 +908,7 @@ fn parse_font_stretch_command(record: &StringRecord) -> Result<Command, ParserEr
     })
 }
 
-fn parse_type_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_type_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 4 {
         return Err(ParserError::ParseError(
             "TYPE command requires pin type, color, and opacity".to_string(),
@@ -638,16 +929,28 @@ fn parse_type_command(record: &StringRecord) -> Result<Command, ParserError> {
     };
 
     let color = record.get(2).unwrap().trim().to_string();
-    let opacity = parse_f32(record.get(3).unwrap())?;
+    let opacity = parse_opacity(record.get(3).unwrap(), decimal)?;
+    let arrow_str = record.get(4).unwrap_or("").trim().to_uppercase();
+    let arrow = match arrow_str.as_str() {
+        "TRUE" | "YES" | "1" | "ARROW" => true,
+        "" | "FALSE" | "NO" | "0" => false,
+        _ => {
+            return Err(ParserError::ParseError(format!(
+                "Invalid arrow value: {}",
+                arrow_str
+            )));
+        }
+    };
 
     Ok(Command::Type {
         pin_type,
         color,
         opacity,
+        arrow,
     })
 }
 
-fn parse_wire_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_wire_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 5 {
         return Err(ParserError::ParseError(
             "WIRE command requires wire type, color, opacity, and thickness".to_string(),
@@ -661,6 +964,8 @@ fn parse_wire_command(record: &StringRecord) -> Result<Command, ParserError> {
         "ANALOG" => WireType::Analog,
         "HS-ANALOG" => WireType::HsAnalog,
         "POWER" => WireType::Power,
+        "BUS" => WireType::Bus,
+        "GND" => WireType::Gnd,
         _ => {
             return Err(ParserError::ParseError(format!(
                 "Invalid wire type: {}",
@@ -670,18 +975,64 @@ fn parse_wire_command(record: &StringRecord) -> Result<Command, ParserError> {
     };
 
     let color = record.get(2).unwrap().trim().to_string();
-    let opacity = parse_f32(record.get(3).unwrap())?;
-    let thickness = parse_f32(record.get(4).unwrap())?;
+    let opacity = parse_opacity(record.get(3).unwrap(), decimal)?;
+    let thickness = parse_f32(record.get(4).unwrap(), decimal)?;
+    let amplitude = record.get(5).and_then(|s| parse_f32(s, decimal).ok());
+    let terminal = record
+        .get(6)
+        .filter(|s| !s.trim().is_empty())
+        .map(parse_terminal)
+        .transpose()?;
+    let start_gap = record
+        .get(7)
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_f32(s, decimal))
+        .transpose()?;
+    let stroke_opacity = record
+        .get(8)
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_opacity(s, decimal))
+        .transpose()?;
+    let dash = record
+        .get(9)
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(parse_dash)
+        .transpose()?;
 
     Ok(Command::Wire {
         wire_type,
         color,
         opacity,
         thickness,
+        amplitude,
+        terminal,
+        start_gap,
+        stroke_opacity,
+        dash,
     })
 }
 
-fn parse_group_command(record: &StringRecord) -> Result<Command, ParserError> {
+/// A dash pattern of dash and gap lengths, separated by spaces as commas split fields
+fn parse_dash(value: &str) -> Result<String, ParserError> {
+    let lengths = value
+        .split_whitespace()
+        .map(|length| length.parse::<f32>().ok().filter(|length| *length >= 0.0))
+        .collect::<Option<Vec<f32>>>();
+    match lengths {
+        Some(lengths) if !lengths.is_empty() => Ok(lengths
+            .iter()
+            .map(|length| length.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")),
+        _ => Err(ParserError::ParseError(format!(
+            "Invalid dash pattern: {}",
+            value
+        ))),
+    }
+}
+
+fn parse_group_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 4 {
         return Err(ParserError::ParseError(
             "GROUP command requires name, color, and opacity".to_string(),
@@ -690,16 +1041,22 @@ fn parse_group_command(record: &StringRecord) -> Result<Command, ParserError> {
 
     let name = record.get(1).unwrap().trim().to_string();
     let color = record.get(2).unwrap().trim().to_string();
-    let opacity = parse_f32(record.get(3).unwrap())?;
+    let opacity = parse_opacity(record.get(3).unwrap(), decimal)?;
+    let shape = record
+        .get(4)
+        .filter(|s| !s.trim().is_empty())
+        .map(parse_group_shape)
+        .transpose()?;
 
     Ok(Command::Group {
         name,
         color,
         opacity,
+        shape,
     })
 }
 
-fn parse_box_theme_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_box_theme_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 13 {
         return Err(ParserError::ParseError(
             "BOX theme command requires all parameters".to_string(),
@@ -708,16 +1065,21 @@ fn parse_box_theme_command(record: &StringRecord) -> Result<Command, ParserError
 
     let name = record.get(1).unwrap().trim().to_string();
     let border_color = record.get(2).unwrap().trim().to_string();
-    let border_opacity = parse_f32(record.get(3).unwrap())?;
+    let border_opacity = parse_opacity(record.get(3).unwrap(), decimal)?;
     let fill_color = record.get(4).unwrap().trim().to_string();
-    let fill_opacity = parse_f32(record.get(5).unwrap())?;
-    let line_width = parse_f32(record.get(6).unwrap())?;
-    let box_width = parse_size(record.get(7).unwrap())?;
-    let box_height = parse_size(record.get(8).unwrap())?;
-    let box_cr_x = parse_f32(record.get(9).unwrap())?;
-    let box_cr_y = parse_f32(record.get(10).unwrap())?;
-    let box_skew = parse_f32(record.get(11).unwrap())?;
-    let box_skew_offset = parse_f32(record.get(12).unwrap())?;
+    let fill_opacity = parse_opacity(record.get(5).unwrap(), decimal)?;
+    let line_width = parse_f32(record.get(6).unwrap(), decimal)?;
+    let box_width = parse_size(record.get(7).unwrap(), decimal)?;
+    let box_height = parse_size(record.get(8).unwrap(), decimal)?;
+    let box_cr_x = parse_f32(record.get(9).unwrap(), decimal)?;
+    let box_cr_y = parse_f32(record.get(10).unwrap(), decimal)?;
+    let box_skew = parse_f32(record.get(11).unwrap(), decimal)?;
+    let box_skew_offset = parse_f32(record.get(12).unwrap(), decimal)?;
+    let shape = record
+        .get(13)
+        .filter(|s| !s.trim().is_empty())
+        .map(parse_box_shape)
+        .transpose()?;
 
     Ok(Command::BoxTheme {
         name,
@@ -732,10 +1094,11 @@ fn parse_box_theme_command(record: &StringRecord) -> Result<Command, ParserError
         box_cr_y,
         box_skew,
         box_skew_offset,
+        shape,
     })
 }
 
-fn parse_text_font_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_text_font_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 9 {
         return Err(ParserError::ParseError(
             "TEXT FONT command requires all parameters".to_string(),
@@ -744,7 +1107,7 @@ fn parse_text_font_command(record: &StringRecord) -> Result<Command, ParserError
 
     let theme_name = record.get(1).unwrap().trim().to_string();
     let font = record.get(2).unwrap().trim().to_string();
-    let size = parse_f32(record.get(3).unwrap())?;
+    let size = parse_f32(record.get(3).unwrap(), decimal)?;
     let outline_color = record.get(4).unwrap().trim().to_string();
     let color = record.get(5).unwrap().trim().to_string();
     let slant = parse_font_slant(record.get(6).unwrap().trim())?;
@@ -763,7 +1126,7 @@ fn parse_text_font_command(record: &StringRecord) -> Result<Command, ParserError
     })
 }
 
-fn parse_page_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_page_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 2 {
         return Err(ParserError::ParseError(
             "PAGE command requires a page name".to_string(),
@@ -772,7 +1135,21 @@ fn parse_page_command(record: &StringRecord) -> Result<Command, ParserError> {
 
     let page_name = record.get(1).unwrap().trim().to_string();
 
-    Ok(Command::Page { page_name })
+    let size = if page_name == "CUSTOM" {
+        if record.len() < 4 {
+            return Err(ParserError::ParseError(
+                "PAGE CUSTOM requires width and height parameters".to_string(),
+            ));
+        }
+        Some((
+            parse_f32(record.get(2).unwrap(), decimal)?,
+            parse_f32(record.get(3).unwrap(), decimal)?,
+        ))
+    } else {
+        None
+    };
+
+    Ok(Command::Page { page_name, size })
 }
 
 fn parse_dpi_command(record: &StringRecord) -> Result<Command, ParserError> {
@@ -796,10 +1173,10 @@ fn parse_google_font_command(record: &StringRecord) -> Result<Command, ParserErr
 
     let link = record.get(1).unwrap().trim().to_string();
 
-    Ok(Command::GoogleFont { _link: link })
+    Ok(Command::GoogleFont { link })
 }
 
-fn parse_image_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_image_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 6 {
         return Err(ParserError::ParseError(
             "IMAGE command requires name, x, y, w, h parameters".to_string(),
@@ -813,54 +1190,72 @@ fn parse_image_command(record: &StringRecord) -> Result<Command, ParserError> {
     let x = record
         .get(2)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
     // Parse width and height as optional size values
     let y = record
         .get(3)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
 
     // Parse width and height as optional size values
     let w = record
         .get(4)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
     let h = record
         .get(5)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
 
     // Parse the optional crop parameters
     let cx = record
         .get(6)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
     let cy = record
         .get(7)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
     let cw = record
         .get(8)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
     let ch = record
         .get(9)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
 
     // Parse rotation
     let rot = record
         .get(10)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_f32(s))
+        .map(|s| parse_f32(s, decimal))
+        .transpose()?;
+
+    // Parse the optional rotation pivot
+    let pivot_x = record
+        .get(11)
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_size(s, decimal))
+        .transpose()?;
+    let pivot_y = record
+        .get(12)
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_size(s, decimal))
+        .transpose()?;
+
+    let anchor = record
+        .get(13)
+        .filter(|s| !s.trim().is_empty())
+        .map(parse_image_anchor)
         .transpose()?;
 
     Ok(Command::Image {
@@ -874,10 +1269,34 @@ fn parse_image_command(record: &StringRecord) -> Result<Command, ParserError> {
         cw,
         ch,
         rot,
+        pivot_x,
+        pivot_y,
+        anchor,
     })
 }
 
-fn parse_icon_command(record: &StringRecord) -> Result<Command, ParserError> {
+/// Reads an anchor such as "TOP LEFT", "BOTTOM-RIGHT", "TOP" or "CENTER"
+fn parse_image_anchor(value: &str) -> Result<(JustifyX, JustifyY), ParserError> {
+    let mut anchor = (JustifyX::Center, JustifyY::Center);
+    for word in value.split([' ', '-', '_']).filter(|word| !word.is_empty()) {
+        match word.to_uppercase().as_str() {
+            "LEFT" => anchor.0 = JustifyX::Left,
+            "RIGHT" => anchor.0 = JustifyX::Right,
+            "TOP" => anchor.1 = JustifyY::Top,
+            "BOTTOM" => anchor.1 = JustifyY::Bottom,
+            "CENTER" => {}
+            _ => {
+                return Err(ParserError::ParseError(format!(
+                    "Invalid image anchor: {}",
+                    value.trim()
+                )));
+            }
+        }
+    }
+    Ok(anchor)
+}
+
+fn parse_icon_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 6 {
         return Err(ParserError::ParseError(
             "ICON command requires name, x, y, w, h parameters".to_string(),
@@ -890,33 +1309,33 @@ fn parse_icon_command(record: &StringRecord) -> Result<Command, ParserError> {
     let x = record
         .get(2)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
 
     let y = record
         .get(3)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
 
     // Parse width and height as size values
     let w = record
         .get(4)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
 
     let h = record
         .get(5)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_size(s))
+        .map(|s| parse_size(s, decimal))
         .transpose()?;
 
     // Parse rotation as an optional parameter
     let rot = record
         .get(6)
         .filter(|s| !s.trim().is_empty())
-        .map(|s| parse_f32(s))
+        .map(|s| parse_f32(s, decimal))
         .transpose()?;
 
     Ok(Command::Icon {
@@ -929,20 +1348,170 @@ fn parse_icon_command(record: &StringRecord) -> Result<Command, ParserError> {
     })
 }
 
-fn parse_anchor_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_anchor_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 3 {
         return Err(ParserError::ParseError(
             "ANCHOR command requires x and y parameters".to_string(),
         ));
     }
 
-    let x = parse_f32(record.get(1).unwrap())?;
-    let y = parse_f32(record.get(2).unwrap())?;
+    let x = parse_f32(record.get(1).unwrap(), decimal)?;
+    let y = parse_f32(record.get(2).unwrap(), decimal)?;
 
     Ok(Command::Anchor { x, y })
 }
 
-fn parse_pinset_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_sheet_info_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
+    if record.len() < 3 {
+        return Err(ParserError::ParseError(
+            "SHEETINFO command requires x and y parameters".to_string(),
+        ));
+    }
+
+    let x = parse_f32(record.get(1).unwrap(), decimal)?;
+    let y = parse_f32(record.get(2).unwrap(), decimal)?;
+
+    Ok(Command::SheetInfo { x, y })
+}
+
+fn parse_scale_bar_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
+    if record.len() < 4 {
+        return Err(ParserError::ParseError(
+            "SCALEBAR command requires x, y and length parameters".to_string(),
+        ));
+    }
+
+    let x = parse_f32(record.get(1).unwrap(), decimal)?;
+    let y = parse_f32(record.get(2).unwrap(), decimal)?;
+    let length_mm = parse_f32(record.get(3).unwrap(), decimal)?;
+
+    Ok(Command::ScaleBar { x, y, length_mm })
+}
+
+fn parse_group_table_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
+    if record.len() < 3 {
+        return Err(ParserError::ParseError(
+            "GROUP TABLE command requires x and y parameters".to_string(),
+        ));
+    }
+
+    let x = parse_f32(record.get(1).unwrap(), decimal)?;
+    let y = parse_f32(record.get(2).unwrap(), decimal)?;
+
+    Ok(Command::GroupTable { x, y })
+}
+
+fn parse_color_scale_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
+    if record.len() < 5 {
+        return Err(ParserError::ParseError(
+            "COLORSCALE command requires min, max, low color and high color".to_string(),
+        ));
+    }
+
+    let min = parse_f32(record.get(1).unwrap(), decimal)?;
+    let max = parse_f32(record.get(2).unwrap(), decimal)?;
+    if max <= min {
+        return Err(ParserError::ParseError(format!(
+            "COLORSCALE max {} must be greater than min {}",
+            max, min
+        )));
+    }
+
+    Ok(Command::ColorScale {
+        min,
+        max,
+        color_low: record.get(3).unwrap().trim().to_string(),
+        color_high: record.get(4).unwrap().trim().to_string(),
+        label: record
+            .get(5)
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string()),
+    })
+}
+
+fn parse_match_command(record: &StringRecord) -> Result<Command, ParserError> {
+    if record.len() < 3 {
+        return Err(ParserError::ParseError(
+            "MATCH command requires a pattern and a theme".to_string(),
+        ));
+    }
+
+    let pattern = record.get(1).unwrap().trim().to_string();
+    Regex::new(&pattern).map_err(|e| {
+        ParserError::ParseError(format!("Invalid MATCH pattern {}: {}", pattern, e))
+    })?;
+
+    Ok(Command::Match {
+        pattern,
+        theme: record.get(2).unwrap().trim().to_string(),
+    })
+}
+
+fn parse_zebra_command(record: &StringRecord) -> Result<Command, ParserError> {
+    if record.len() < 2 {
+        return Err(ParserError::ParseError(
+            "ZEBRA command requires a color".to_string(),
+        ));
+    }
+
+    Ok(Command::Zebra {
+        color: record.get(1).unwrap().trim().to_string(),
+    })
+}
+
+fn parse_shell_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
+    if record.len() < 2 {
+        return Err(ParserError::ParseError(
+            "SHELL command requires a box theme".to_string(),
+        ));
+    }
+
+    let theme = record.get(1).unwrap().trim().to_string();
+    let padding = record
+        .get(2)
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_f32(s, decimal))
+        .transpose()?;
+
+    Ok(Command::Shell { theme, padding })
+}
+
+fn parse_mirror_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
+    if record.len() < 2 {
+        return Err(ParserError::ParseError(
+            "MIRROR command requires an axis offset".to_string(),
+        ));
+    }
+
+    let offset = parse_f32(record.get(1).unwrap(), decimal)?;
+
+    Ok(Command::Mirror { offset })
+}
+
+fn parse_watermark_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
+    if record.len() < 3 {
+        return Err(ParserError::ParseError(
+            "WATERMARK command requires text and opacity".to_string(),
+        ));
+    }
+
+    let text = record.get(1).unwrap().trim().to_string();
+    let opacity = parse_opacity(record.get(2).unwrap(), decimal)?;
+    let angle = record
+        .get(3)
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_f32(s, decimal))
+        .transpose()?;
+
+    Ok(Command::Watermark {
+        text,
+        opacity,
+        angle,
+    })
+}
+
+fn parse_pinset_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 11 {
         return Err(ParserError::ParseError(
             "PINSET command requires all parameters".to_string(),
@@ -955,6 +1524,7 @@ fn parse_pinset_command(record: &StringRecord) -> Result<Command, ParserError> {
         "RIGHT" => Side::Right,
         "TOP" => Side::Top,
         "BOTTOM" => Side::Bottom,
+        "RADIAL" => Side::Radial,
         _ => {
             return Err(ParserError::ParseError(format!(
                 "Invalid side: {}",
@@ -977,12 +1547,56 @@ fn parse_pinset_command(record: &StringRecord) -> Result<Command, ParserError> {
 
     let justify_x = parse_justify_x(record.get(3).unwrap().trim())?;
     let justify_y = parse_justify_y(record.get(4).unwrap().trim())?;
-    let line_step = parse_f32(record.get(5).unwrap())?;
-    let pin_width = parse_f32(record.get(6).unwrap())?;
-    let group_width = parse_f32(record.get(7).unwrap())?;
-    let leader_offset = parse_f32(record.get(8).unwrap())?;
-    let column_gap = parse_f32(record.get(9).unwrap())?;
-    let leader_h_step = parse_f32(record.get(10).unwrap())?;
+    let line_step = parse_f32(record.get(5).unwrap(), decimal)?;
+    let pin_width = parse_f32(record.get(6).unwrap(), decimal)?;
+    let group_width = parse_f32(record.get(7).unwrap(), decimal)?;
+    let leader_offset = parse_f32(record.get(8).unwrap(), decimal)?;
+    // Only an explicit % makes the gap relative to the box width
+    let column_gap = record.get(9).unwrap().trim();
+    let (column_gap, column_gap_percent) = match column_gap.strip_suffix('%') {
+        Some(percent) => (parse_f32(percent, decimal)?, true),
+        None => (parse_f32(column_gap, decimal)?, false),
+    };
+    let leader_h_step = parse_f32(record.get(10).unwrap(), decimal)?;
+    let angle = record
+        .get(11)
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_f32(s, decimal))
+        .transpose()?;
+    let units = record
+        .get(12)
+        .filter(|s| !s.trim().is_empty())
+        .map(parse_unit)
+        .transpose()?;
+    let advance = record
+        .get(13)
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_f32(s, decimal))
+        .transpose()?;
+    let first_number = record
+        .get(14)
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| {
+            s.trim()
+                .parse::<u32>()
+                .map_err(|_| ParserError::ParseError(format!("Invalid pin number: {}", s.trim())))
+        })
+        .transpose()?;
+    let box_gap = record
+        .get(15)
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| parse_f32(s, decimal))
+        .transpose()?;
+    let frame = record
+        .get(16)
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+    let title = record
+        .get(17)
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
 
     Ok(Command::PinSet {
         side,
@@ -994,7 +1608,15 @@ fn parse_pinset_command(record: &StringRecord) -> Result<Command, ParserError> {
         group_width,
         leader_offset,
         column_gap,
+        column_gap_percent,
         leader_h_step,
+        angle,
+        units,
+        advance,
+        first_number,
+        box_gap,
+        frame,
+        title,
     })
 }
 
@@ -1016,6 +1638,8 @@ fn parse_pin_text_command(record: &StringRecord) -> Result<Command, ParserError>
                 "ANALOG" => Some(WireType::Analog),
                 "HS-ANALOG" => Some(WireType::HsAnalog),
                 "POWER" => Some(WireType::Power),
+                "BUS" => Some(WireType::Bus),
+                "GND" => Some(WireType::Gnd),
                 _ => None,
             }
         }
@@ -1057,6 +1681,12 @@ fn parse_pin_text_command(record: &StringRecord) -> Result<Command, ParserError>
 
     let message = record.get(6).unwrap_or("").trim().to_string();
 
+    let color = record
+        .get(7)
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+
     Ok(Command::PinText {
         wire,
         pin_type,
@@ -1064,10 +1694,11 @@ fn parse_pin_text_command(record: &StringRecord) -> Result<Command, ParserError>
         msg_theme,
         label,
         message,
+        color,
     })
 }
 
-fn parse_box_command(record: &StringRecord) -> Result<Command, ParserError> {
+fn parse_box_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
     if record.len() < 4 {
         return Err(ParserError::ParseError(
             "BOX command requires theme, x, y parameters".to_string(),
@@ -1075,14 +1706,15 @@ fn parse_box_command(record: &StringRecord) -> Result<Command, ParserError> {
     }
 
     let theme = record.get(1).unwrap().trim().to_string();
-    let x = parse_f32(record.get(2).unwrap())?;
-    let y = parse_f32(record.get(3).unwrap())?;
+    let x = parse_f32(record.get(2).unwrap(), decimal)?;
+    let y = parse_f32(record.get(3).unwrap(), decimal)?;
 
-    let box_width = record.get(4).and_then(|s| parse_size(s).ok());
-    let box_height = record.get(5).and_then(|s| parse_size(s).ok());
+    let box_width = record.get(4).and_then(|s| parse_size(s, decimal).ok());
+    let box_height = record.get(5).and_then(|s| parse_size(s, decimal).ok());
     let x_justify = record.get(6).and_then(|s| parse_justify_x(s.trim()).ok());
     let y_justify = record.get(7).and_then(|s| parse_justify_y(s.trim()).ok());
     let message = record.get(8).map(|s| s.trim().to_string());
+    let text_anchor = record.get(9).and_then(|s| parse_justify_x(s.trim()).ok());
 
     Ok(Command::Box {
         theme,
@@ -1093,17 +1725,28 @@ fn parse_box_command(record: &StringRecord) -> Result<Command, ParserError> {
         x_justify,
         y_justify,
         message,
+        text_anchor,
     })
 }
 
-fn parse_message_command(record: &StringRecord) -> Result<Command, ParserError> {
-    let x = record.get(1).and_then(|s| parse_f32(s).ok());
-    let y = record.get(2).and_then(|s| parse_f32(s).ok());
-    let line_step = record.get(3).and_then(|s| parse_f32(s).ok());
+fn parse_message_command(record: &StringRecord, decimal: char) -> Result<Command, ParserError> {
+    let x = record.get(1).and_then(|s| parse_f32(s, decimal).ok());
+    let y = record.get(2).and_then(|s| parse_f32(s, decimal).ok());
+    let line_step = record.get(3).and_then(|s| parse_f32(s, decimal).ok());
     let font = record.get(4).map(|s| s.trim().to_string());
-    let font_size = record.get(5).and_then(|s| parse_f32(s).ok());
+    let font_size = record.get(5).and_then(|s| parse_f32(s, decimal).ok());
     let x_justify = record.get(6).and_then(|s| parse_justify_x(s.trim()).ok());
     let y_justify = record.get(7).and_then(|s| parse_justify_y(s.trim()).ok());
+    let tab_stops = record
+        .get(8)
+        .filter(|s| !s.trim().is_empty())
+        .and_then(|s| {
+            s.split_whitespace()
+                .map(|s| parse_f32(s, decimal))
+                .collect::<Result<_, _>>()
+                .ok()
+        });
+    let max_height = record.get(9).and_then(|s| parse_f32(s, decimal).ok());
 
     Ok(Command::Message {
         x,
@@ -1113,6 +1756,8 @@ fn parse_message_command(record: &StringRecord) -> Result<Command, ParserError>
         font_size,
         x_justify,
         y_justify,
+        tab_stops,
+        max_height,
     })
 }
 
@@ -1127,7 +1772,18 @@ fn parse_text_command(record: &StringRecord) -> Result<Command, ParserError> {
     let color = record.get(2).unwrap().trim().to_string();
     let message = record.get(3).unwrap().trim().to_string();
 
-    let new_line = record.get(4).is_some();
+    // Only an explicit flag advances the line, a trailing comma alone does not
+    let new_line_str = record.get(4).unwrap_or("").trim().to_uppercase();
+    let new_line = match new_line_str.as_str() {
+        "NL" | "NEWLINE" | "TRUE" | "YES" | "1" => true,
+        "" | "FALSE" | "NO" | "0" => false,
+        _ => {
+            return Err(ParserError::ParseError(format!(
+                "Invalid TEXT new line flag: {}",
+                new_line_str
+            )));
+        }
+    };
 
     Ok(Command::Text {
         edge_color,
@@ -1195,6 +1851,61 @@ fn parse_font_stretch(value: &str) -> Result<FontStretch, ParserError> {
     }
 }
 
+fn parse_terminal(value: &str) -> Result<Terminal, ParserError> {
+    let value = value.trim(); // Trim the input
+    match value.to_uppercase().as_str() {
+        "NONE" => Ok(Terminal::None),
+        "DOT" => Ok(Terminal::Dot),
+        "CIRCLE" => Ok(Terminal::Circle),
+        _ => Err(ParserError::ParseError(format!(
+            "Invalid terminal: {}",
+            value
+        ))),
+    }
+}
+
+fn parse_group_shape(value: &str) -> Result<GroupShape, ParserError> {
+    let value = value.trim(); // Trim the input
+    match value.to_uppercase().as_str() {
+        "CIRCLE" => Ok(GroupShape::Circle),
+        "SQUARE" => Ok(GroupShape::Square),
+        "HEXAGON" | "HEX" => Ok(GroupShape::Hexagon),
+        "DIAMOND" => Ok(GroupShape::Diamond),
+        _ => Err(ParserError::ParseError(format!(
+            "Invalid group shape: {}",
+            value
+        ))),
+    }
+}
+
+fn parse_box_shape(value: &str) -> Result<BoxShape, ParserError> {
+    let value = value.trim(); // Trim the input
+    let upper = value.to_uppercase();
+    let mut words = upper.split_whitespace();
+
+    match (words.next(), words.next()) {
+        (Some("RECT" | "RECTANGLE"), None) => Ok(BoxShape::Rectangle),
+        (Some("CHAMFER" | "CHAMFERED"), None) => Ok(BoxShape::Chamfered),
+        (Some("NOTCH" | "NOTCHED"), None | Some("TL")) => Ok(BoxShape::Notched(Corner::TopLeft)),
+        (Some("NOTCH" | "NOTCHED"), Some("TR")) => Ok(BoxShape::Notched(Corner::TopRight)),
+        (Some("NOTCH" | "NOTCHED"), Some("BL")) => Ok(BoxShape::Notched(Corner::BottomLeft)),
+        (Some("NOTCH" | "NOTCHED"), Some("BR")) => Ok(BoxShape::Notched(Corner::BottomRight)),
+        _ => Err(ParserError::ParseError(format!(
+            "Invalid box shape: {}",
+            value
+        ))),
+    }
+}
+
+fn parse_unit(value: &str) -> Result<Unit, ParserError> {
+    let value = value.trim(); // Trim the input
+    match value.to_uppercase().as_str() {
+        "PX" => Ok(Unit::Px),
+        "MM" => Ok(Unit::Mm),
+        _ => Err(ParserError::ParseError(format!("Invalid unit: {}", value))),
+    }
+}
+
 fn _parse_side(value: &str) -> Result<Side, ParserError> {
     let value = value.trim(); // Trim the input
     match value.to_uppercase().as_str() {
@@ -1202,11 +1913,12 @@ fn _parse_side(value: &str) -> Result<Side, ParserError> {
         "RIGHT" => Ok(Side::Right),
         "TOP" => Ok(Side::Top),
         "BOTTOM" => Ok(Side::Bottom),
+        "RADIAL" => Ok(Side::Radial),
         _ => Err(ParserError::ParseError(format!("Invalid side: {}", value))),
     }
 }
 
-fn parse_size(value: &str) -> Result<f32, ParserError> {
+fn parse_size(value: &str, decimal: char) -> Result<f32, ParserError> {
     let value = value.trim(); // Trim the input
     if value.is_empty() {
         return Err(ParserError::ParseError("Empty size value".to_string()));
@@ -1215,20 +1927,20 @@ fn parse_size(value: &str) -> Result<f32, ParserError> {
     // Check if it's a percentage
     if value.ends_with('%') {
         if let Some(percent_str) = value.trim().strip_suffix('%') {
-            if let Ok(percent_val) = percent_str.parse::<f32>() {
+            if let Ok(percent_val) = parse_f32(percent_str, decimal) {
                 // Convert percentage to a value between 0.0 and 0.9999
                 // where 0.9999 represents 100%
                 let normalized = (0.9999 * f32::min(percent_val, 100.0)) / 100.0;
                 return Ok(normalized);
             }
         }
-        return Err(ParserError::ParseError(format!(
+        Err(ParserError::ParseError(format!(
             "Failed to parse percentage: {}",
             value
-        )));
+        )))
     } else {
         // Try to parse as a regular number
-        parse_f32(value)
+        parse_f32(value, decimal)
     }
 }
 
@@ -1255,7 +1967,7 @@ mod tests {
         ]);
 
         // Parse the record
-        let result = parse_image_command(&record);
+        let result = parse_image_command(&record, '.');
 
         // Verify the result is Ok
         assert!(
@@ -1276,6 +1988,7 @@ mod tests {
             cw,
             ch,
             rot,
+            ..
         }) = result
         {
             // Check the name
@@ -1318,7 +2031,7 @@ mod tests {
         ]);
 
         // Parse the record
-        let result = parse_image_command(&record);
+        let result = parse_image_command(&record, '.');
 
         // Verify the result is Ok
         assert!(
@@ -1339,6 +2052,7 @@ mod tests {
             cw,
             ch,
             rot,
+            ..
         }) = result
         {
             // Check the name
@@ -1379,4 +2093,400 @@ mod tests {
             panic!("Expected Command::Image, got something else: {:?}", result);
         }
     }
+
+    #[test]
+    fn test_parse_custom_command() {
+        let text = "DRAW\nMYCMD, tap , 3\n";
+        let options = ParseOptions {
+            custom_commands: vec!["mycmd".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_csv_reader_with_options(text.as_bytes(), &options).unwrap()[1],
+            Command::Custom {
+                name: "MYCMD".to_string(),
+                fields: vec!["tap".to_string(), "3".to_string()],
+            }
+        );
+
+        // Names nobody registered are misspellings, reported with their line
+        let error = parse_csv_str("DRAW\nANCHOR, 1, 2\nANCHR, 3, 4\n").unwrap_err();
+        assert!(
+            matches!(
+                &error,
+                ParserError::AtLine { line: 3, source }
+                    if matches!(source.as_ref(), ParserError::UnknownCommand(name) if name == "ANCHR")
+            ),
+            "{}",
+            error
+        );
+
+        // Built-in commands in the wrong phase are still rejected
+        let record = StringRecord::from(vec!["DPI", "300"]);
+        assert!(matches!(
+            parse_command("DPI".to_string(), &record, Phase::Draw, '.'),
+            Err(ParserError::InvalidPhase)
+        ));
+    }
+
+    #[test]
+    fn test_parse_opacity_percent() {
+        let record = StringRecord::from(vec!["OPACITY", "50%", "0.25", "", "1.5"]);
+        assert_eq!(
+            parse_command("OPACITY".to_string(), &record, Phase::Setup, '.').unwrap(),
+            Command::Opacity {
+                default: 0.5,
+                pin_type: Some(0.25),
+                group: None,
+                opacities: vec![1.5],
+            }
+        );
+
+        // Percentages follow the decimal separator too
+        let record = StringRecord::from(vec!["BORDER OPACITY", " 12,5% "]);
+        assert_eq!(
+            parse_command("BORDER OPACITY".to_string(), &record, Phase::Setup, ',').unwrap(),
+            Command::BorderOpacity { opacity: 0.125 }
+        );
+        assert!(parse_opacity("%", '.').is_err());
+    }
+
+    #[test]
+    fn test_parse_text_new_line() {
+        let new_line = |fields: Vec<&str>| match parse_text_command(&StringRecord::from(fields)) {
+            Ok(Command::Text { new_line, .. }) => new_line,
+            other => panic!("Unexpected result: {:?}", other),
+        };
+
+        assert!(!new_line(vec!["TEXT", "none", "black", "Hello"]));
+        assert!(!new_line(vec!["TEXT", "none", "black", "Hello", ""]));
+        assert!(!new_line(vec!["TEXT", "none", "black", "Hello", " false"]));
+        assert!(new_line(vec!["TEXT", "none", "black", "Hello", " NL"]));
+        assert!(new_line(vec!["TEXT", "none", "black", "Hello", "newline"]));
+        assert!(new_line(vec!["TEXT", "none", "black", "Hello", "1"]));
+
+        let record = StringRecord::from(vec!["TEXT", "none", "black", "Hello", "maybe"]);
+        assert!(parse_text_command(&record).is_err());
+    }
+
+    #[test]
+    fn test_quoted_fields() {
+        let path = std::env::temp_dir().join("pinout_quoted_fields.csv");
+        std::fs::write(
+            &path,
+            "DRAW\nPIN, DIGITAL, IO, ,\"3.3V, 5V tolerant\",\"12\"\" \"\"HDR\"\"\"\nPIN, DIGITAL, IO, ,\"GPIO2, ADC\",\"Boot\nstrap\"\n",
+        )
+        .unwrap();
+
+        let commands = parse_csv_file(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        match &commands[1] {
+            Command::Pin { attributes, .. } => assert_eq!(
+                attributes,
+                &vec!["3.3V, 5V tolerant".to_string(), "12\" \"HDR\"".to_string()]
+            ),
+            other => panic!("Unexpected command: {:?}", other),
+        }
+
+        // A line break inside quotes stays in the field, for text_box to break the line on
+        match &commands[2] {
+            Command::Pin { attributes, .. } => assert_eq!(
+                attributes,
+                &vec!["GPIO2, ADC".to_string(), "Boot\nstrap".to_string()]
+            ),
+            other => panic!("Unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_pin_leader_length() {
+        let pin = |wire: &str| {
+            parse_pin_command(&StringRecord::from(vec!["PIN", wire, "IO", "", "D1"]), '.')
+        };
+
+        assert!(matches!(
+            pin("ANALOG:60").unwrap(),
+            Command::Pin {
+                wire: Some(WireType::Analog),
+                leader: Some(60.0),
+                ..
+            }
+        ));
+        assert!(matches!(
+            pin("DIGITAL").unwrap(),
+            Command::Pin {
+                wire: Some(WireType::Digital),
+                leader: None,
+                ..
+            }
+        ));
+        assert!(pin("ANALOG:long").is_err());
+    }
+
+    #[test]
+    fn test_parse_pin_text_command() {
+        let record = StringRecord::from(vec![
+            "PINTEXT",
+            "POWER",
+            "OUTPUT",
+            "POWER",
+            "VCC",
+            "PowerPin",
+            "Unregulated Supply",
+            "red",
+        ]);
+
+        let command = parse_command("PINTEXT".to_string(), &record, Phase::Draw, '.').unwrap();
+
+        assert_eq!(
+            command,
+            Command::PinText {
+                wire: Some(WireType::Power),
+                pin_type: Some(PinType::Output),
+                pin_group: Some("POWER".to_string()),
+                msg_theme: "PowerPin".to_string(),
+                label: Some("VCC".to_string()),
+                message: "Unregulated Supply".to_string(),
+                color: Some("red".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_box_command() {
+        let record = StringRecord::from(vec![
+            "BOX", "theme", "10", "20", "100", "40", "LEFT", "TOP", "Hello",
+        ]);
+
+        let command = parse_command("BOX".to_string(), &record, Phase::Draw, '.').unwrap();
+
+        assert_eq!(
+            command,
+            Command::Box {
+                theme: "theme".to_string(),
+                x: 10.0,
+                y: 20.0,
+                box_width: Some(100.0),
+                box_height: Some(40.0),
+                x_justify: Some(JustifyX::Left),
+                y_justify: Some(JustifyY::Top),
+                message: Some("Hello".to_string()),
+                text_anchor: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_page_command() {
+        let record = StringRecord::from(vec!["PAGE", "A3-P"]);
+        assert_eq!(
+            parse_command("PAGE".to_string(), &record, Phase::Setup, '.').unwrap(),
+            Command::Page {
+                page_name: "A3-P".to_string(),
+                size: None,
+            }
+        );
+
+        let record = StringRecord::from(vec!["PAGE", "CUSTOM", "215.9", "279.4"]);
+        assert_eq!(
+            parse_command("PAGE".to_string(), &record, Phase::Setup, '.').unwrap(),
+            Command::Page {
+                page_name: "CUSTOM".to_string(),
+                size: Some((215.9, 279.4)),
+            }
+        );
+
+        let record = StringRecord::from(vec!["PAGE", "CUSTOM", "215.9"]);
+        assert!(parse_command("PAGE".to_string(), &record, Phase::Setup, '.').is_err());
+    }
+
+    #[test]
+    fn test_parse_image_anchor() {
+        assert_eq!(
+            parse_image_anchor("TOP LEFT").unwrap(),
+            (JustifyX::Left, JustifyY::Top)
+        );
+        assert_eq!(
+            parse_image_anchor("bottom-right").unwrap(),
+            (JustifyX::Right, JustifyY::Bottom)
+        );
+        assert_eq!(
+            parse_image_anchor("TOP").unwrap(),
+            (JustifyX::Center, JustifyY::Top)
+        );
+        assert!(parse_image_anchor("MIDDLE").is_err());
+    }
+
+    #[test]
+    fn test_max_commands() {
+        let path = std::env::temp_dir().join("pinout_max_commands.csv");
+        std::fs::write(&path, "DPI, 300\nDRAW\nANCHOR, 1, 2\nANCHOR, 3, 4\n").unwrap();
+
+        let limited = |max| {
+            let options = ParseOptions {
+                max_commands: Some(max),
+                ..Default::default()
+            };
+            parse_csv_file_with_options(path.to_str().unwrap(), &options)
+        };
+        let exact = limited(4);
+        let over = limited(3);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(exact.unwrap().len(), 4);
+        assert!(matches!(over, Err(ParserError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn test_parse_notes() {
+        let path = std::env::temp_dir().join("pinout_notes.csv");
+        std::fs::write(
+            &path,
+            "#@ setup notes are plain comments\nDRAW\n#@ check, boot pins\n# hidden\n",
+        )
+        .unwrap();
+
+        let commands = parse_csv_file(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            commands,
+            vec![
+                Command::Draw,
+                Command::Note {
+                    text: "check, boot pins".to_string()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comma_decimal_mode() {
+        assert_eq!(parse_f32("1,5", ',').unwrap(), 1.5);
+        assert_eq!(parse_f32("-1.234,5", ',').unwrap(), -1234.5);
+        assert_eq!(comma_decimal_to_point("Pin 1,5"), "Pin 1,5");
+
+        let path = std::env::temp_dir().join("pinout_comma_decimal.csv");
+        std::fs::write(&path, "DRAW\nANCHOR; 1,5; 20\n").unwrap();
+
+        let commands =
+            parse_csv_file_with_options(path.to_str().unwrap(), &ParseOptions::comma_decimal())
+                .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(commands[1], Command::Anchor { x: 1.5, y: 20.0 });
+
+        // Only numeric columns are converted, labels that look like numbers are kept as written
+        let commands = parse_csv_reader_with_options(
+            "DRAW\nPIN; DIGITAL; IO; ; 3.3; 1.2.0; 2,5\n".as_bytes(),
+            &ParseOptions::comma_decimal(),
+        )
+        .unwrap();
+        assert_eq!(
+            commands[1],
+            Command::Pin {
+                wire: Some(WireType::Digital),
+                pin_type: Some(PinType::IO),
+                group: None,
+                attributes: vec!["3.3".to_string(), "1.2.0".to_string(), "2,5".to_string()],
+                leader: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_csv_str() {
+        let text = "# setup\n\nDPI, 150\n,\nDRAW\n#@ boot pins\nANCHOR, 10, 20\n";
+        let expected = vec![
+            Command::Dpi { dpi: 150 },
+            Command::Draw,
+            Command::Note {
+                text: "boot pins".to_string(),
+            },
+            Command::Anchor { x: 10.0, y: 20.0 },
+        ];
+
+        assert_eq!(parse_csv_str(text).unwrap(), expected);
+        assert_eq!(
+            parse_csv_reader(std::io::Cursor::new(text)).unwrap(),
+            expected
+        );
+
+        let options = ParseOptions {
+            max_commands: Some(1),
+            ..Default::default()
+        };
+        let error = parse_csv_reader_with_options(text.as_bytes(), &options).unwrap_err();
+        assert!(matches!(error, ParserError::LimitExceeded(_)), "{}", error);
+    }
+
+    #[test]
+    fn test_error_line_number() {
+        let error = parse_csv_str("DPI, 150\nDRAW\nANCHOR, left, 20\n").unwrap_err();
+
+        assert!(
+            matches!(&error, ParserError::AtLine { line: 3, source }
+                if matches!(**source, ParserError::ParseError(_))),
+            "{:?}",
+            error
+        );
+        assert!(error.to_string().starts_with("Line 3: "), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_wire_dash() {
+        let dash = |value: &str| -> Result<Option<String>, ParserError> {
+            let record = StringRecord::from(vec![
+                "WIRE", "PWM", "black", "1", "2", "", "", "", "", value,
+            ]);
+            match parse_command("WIRE".to_string(), &record, Phase::Setup, '.')? {
+                Command::Wire { dash, .. } => Ok(dash),
+                other => panic!("Unexpected command: {:?}", other),
+            }
+        };
+
+        assert_eq!(dash("").unwrap(), None);
+        assert_eq!(dash(" 6  3.5 ").unwrap(), Some("6 3.5".to_string()));
+        assert!(dash("6 -3").is_err());
+        assert!(dash("dotted").is_err());
+    }
+
+    #[test]
+    fn test_parse_type_arrow() {
+        let arrow = |fields: &[&str]| -> Result<bool, ParserError> {
+            let record = StringRecord::from(fields.to_vec());
+            match parse_command("TYPE".to_string(), &record, Phase::Setup, '.')? {
+                Command::Type { arrow, .. } => Ok(arrow),
+                other => panic!("Unexpected command: {:?}", other),
+            }
+        };
+
+        assert!(!arrow(&["TYPE", "INPUT", "green", "1"]).unwrap());
+        assert!(arrow(&["TYPE", "INPUT", "green", "1", " true "]).unwrap());
+        assert!(!arrow(&["TYPE", "OUTPUT", "red", "1", "NO"]).unwrap());
+        assert!(arrow(&["TYPE", "OUTPUT", "red", "1", "sideways"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_pinset_column_gap() {
+        let gap = |value: &str| -> Result<(f32, bool), ParserError> {
+            let record = StringRecord::from(vec![
+                "PINSET", "LEFT", "PACKED", "CENTER", "CENTER", "25", "10", "20", "40", value, "0",
+            ]);
+            match parse_command("PINSET".to_string(), &record, Phase::Draw, '.')? {
+                Command::PinSet {
+                    column_gap,
+                    column_gap_percent,
+                    ..
+                } => Ok((column_gap, column_gap_percent)),
+                other => panic!("Unexpected command: {:?}", other),
+            }
+        };
+
+        assert_eq!(gap("5").unwrap(), (5.0, false));
+        assert_eq!(gap("0.5").unwrap(), (0.5, false));
+        assert_eq!(gap(" 50% ").unwrap(), (50.0, true));
+        assert!(gap("half%").is_err());
+    }
 }
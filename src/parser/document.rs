@@ -8,6 +8,12 @@ pub struct Document {
     pub phase: Phase,
 }
 
+impl Default for Document {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Document {
     pub fn new() -> Self {
         Self {
@@ -43,11 +49,13 @@ impl Document {
     }
 }
 
-fn is_setup_command(command: &Command) -> bool {
+pub(crate) fn is_setup_command(command: &Command) -> bool {
     matches!(
         command,
         Command::Labels { .. }
             | Command::BorderColor { .. }
+            | Command::BorderWidth { .. }
+            | Command::BorderOpacity { .. }
             | Command::FillColor { .. }
             | Command::Opacity { .. }
             | Command::Font { .. }
@@ -56,17 +64,22 @@ fn is_setup_command(command: &Command) -> bool {
             | Command::FontSlant { .. }
             | Command::FontBold { .. }
             | Command::FontStretch { .. }
+            | Command::FontOutline { .. }
+            | Command::FontOutlineThickness { .. }
             | Command::Type { .. }
             | Command::Wire { .. }
             | Command::Group { .. }
+            | Command::ColorScale { .. }
+            | Command::Match { .. }
             | Command::BoxTheme { .. }
             | Command::TextFont { .. }
             | Command::Page { .. }
+            | Command::AutoOrient
             | Command::Dpi { .. }
     )
 }
 
-fn is_draw_command(command: &Command) -> bool {
+pub(crate) fn is_draw_command(command: &Command) -> bool {
     matches!(
         command,
         Command::GoogleFont { .. }
@@ -75,10 +88,66 @@ fn is_draw_command(command: &Command) -> bool {
             | Command::Anchor { .. }
             | Command::PinSet { .. }
             | Command::Pin { .. }
+            | Command::PinData { .. }
             | Command::PinText { .. }
             | Command::Box { .. }
             | Command::Message { .. }
             | Command::Text { .. }
             | Command::EndMessage
+            | Command::PageBreak
+            | Command::Note { .. }
+            | Command::SheetInfo { .. }
+            | Command::ScaleBar { .. }
+            | Command::GroupTable { .. }
+            | Command::Layer { .. }
+            | Command::Zebra { .. }
+            | Command::Header { .. }
+            | Command::Mirror { .. }
+            | Command::Shell { .. }
+            | Command::Watermark { .. }
+            | Command::Custom { .. }
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_add_setup_commands() {
+        let mut document = Document::new();
+        let setup = [
+            Command::Labels {
+                default: "DEFAULT".to_string(),
+                pin_type: None,
+                group: None,
+                labels: vec!["Name".to_string()],
+            },
+            Command::BorderWidth { width: 2 },
+            Command::BorderOpacity { opacity: 0.5 },
+            Command::FontOutline {
+                default: "white".to_string(),
+                pin_type: None,
+                group: None,
+                colors: Vec::new(),
+            },
+            Command::FontOutlineThickness {
+                default: 1.0,
+                pin_type: None,
+                group: None,
+                thickness: Vec::new(),
+            },
+        ];
+        for command in setup {
+            document.add_command(command).unwrap();
+        }
+
+        // Setup commands are refused once drawing has started
+        document.add_command(Command::Draw).unwrap();
+        assert!(matches!(
+            document.add_command(Command::BorderWidth { width: 1 }),
+            Err(ParserError::InvalidPhase)
+        ));
+        assert_eq!(document.commands.len(), 6);
+    }
+}
@@ -1,3 +1,10 @@
 pub mod csv;
 pub mod document;
+pub mod report;
+#[cfg(feature = "theme-pack")]
+pub mod theme_pack;
 pub mod types;
+pub mod validate;
+
+pub use report::report;
+pub use validate::{ValidationError, validate_document};
@@ -0,0 +1,133 @@
+use super::{
+    types::Command,
+    validate::{command_name, theme_names},
+};
+use std::fmt::Write;
+
+/// Plain text summary of a command list for review: how many of each command there are,
+/// the themes it defines, and every pin with its wire, type, group and attributes.
+pub fn report(commands: &[Command]) -> String {
+    let mut report = String::new();
+
+    let setup = commands
+        .iter()
+        .take_while(|cmd| !matches!(cmd, Command::Draw))
+        .count();
+    let draw = commands.len().saturating_sub(setup + 1);
+    let _ = writeln!(
+        report,
+        "Commands: {} (setup {}, draw {})",
+        commands.len(),
+        setup,
+        draw
+    );
+
+    // Counts per command, in order of first use
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for command in commands {
+        let name = command_name(command);
+        match counts.iter_mut().find(|(counted, _)| *counted == name) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((name, 1)),
+        }
+    }
+    let _ = writeln!(report, "\nCommand counts:");
+    for (name, count) in &counts {
+        let _ = writeln!(report, "  {}: {}", name, count);
+    }
+
+    let _ = writeln!(report, "\nThemes:");
+    for theme in theme_names(commands) {
+        let _ = writeln!(report, "  {}", theme);
+    }
+
+    let pins: Vec<String> = commands.iter().filter_map(pin_line).collect();
+    let _ = writeln!(report, "\nPins: {}", pins.len());
+    for pin in pins {
+        let _ = writeln!(report, "  {}", pin);
+    }
+
+    report
+}
+
+/// One pin as `wire, type, group: attributes`, with `-` for anything not set
+fn pin_line(command: &Command) -> Option<String> {
+    let attributes = match command {
+        Command::Pin { attributes, .. } => attributes.join(", "),
+        Command::PinText { label, message, .. } => {
+            let label = label.clone().unwrap_or_default();
+            [label, message.clone()]
+                .into_iter()
+                .filter(|text| !text.is_empty())
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+        _ => return None,
+    };
+
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    Some(format!(
+        "{}, {}, {}: {}",
+        or_dash(command.wire().map(|wire| wire.to_string())),
+        or_dash(command.pin_type().map(|pin_type| pin_type.to_string())),
+        or_dash(command.group().map(str::to_string)),
+        attributes
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::WireType;
+
+    #[test]
+    fn test_report() {
+        let pin = |name: &str, group: Option<&str>| Command::Pin {
+            wire: Some(WireType::Digital),
+            pin_type: None,
+            group: group.map(str::to_string),
+            attributes: vec![name.to_string(), "GPIO".to_string()],
+            leader: None,
+        };
+        let commands = [
+            Command::Labels {
+                default: "DEFAULT".to_string(),
+                pin_type: None,
+                group: None,
+                labels: vec!["Name".to_string(), "Function".to_string()],
+            },
+            Command::Group {
+                name: "PORTA".to_string(),
+                color: "red".to_string(),
+                opacity: 1.0,
+                shape: None,
+            },
+            Command::Draw,
+            pin("D1", Some("PORTA")),
+            pin("D2", None),
+        ];
+
+        let report = report(&commands);
+
+        assert!(
+            report.contains("Commands: 5 (setup 2, draw 2)"),
+            "{}",
+            report
+        );
+        assert!(report.contains("\n  Pin: 2\n"), "{}", report);
+        assert!(
+            report.contains(
+                "\nThemes:\n  DEFAULT\n  TYPE\n  GROUP\n  Name\n  Function\n  GROUP_PORTA\n"
+            ),
+            "{}",
+            report
+        );
+        assert!(report.contains("\nPins: 2\n"), "{}", report);
+        assert!(
+            report.contains("  DIGITAL, -, PORTA: D1, GPIO\n"),
+            "{}",
+            report
+        );
+        assert!(report.contains("  DIGITAL, -, -: D2, GPIO\n"), "{}", report);
+    }
+}
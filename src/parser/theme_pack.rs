@@ -0,0 +1,229 @@
+use super::{
+    csv::{ParserError, parse_command},
+    types::{Command, Phase},
+};
+use csv::StringRecord;
+use toml::{Table, Value};
+
+/// Theme tables, the setup command each entry becomes and its fields in CSV column order
+const TABLES: [(&str, &str, &[&str]); 5] = [
+    ("type", "TYPE", &["color", "opacity", "arrow"]),
+    (
+        "wire",
+        "WIRE",
+        &[
+            "color",
+            "opacity",
+            "thickness",
+            "amplitude",
+            "terminal",
+            "start_gap",
+            "stroke_opacity",
+            "dash",
+        ],
+    ),
+    ("group", "GROUP", &["color", "opacity", "shape"]),
+    (
+        "box",
+        "BOX",
+        &[
+            "border_color",
+            "border_opacity",
+            "fill_color",
+            "fill_opacity",
+            "line_width",
+            "width",
+            "height",
+            "corner_x",
+            "corner_y",
+            "skew",
+            "skew_offset",
+            "shape",
+        ],
+    ),
+    (
+        "text_font",
+        "TEXT FONT",
+        &[
+            "font",
+            "size",
+            "outline_color",
+            "color",
+            "slant",
+            "bold",
+            "stretch",
+        ],
+    ),
+];
+
+/// Reads a TOML theme pack into setup commands that can be put in front of any diagram
+pub fn load(path: &str) -> Result<Vec<Command>, ParserError> {
+    let text = std::fs::read_to_string(path)?;
+    parse(&text).map_err(|e| ParserError::ParseError(format!("{}: {}", path, e)))
+}
+
+/// Converts theme pack text into setup commands. A pack holds an optional `page` name and
+/// `[type.<TYPE>]`, `[wire.<WIRE>]`, `[group.<name>]`, `[box.<theme>]` and
+/// `[text_font.<theme>]` tables, whose keys are the fields of the matching command.
+pub fn parse(text: &str) -> Result<Vec<Command>, ParserError> {
+    let pack: Table = text
+        .parse()
+        .map_err(|e: toml::de::Error| ParserError::ParseError(e.to_string()))?;
+
+    if let Some(key) = pack
+        .keys()
+        .find(|key| *key != "page" && !TABLES.iter().any(|(table, ..)| table == key))
+    {
+        return Err(ParserError::ParseError(format!(
+            "Unknown theme pack entry: {}",
+            key
+        )));
+    }
+
+    let mut commands = Vec::new();
+    if let Some(page) = pack.get("page") {
+        commands.push(setup_command("PAGE", vec![field(page)?])?);
+    }
+
+    for (table, command_name, columns) in TABLES {
+        let Some(entries) = pack.get(table) else {
+            continue;
+        };
+        let entries = entries.as_table().ok_or_else(|| {
+            ParserError::ParseError(format!("Theme pack entry {} must be a table", table))
+        })?;
+
+        for (name, entry) in entries {
+            let entry = entry.as_table().ok_or_else(|| {
+                ParserError::ParseError(format!("[{}.{}] must be a table", table, name))
+            })?;
+            if let Some(key) = entry.keys().find(|key| !columns.contains(&key.as_str())) {
+                return Err(ParserError::ParseError(format!(
+                    "Unknown field {} in [{}.{}]",
+                    key, table, name
+                )));
+            }
+
+            let mut fields = vec![name.clone()];
+            for column in columns {
+                fields.push(
+                    entry
+                        .get(*column)
+                        .map(field)
+                        .transpose()?
+                        .unwrap_or_default(),
+                );
+            }
+            commands.push(setup_command(command_name, fields)?);
+        }
+    }
+
+    Ok(commands)
+}
+
+/// Parses the fields like a CSV row, so packs get the same defaults and checks as files
+fn setup_command(command_name: &str, mut fields: Vec<String>) -> Result<Command, ParserError> {
+    while fields.last().is_some_and(String::is_empty) {
+        fields.pop();
+    }
+
+    let record: StringRecord = std::iter::once(command_name)
+        .chain(fields.iter().map(String::as_str))
+        .collect();
+    parse_command(command_name.to_string(), &record, Phase::Setup, '.')
+}
+
+fn field(value: &Value) -> Result<String, ParserError> {
+    match value {
+        Value::String(text) => Ok(text.clone()),
+        Value::Integer(number) => Ok(number.to_string()),
+        Value::Float(number) => Ok(number.to_string()),
+        _ => Err(ParserError::ParseError(format!(
+            "Theme pack values must be text or numbers, found {}",
+            value
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::{FontBoldness, FontSlant, FontStretch};
+
+    #[test]
+    fn test_load_theme_pack() {
+        let path = std::env::temp_dir().join("pinout_theme_pack.toml");
+        std::fs::write(
+            &path,
+            r#"
+page = "A4-L"
+
+[box.STD]
+border_color = "black"
+border_opacity = 1
+fill_color = "white"
+fill_opacity = 0.5
+line_width = 2
+width = 80
+height = 20
+corner_x = 3
+corner_y = 3
+skew = 0
+skew_offset = 0
+
+[text_font.STD]
+font = "Arial"
+size = 12
+outline_color = "white"
+color = "black"
+slant = "italic"
+bold = "bold"
+stretch = "normal"
+"#,
+        )
+        .unwrap();
+
+        let commands = load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            commands,
+            vec![
+                Command::Page {
+                    page_name: "A4-L".to_string(),
+                    size: None,
+                },
+                Command::BoxTheme {
+                    name: "STD".to_string(),
+                    border_color: "black".to_string(),
+                    border_opacity: 1.0,
+                    fill_color: "white".to_string(),
+                    fill_opacity: 0.5,
+                    line_width: 2.0,
+                    box_width: 80.0,
+                    box_height: 20.0,
+                    box_cr_x: 3.0,
+                    box_cr_y: 3.0,
+                    box_skew: 0.0,
+                    box_skew_offset: 0.0,
+                    shape: None,
+                },
+                Command::TextFont {
+                    theme_name: "STD".to_string(),
+                    font: "Arial".to_string(),
+                    size: 12.0,
+                    outline_color: "white".to_string(),
+                    color: "black".to_string(),
+                    slant: FontSlant::Italic,
+                    bold: FontBoldness::Bold,
+                    stretch: FontStretch::Normal,
+                },
+            ]
+        );
+
+        // Missing fields fail like a short CSV row, unknown ones are reported by name
+        assert!(parse("[text_font.STD]\nfont = \"Arial\"\n").is_err());
+        let error = parse("[box.STD]\ncolour = \"red\"\n").unwrap_err();
+        assert!(error.to_string().contains("colour"), "{}", error);
+    }
+}
@@ -94,17 +94,37 @@ pub enum Command {
         pin_type: PinType,
         color: String,
         opacity: f32,
+        arrow: bool, // Arrowhead on the leaders of INPUT and OUTPUT pins
     },
     Wire {
         wire_type: WireType,
         color: String,
         opacity: f32,
         thickness: f32,
+        amplitude: Option<f32>,
+        terminal: Option<Terminal>,
+        start_gap: Option<f32>,
+        stroke_opacity: Option<f32>,
+        dash: Option<String>, // stroke-dasharray of the leader, solid when None
     },
     Group {
         name: String,
         color: String,
         opacity: f32,
+        shape: Option<GroupShape>,
+    },
+    // Maps numeric pin attribute values to a box fill color between two colors
+    ColorScale {
+        min: f32,
+        max: f32,
+        color_low: String,
+        color_high: String,
+        label: Option<String>,
+    },
+    // Applies a theme to every pin whose first attribute matches the pattern
+    Match {
+        pattern: String,
+        theme: String,
     },
     BoxTheme {
         name: String,
@@ -119,6 +139,7 @@ pub enum Command {
         box_cr_y: f32,
         box_skew: f32,
         box_skew_offset: f32,
+        shape: Option<BoxShape>,
     },
     TextFont {
         theme_name: String,
@@ -132,7 +153,9 @@ pub enum Command {
     },
     Page {
         page_name: String,
+        size: Option<(f32, f32)>, // Width and height in mm of a CUSTOM page
     },
+    AutoOrient, // Turns the page to whichever orientation fits the drawn content best
     Dpi {
         dpi: u32,
     },
@@ -140,7 +163,7 @@ pub enum Command {
 
     // Draw Phase Commands
     GoogleFont {
-        _link: String,
+        link: String,
     },
     Image {
         name: String,
@@ -153,6 +176,10 @@ pub enum Command {
         cw: Option<f32>,
         ch: Option<f32>,
         rot: Option<f32>,
+        pivot_x: Option<f32>,
+        pivot_y: Option<f32>,
+        // Point of the image placed at x, y, the center when not given
+        anchor: Option<(JustifyX, JustifyY)>,
     },
     Icon {
         name: String,
@@ -176,13 +203,37 @@ pub enum Command {
         group_width: f32,
         leader_offset: f32,
         column_gap: f32,
+        // Whether the column gap is a percentage of the box width rather than a distance
+        column_gap_percent: bool,
         leader_h_step: f32,
+        angle: Option<f32>,
+        units: Option<Unit>,
+        // Gap to advance the anchor by, past the set's width, once the set is complete
+        advance: Option<f32>,
+        // Number printed on the first pin's leader, counting up for each pin after it
+        first_number: Option<u32>,
+        // Space between the end of the leader and the first attribute box
+        box_gap: Option<f32>,
+        // Color of a rounded frame drawn around the completed set
+        frame: Option<String>,
+        // Title printed above the frame
+        title: Option<String>,
     },
     Pin {
         wire: Option<WireType>,
         pin_type: Option<PinType>,
         group: Option<String>,
         attributes: Vec<String>,
+        // Leader length of this pin alone, in place of the pin set's
+        leader: Option<f32>,
+    },
+    PinData {
+        path: String,
+        // Read with the field delimiter and decimal separator of the file naming it
+        delimiter: u8,
+        decimal_separator: char,
+        // Most rows the data file may hold, the naming file's command limit
+        max_rows: Option<usize>,
     },
     PinText {
         wire: Option<WireType>,
@@ -191,6 +242,7 @@ pub enum Command {
         msg_theme: String,
         label: Option<String>,
         message: String,
+        color: Option<String>,
     },
     Box {
         theme: String,
@@ -201,6 +253,7 @@ pub enum Command {
         x_justify: Option<JustifyX>,
         y_justify: Option<JustifyY>,
         message: Option<String>,
+        text_anchor: Option<JustifyX>,
     },
     Message {
         x: Option<f32>,
@@ -210,6 +263,8 @@ pub enum Command {
         font_size: Option<f32>,
         x_justify: Option<JustifyX>,
         y_justify: Option<JustifyY>,
+        tab_stops: Option<Vec<f32>>,
+        max_height: Option<f32>,
     },
     Text {
         edge_color: String,
@@ -218,6 +273,55 @@ pub enum Command {
         new_line: bool,
     },
     EndMessage,
+    PageBreak, // Starts a new sheet
+    // A "#@" comment row, shown as a note at the current anchor
+    Note {
+        text: String,
+    },
+    // Puts the draw commands that follow on a named layer, an empty name ends the layer
+    Layer {
+        name: Option<String>,
+    },
+    // Table of every pin group, its color and its pins
+    GroupTable {
+        x: f32,
+        y: f32,
+    },
+    SheetInfo {
+        x: f32,
+        y: f32,
+    },
+    // Bar of a physical length, with end ticks and its length printed above it
+    ScaleBar {
+        x: f32,
+        y: f32,
+        length_mm: f32,
+    },
+    Zebra {
+        color: String,
+    },
+    // Column titles for the current pin set, one box per label
+    Header {
+        theme: Option<String>,
+    },
+    Shell {
+        theme: String,
+        padding: Option<f32>,
+    },
+    // Redraw the last pin set mirrored across a vertical axis this far right of its anchor
+    Mirror {
+        offset: f32,
+    },
+    Watermark {
+        text: String,
+        opacity: f32,
+        angle: Option<f32>,
+    },
+    // Unknown draw command, rendered by a handler registered on the renderer
+    Custom {
+        name: String,
+        fields: Vec<String>,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -234,6 +338,48 @@ pub enum WireType {
     Analog,
     HsAnalog,
     Power,
+    Bus,
+    Gnd,
+}
+
+/// Glyph drawn as a pin group indicator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GroupShape {
+    Circle,
+    Square,
+    Hexagon,
+    Diamond,
+}
+
+/// Outline of a box, chamfered or notched shapes mark pin 1 on connector and IC bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoxShape {
+    Rectangle,
+    Chamfered,
+    Notched(Corner),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Unit of length for pin set geometry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unit {
+    Px,
+    Mm,
+}
+
+/// Marker drawn at the far end of a leader line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Terminal {
+    None,
+    Dot,
+    Circle,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -242,6 +388,7 @@ pub enum Side {
     Right,
     Top,
     Bottom,
+    Radial, // Pins evenly spaced around the anchor, leaders pointing outward
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -297,6 +444,36 @@ pub enum FontStretch {
     UltraExpanded,
 }
 
+impl Command {
+    /// The pin group a pin row is drawn in, or the group a GROUP command defines
+    pub fn group(&self) -> Option<&str> {
+        match self {
+            Command::Pin { group, .. } => group.as_deref(),
+            Command::PinText { pin_group, .. } => pin_group.as_deref(),
+            Command::Group { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// The wire of a pin row, or the wire a WIRE command defines
+    pub fn wire(&self) -> Option<WireType> {
+        match self {
+            Command::Pin { wire, .. } | Command::PinText { wire, .. } => *wire,
+            Command::Wire { wire_type, .. } => Some(*wire_type),
+            _ => None,
+        }
+    }
+
+    /// The pin type of a pin row, or the pin type a TYPE command defines
+    pub fn pin_type(&self) -> Option<PinType> {
+        match self {
+            Command::Pin { pin_type, .. } | Command::PinText { pin_type, .. } => *pin_type,
+            Command::Type { pin_type, .. } => Some(*pin_type),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for PinType {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -315,6 +492,59 @@ impl fmt::Display for WireType {
             WireType::Analog => write!(f, "ANALOG"),
             WireType::HsAnalog => write!(f, "HS-ANALOG"),
             WireType::Power => write!(f, "POWER"),
+            WireType::Bus => write!(f, "BUS"),
+            WireType::Gnd => write!(f, "GND"),
+        }
+    }
+}
+
+impl fmt::Display for GroupShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GroupShape::Circle => write!(f, "CIRCLE"),
+            GroupShape::Square => write!(f, "SQUARE"),
+            GroupShape::Hexagon => write!(f, "HEXAGON"),
+            GroupShape::Diamond => write!(f, "DIAMOND"),
+        }
+    }
+}
+
+impl fmt::Display for BoxShape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoxShape::Rectangle => write!(f, "RECTANGLE"),
+            BoxShape::Chamfered => write!(f, "CHAMFER"),
+            BoxShape::Notched(corner) => write!(f, "NOTCH {}", corner),
+        }
+    }
+}
+
+impl fmt::Display for Corner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Corner::TopLeft => write!(f, "TL"),
+            Corner::TopRight => write!(f, "TR"),
+            Corner::BottomLeft => write!(f, "BL"),
+            Corner::BottomRight => write!(f, "BR"),
+        }
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unit::Px => write!(f, "PX"),
+            Unit::Mm => write!(f, "MM"),
+        }
+    }
+}
+
+impl fmt::Display for Terminal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminal::None => write!(f, "NONE"),
+            Terminal::Dot => write!(f, "DOT"),
+            Terminal::Circle => write!(f, "CIRCLE"),
         }
     }
 }
@@ -366,3 +596,41 @@ impl fmt::Display for FontStretch {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_accessors() {
+        let pin = Command::Pin {
+            wire: Some(WireType::Pwm),
+            pin_type: None,
+            group: Some("PORTA".to_string()),
+            attributes: vec!["PA0".to_string()],
+            leader: None,
+        };
+        assert_eq!(pin.group(), Some("PORTA"));
+        assert_eq!(pin.wire(), Some(WireType::Pwm));
+        assert_eq!(pin.pin_type(), None);
+
+        let box_theme = Command::BoxTheme {
+            name: "PORTA".to_string(),
+            border_color: "black".to_string(),
+            border_opacity: 1.0,
+            fill_color: "white".to_string(),
+            fill_opacity: 1.0,
+            line_width: 1.0,
+            box_width: 50.0,
+            box_height: 20.0,
+            box_cr_x: 0.0,
+            box_cr_y: 0.0,
+            box_skew: 0.0,
+            box_skew_offset: 0.0,
+            shape: None,
+        };
+        assert_eq!(box_theme.group(), None);
+        assert_eq!(box_theme.wire(), None);
+        assert_eq!(box_theme.pin_type(), None);
+    }
+}
@@ -0,0 +1,417 @@
+use super::{
+    document::{is_draw_command, is_setup_command},
+    types::{Command, Phase},
+};
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// A problem found in a command list before rendering, with the index of the offending command
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    #[error("Command {index}: {command} is not allowed in the {phase:?} phase")]
+    InvalidPhase {
+        index: usize,
+        command: String,
+        phase: Phase,
+    },
+
+    #[error("Command {index}: {reason}")]
+    UnbalancedMessage { index: usize, reason: String },
+
+    #[error("Command {index}: {command} needs a prior PINSET")]
+    MissingPinSet { index: usize, command: String },
+
+    #[error("Command {index}: {kind} {name} is not defined")]
+    UndefinedReference {
+        index: usize,
+        kind: &'static str,
+        name: String,
+    },
+
+    #[error("Command {index}: {field} must be {expected}, got {value}")]
+    OutOfRange {
+        index: usize,
+        field: &'static str,
+        value: f32,
+        expected: &'static str,
+    },
+}
+
+/// Check a command list for everything the renderer would reject, returning every problem
+/// found rather than stopping at the first.
+///
+/// Covers phase ordering, TEXT and END MESSAGE outside a message, commands that need a pin
+/// set, references to undefined groups and themes, and out of range numbers.
+pub fn validate_document(commands: &[Command]) -> Result<(), Vec<ValidationError>> {
+    let mut errors = Vec::new();
+
+    // Setup always precedes the use of a theme in a valid document
+    let themes: HashSet<String> = theme_names(commands).into_iter().collect();
+
+    let mut phase = Phase::Setup;
+    let mut in_message = false;
+    let mut in_pin_set = false;
+
+    for (index, command) in commands.iter().enumerate() {
+        // Phase ordering
+        let allowed = match (command, phase) {
+            (Command::Draw, Phase::Setup) => {
+                phase = Phase::Draw;
+                true
+            }
+            (Command::Draw, Phase::Draw) => false,
+            (_, Phase::Setup) => is_setup_command(command),
+            (_, Phase::Draw) => is_draw_command(command),
+        };
+        if !allowed {
+            errors.push(ValidationError::InvalidPhase {
+                index,
+                command: command_name(command),
+                phase,
+            });
+        }
+
+        // Messages and pin sets
+        match command {
+            Command::Message { .. } => in_message = true,
+            Command::Text { .. } if !in_message => {
+                errors.push(ValidationError::UnbalancedMessage {
+                    index,
+                    reason: "TEXT outside of a MESSAGE".to_string(),
+                });
+            }
+            Command::EndMessage if !in_message => {
+                errors.push(ValidationError::UnbalancedMessage {
+                    index,
+                    reason: "END MESSAGE without an open MESSAGE".to_string(),
+                });
+            }
+            Command::EndMessage => in_message = false,
+            Command::PinSet { .. } => in_pin_set = true,
+            Command::Zebra { .. } | Command::Header { .. } | Command::Mirror { .. }
+                if !in_pin_set =>
+            {
+                errors.push(ValidationError::MissingPinSet {
+                    index,
+                    command: command_name(command),
+                });
+            }
+            _ => {}
+        }
+
+        check_references(index, command, &themes, &mut errors);
+        check_ranges(index, command, &mut errors);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Every theme the commands define, as the renderer names them, in definition order
+pub(crate) fn theme_names(commands: &[Command]) -> Vec<String> {
+    let mut themes: Vec<String> = ["DEFAULT", "TYPE", "GROUP"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    for command in commands {
+        let defined = match command {
+            Command::Labels { labels, .. } => labels.clone(),
+            Command::BoxTheme { name, .. } => vec![format!("BOX_{}", name)],
+            Command::TextFont { theme_name, .. } => vec![format!("FONT_{}", theme_name)],
+            Command::Group { name, .. } => vec![format!("GROUP_{}", name)],
+            Command::Type { pin_type, .. } => vec![format!("PINTYPE_{}", pin_type)],
+            Command::Wire { wire_type, .. } => vec![format!("PINWIRE_{}", wire_type)],
+            _ => Vec::new(),
+        };
+        for theme in defined {
+            if !themes.contains(&theme) {
+                themes.push(theme);
+            }
+        }
+    }
+    themes
+}
+
+/// Name of the command's variant, for messages
+pub(crate) fn command_name(command: &Command) -> String {
+    let debug = format!("{:?}", command);
+    debug
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn check_references(
+    index: usize,
+    command: &Command,
+    themes: &HashSet<String>,
+    errors: &mut Vec<ValidationError>,
+) {
+    let box_theme = |theme: &str| {
+        if theme.starts_with("BOX_") {
+            theme.to_string()
+        } else {
+            format!("BOX_{}", theme)
+        }
+    };
+
+    let missing = match command {
+        Command::Pin {
+            group: Some(group), ..
+        }
+        | Command::PinText {
+            pin_group: Some(group),
+            ..
+        } if !themes.contains(&format!("GROUP_{}", group)) => Some(("Group", group.clone())),
+        Command::Shell { theme, .. } | Command::Header { theme: Some(theme) }
+            if !themes.contains(&box_theme(theme)) =>
+        {
+            Some(("Box theme", theme.clone()))
+        }
+        Command::Match { theme, .. } if !themes.contains(theme) => Some(("Theme", theme.clone())),
+        _ => None,
+    };
+
+    if let Some((kind, name)) = missing {
+        errors.push(ValidationError::UndefinedReference { index, kind, name });
+    }
+}
+
+fn check_ranges(index: usize, command: &Command, errors: &mut Vec<ValidationError>) {
+    // Opacities are fractions, the parser already divided any percentage by 100
+    let mut opacities: Vec<(&'static str, f32)> = Vec::new();
+    let mut sizes: Vec<(&'static str, f32)> = Vec::new();
+    let mut positive: Vec<(&'static str, f32)> = Vec::new();
+
+    match command {
+        Command::Opacity {
+            default,
+            pin_type,
+            group,
+            opacities: values,
+        } => {
+            opacities.push(("Opacity", *default));
+            opacities.extend(pin_type.iter().chain(group).map(|v| ("Opacity", *v)));
+            opacities.extend(values.iter().map(|v| ("Opacity", *v)));
+        }
+        Command::BorderOpacity { opacity }
+        | Command::Type { opacity, .. }
+        | Command::Group { opacity, .. }
+        | Command::Watermark { opacity, .. } => opacities.push(("Opacity", *opacity)),
+        Command::Wire {
+            opacity,
+            thickness,
+            stroke_opacity,
+            ..
+        } => {
+            opacities.push(("Opacity", *opacity));
+            opacities.extend(stroke_opacity.map(|v| ("Stroke opacity", v)));
+            sizes.push(("Thickness", *thickness));
+        }
+        Command::BoxTheme {
+            border_opacity,
+            fill_opacity,
+            line_width,
+            box_width,
+            box_height,
+            ..
+        } => {
+            opacities.push(("Border opacity", *border_opacity));
+            opacities.push(("Fill opacity", *fill_opacity));
+            sizes.push(("Line width", *line_width));
+            sizes.push(("Box width", *box_width));
+            sizes.push(("Box height", *box_height));
+        }
+        Command::Box {
+            box_width,
+            box_height,
+            ..
+        } => {
+            sizes.extend(box_width.map(|v| ("Box width", v)));
+            sizes.extend(box_height.map(|v| ("Box height", v)));
+        }
+        Command::FontSize {
+            default,
+            pin_type,
+            group,
+            sizes: values,
+        } => {
+            positive.push(("Font size", *default));
+            positive.extend(pin_type.iter().chain(group).map(|v| ("Font size", *v)));
+            positive.extend(values.iter().map(|v| ("Font size", *v)));
+        }
+        Command::TextFont { size, .. } => positive.push(("Font size", *size)),
+        Command::Dpi { dpi } => positive.push(("DPI", *dpi as f32)),
+        Command::PinSet {
+            line_step,
+            pin_width,
+            group_width,
+            leader_offset,
+            box_gap,
+            ..
+        } => {
+            positive.push(("Line step", *line_step));
+            sizes.push(("Pin width", *pin_width));
+            sizes.push(("Group width", *group_width));
+            sizes.push(("Leader offset", *leader_offset));
+            sizes.extend(box_gap.map(|v| ("Box gap", v)));
+        }
+        Command::Message {
+            font_size,
+            line_step,
+            max_height,
+            ..
+        } => {
+            positive.extend(font_size.map(|v| ("Font size", v)));
+            sizes.extend(line_step.map(|v| ("Line step", v)));
+            sizes.extend(max_height.map(|v| ("Max height", v)));
+        }
+        Command::ScaleBar { length_mm, .. } => positive.push(("Scale bar length", *length_mm)),
+        Command::ColorScale { min, max, .. } if max <= min => {
+            errors.push(ValidationError::OutOfRange {
+                index,
+                field: "Max",
+                value: *max,
+                expected: "greater than min",
+            });
+        }
+        _ => {}
+    }
+
+    let checks = [
+        (opacities, "between 0 and 1", 0.0..=1.0),
+        (sizes, "zero or more", 0.0..=f32::INFINITY),
+        (
+            positive,
+            "greater than zero",
+            f32::MIN_POSITIVE..=f32::INFINITY,
+        ),
+    ];
+    for (values, expected, range) in checks {
+        for (field, value) in values {
+            if !range.contains(&value) {
+                errors.push(ValidationError::OutOfRange {
+                    index,
+                    field,
+                    value,
+                    expected,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_document() {
+        let labels = Command::Labels {
+            default: "DEFAULT".to_string(),
+            pin_type: None,
+            group: None,
+            labels: vec!["Name".to_string()],
+        };
+        let pin = |group: &str| Command::Pin {
+            wire: None,
+            pin_type: None,
+            group: Some(group.to_string()),
+            attributes: vec!["D1".to_string()],
+            leader: None,
+        };
+        let group = Command::Group {
+            name: "PORTA".to_string(),
+            color: "red".to_string(),
+            opacity: 1.0,
+            shape: None,
+        };
+
+        assert_eq!(
+            validate_document(&[labels.clone(), group.clone(), Command::Draw, pin("PORTA")]),
+            Ok(())
+        );
+
+        // A setup command after DRAW, and a pin in a group that was never defined
+        let errors = validate_document(&[
+            labels,
+            Command::Draw,
+            group,
+            pin("PORTB"),
+            Command::Dpi { dpi: 300 },
+        ])
+        .unwrap_err();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert_eq!(
+            errors[0],
+            ValidationError::InvalidPhase {
+                index: 2,
+                command: "Group".to_string(),
+                phase: Phase::Draw,
+            }
+        );
+        assert_eq!(
+            errors[1],
+            ValidationError::UndefinedReference {
+                index: 3,
+                kind: "Group",
+                name: "PORTB".to_string(),
+            }
+        );
+        assert!(matches!(
+            errors[2],
+            ValidationError::InvalidPhase { index: 4, .. }
+        ));
+    }
+
+    #[test]
+    fn test_validate_messages_and_ranges() {
+        let text = Command::Text {
+            edge_color: "none".to_string(),
+            color: "black".to_string(),
+            message: "Hello".to_string(),
+            new_line: false,
+        };
+
+        let errors = validate_document(&[
+            Command::Draw,
+            text,
+            Command::EndMessage,
+            Command::Watermark {
+                text: "DRAFT".to_string(),
+                opacity: 1.5,
+                angle: None,
+            },
+            Command::Zebra {
+                color: "#eeeeee".to_string(),
+            },
+        ])
+        .unwrap_err();
+
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert!(matches!(
+            errors[0],
+            ValidationError::UnbalancedMessage { index: 1, .. }
+        ));
+        assert!(matches!(
+            errors[1],
+            ValidationError::UnbalancedMessage { index: 2, .. }
+        ));
+        assert!(matches!(
+            errors[2],
+            ValidationError::OutOfRange {
+                index: 3,
+                field: "Opacity",
+                ..
+            }
+        ));
+        assert!(matches!(
+            errors[3],
+            ValidationError::MissingPinSet { index: 4, .. }
+        ));
+    }
+}
@@ -0,0 +1,218 @@
+use roxmltree::{Document, Node};
+use thiserror::Error;
+
+/// Attributes an element can't be drawn without
+const REQUIRED_ATTRIBUTES: [(&str, &[&str]); 6] = [
+    ("rect", &["width", "height"]),
+    ("circle", &["r"]),
+    ("polyline", &["points"]),
+    ("polygon", &["points"]),
+    ("path", &["d"]),
+    ("image", &["href"]),
+];
+
+/// Attributes that must hold a plain number when present
+const NUMBER_ATTRIBUTES: [&str; 10] = [
+    "x", "y", "width", "height", "r", "cx", "cy", "rx", "ry", "opacity",
+];
+
+/// A problem found in rendered SVG
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum OutputError {
+    #[error("Malformed SVG: {0}")]
+    Malformed(String),
+
+    #[error("<{element}> is missing its {attribute} attribute")]
+    MissingAttribute {
+        element: String,
+        attribute: &'static str,
+    },
+
+    #[error("<{element}> has an invalid {attribute}: {value}")]
+    InvalidAttribute {
+        element: String,
+        attribute: String,
+        value: String,
+    },
+
+    #[error("<{element}> has a malformed transform: {transform}")]
+    InvalidTransform { element: String, transform: String },
+}
+
+/// Check SVG text for well-formedness, a usable viewBox on the root, the attributes each
+/// shape needs, numeric geometry and valid transform lists. Every problem is returned.
+pub fn check_svg(svg: &str) -> Result<(), Vec<OutputError>> {
+    let document = Document::parse(svg).map_err(|e| vec![OutputError::Malformed(e.to_string())])?;
+    let root = document.root_element();
+
+    let mut errors = Vec::new();
+    if root.tag_name().name() != "svg" {
+        errors.push(OutputError::Malformed(format!(
+            "root element is <{}>, not <svg>",
+            root.tag_name().name()
+        )));
+    }
+    match root.attribute("viewBox") {
+        None => errors.push(OutputError::MissingAttribute {
+            element: "svg".to_string(),
+            attribute: "viewBox",
+        }),
+        Some(view_box) if !valid_view_box(view_box) => {
+            errors.push(OutputError::InvalidAttribute {
+                element: "svg".to_string(),
+                attribute: "viewBox".to_string(),
+                value: view_box.to_string(),
+            });
+        }
+        Some(_) => {}
+    }
+
+    for node in root.descendants().filter(Node::is_element) {
+        check_element(node, &mut errors);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn check_element(node: Node, errors: &mut Vec<OutputError>) {
+    let element = node.tag_name().name();
+
+    if let Some((_, required)) = REQUIRED_ATTRIBUTES
+        .iter()
+        .find(|(name, _)| *name == element)
+    {
+        for attribute in required.iter() {
+            if node.attribute(*attribute).is_none() {
+                errors.push(OutputError::MissingAttribute {
+                    element: element.to_string(),
+                    attribute,
+                });
+            }
+        }
+    }
+
+    // The root's size may carry units, everything inside is in page pixels
+    if element != "svg" {
+        for attribute in NUMBER_ATTRIBUTES {
+            match node.attribute(attribute) {
+                Some(value) if value.trim().parse::<f32>().is_err() => {
+                    errors.push(OutputError::InvalidAttribute {
+                        element: element.to_string(),
+                        attribute: attribute.to_string(),
+                        value: value.to_string(),
+                    });
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(transform) = node.attribute("transform") {
+        if !valid_transform(transform) {
+            errors.push(OutputError::InvalidTransform {
+                element: element.to_string(),
+                transform: transform.to_string(),
+            });
+        }
+    }
+}
+
+fn valid_view_box(view_box: &str) -> bool {
+    let numbers = numbers(view_box);
+    matches!(numbers.as_deref(), Some([_, _, width, height]) if *width > 0.0 && *height > 0.0)
+}
+
+/// A list of `name(arguments)` functions with the argument counts SVG allows
+fn valid_transform(transform: &str) -> bool {
+    let mut rest = transform.trim();
+    if rest.is_empty() {
+        return false;
+    }
+
+    while !rest.is_empty() {
+        let Some((name, after)) = rest.split_once('(') else {
+            return false;
+        };
+        let Some((arguments, after)) = after.split_once(')') else {
+            return false;
+        };
+        let Some(count) = numbers(arguments).map(|numbers| numbers.len()) else {
+            return false;
+        };
+
+        let valid = match name.trim() {
+            "translate" | "scale" => (1..=2).contains(&count),
+            "rotate" => count == 1 || count == 3,
+            "skewX" | "skewY" => count == 1,
+            "matrix" => count == 6,
+            _ => false,
+        };
+        if !valid {
+            return false;
+        }
+
+        rest = after.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+
+    true
+}
+
+/// Numbers separated by whitespace or commas, None when any of them isn't a number
+fn numbers(list: &str) -> Option<Vec<f32>> {
+    list.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::svg::SvgRenderer;
+
+    #[test]
+    fn test_check_svg() {
+        let mut renderer = SvgRenderer::new();
+        renderer.process_commands(&[]).unwrap();
+        assert_eq!(renderer.validate_output(), Ok(()));
+
+        assert!(matches!(
+            check_svg("<svg><rect></svg>").unwrap_err()[..],
+            [OutputError::Malformed(_)]
+        ));
+
+        let errors = check_svg(
+            r#"<svg xmlns="http://www.w3.org/2000/svg">
+                <rect width="10" x="left"/>
+                <g transform="translate(1,2) rotate(45 10)"/>
+            </svg>"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                OutputError::MissingAttribute {
+                    element: "svg".to_string(),
+                    attribute: "viewBox",
+                },
+                OutputError::MissingAttribute {
+                    element: "rect".to_string(),
+                    attribute: "height",
+                },
+                OutputError::InvalidAttribute {
+                    element: "rect".to_string(),
+                    attribute: "x".to_string(),
+                    value: "left".to_string(),
+                },
+                OutputError::InvalidTransform {
+                    element: "g".to_string(),
+                    transform: "translate(1,2) rotate(45 10)".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,559 @@
+use crate::parser::types::Command;
+use crate::renderer::svg::{RenderError, SvgRenderer};
+use std::fmt::Write;
+
+/// Layer of the box outlines and pin indicators
+const OUTLINE_LAYER: &str = "OUTLINES";
+/// Layer of the leader lines and their terminals
+const LEADER_LAYER: &str = "LEADERS";
+/// Layer of every piece of text
+const TEXT_LAYER: &str = "TEXT";
+
+/// Render commands with the SVG layout engine and write the result as a DXF file
+pub fn generate_dxf(commands: &[Command], output_path: &str) -> Result<(), RenderError> {
+    let mut renderer = SvgRenderer::new();
+    renderer.process_commands(commands)?;
+
+    std::fs::write(output_path, to_dxf(&renderer))?;
+    Ok(())
+}
+
+/// Convert everything a renderer has drawn to an R12 DXF drawing, in millimeters with y
+/// pointing up.
+///
+/// Boxes become closed POLYLINEs, straight leaders LINEs, waveform leaders open POLYLINEs,
+/// and text TEXT entities. Images and fills have no DXF counterpart and are left out.
+pub fn to_dxf(renderer: &SvgRenderer) -> String {
+    let (resolution, dimensions) = renderer.page_size();
+    let page = Page {
+        height: resolution.1 as f32,
+        scale: dimensions.0 / resolution.0 as f32,
+    };
+
+    let mut entities = Vec::new();
+    for node in renderer.document().get_children() {
+        collect(node.as_ref(), Matrix::IDENTITY, &mut entities);
+    }
+
+    // R12 needs no tables or objects, only the version says which entities to expect
+    let mut dxf = String::new();
+    write_pair(&mut dxf, 0, "SECTION");
+    write_pair(&mut dxf, 2, "HEADER");
+    write_pair(&mut dxf, 9, "$ACADVER");
+    write_pair(&mut dxf, 1, "AC1009");
+    write_pair(&mut dxf, 0, "ENDSEC");
+    write_pair(&mut dxf, 0, "SECTION");
+    write_pair(&mut dxf, 2, "ENTITIES");
+    for entity in &entities {
+        entity.write(&mut dxf, &page);
+    }
+    write_pair(&mut dxf, 0, "ENDSEC");
+    write_pair(&mut dxf, 0, "EOF");
+    dxf
+}
+
+/// Maps page pixels, y down, to DXF millimeters, y up
+struct Page {
+    height: f32,
+    scale: f32,
+}
+
+impl Page {
+    fn point(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (x * self.scale, (self.height - y) * self.scale)
+    }
+}
+
+/// SVG transform matrix, mapping (x, y) to (a x + c y + e, b x + d y + f)
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Matrix([f32; 6]);
+
+impl Matrix {
+    const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn then(self, inner: Matrix) -> Matrix {
+        let [a, b, c, d, e, f] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = inner.0;
+        Matrix([
+            a * a2 + c * b2,
+            b * a2 + d * b2,
+            a * c2 + c * d2,
+            b * c2 + d * d2,
+            a * e2 + c * f2 + e,
+            b * e2 + d * f2 + f,
+        ])
+    }
+
+    fn apply(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    /// Clockwise rotation of the x axis in degrees, as seen on the page
+    fn angle(&self) -> f32 {
+        self.0[1].atan2(self.0[0]).to_degrees()
+    }
+
+    fn scale(&self) -> f32 {
+        self.0[0].hypot(self.0[1])
+    }
+
+    /// Parse a transform attribute such as `translate(10,20) rotate(45 0 0)`
+    fn parse(transform: &str) -> Matrix {
+        let mut matrix = Matrix::IDENTITY;
+        for part in transform.split(')') {
+            let Some((name, args)) = part.split_once('(') else {
+                continue;
+            };
+            let args: Vec<f32> = args
+                .split([' ', ','])
+                .filter_map(|v| v.trim().parse().ok())
+                .collect();
+            let arg = |i: usize| args.get(i).copied().unwrap_or(0.0);
+
+            let step = match name.trim() {
+                "translate" => Matrix([1.0, 0.0, 0.0, 1.0, arg(0), arg(1)]),
+                "scale" => {
+                    let sy = args.get(1).copied().unwrap_or(arg(0));
+                    Matrix([arg(0), 0.0, 0.0, sy, 0.0, 0.0])
+                }
+                "rotate" => {
+                    let (sin, cos) = arg(0).to_radians().sin_cos();
+                    let (cx, cy) = (arg(1), arg(2));
+                    Matrix([1.0, 0.0, 0.0, 1.0, cx, cy])
+                        .then(Matrix([cos, sin, -sin, cos, 0.0, 0.0]))
+                        .then(Matrix([1.0, 0.0, 0.0, 1.0, -cx, -cy]))
+                }
+                "skewX" => Matrix([1.0, 0.0, arg(0).to_radians().tan(), 1.0, 0.0, 0.0]),
+                "skewY" => Matrix([1.0, arg(0).to_radians().tan(), 0.0, 1.0, 0.0, 0.0]),
+                "matrix" if args.len() == 6 => {
+                    Matrix([args[0], args[1], args[2], args[3], args[4], args[5]])
+                }
+                _ => continue,
+            };
+            matrix = matrix.then(step);
+        }
+        matrix
+    }
+}
+
+/// A drawing entity, with its points already on the page
+enum Entity {
+    Line {
+        layer: &'static str,
+        from: (f32, f32),
+        to: (f32, f32),
+    },
+    Polyline {
+        layer: &'static str,
+        points: Vec<(f32, f32)>,
+        closed: bool,
+    },
+    Circle {
+        layer: &'static str,
+        center: (f32, f32),
+        radius: f32,
+    },
+    Text {
+        position: (f32, f32),
+        height: f32,
+        angle: f32,
+        anchor: String,
+        content: String,
+    },
+}
+
+impl Entity {
+    fn write(&self, dxf: &mut String, page: &Page) {
+        match self {
+            Entity::Line { layer, from, to } => {
+                write_pair(dxf, 0, "LINE");
+                write_pair(dxf, 8, layer);
+                write_point(dxf, 10, page.point(*from));
+                write_point(dxf, 11, page.point(*to));
+            }
+            Entity::Polyline {
+                layer,
+                points,
+                closed,
+            } => {
+                // The vertices follow the polyline as entities of their own, up to SEQEND
+                write_pair(dxf, 0, "POLYLINE");
+                write_pair(dxf, 8, layer);
+                write_pair(dxf, 66, 1);
+                write_point(dxf, 10, (0.0, 0.0));
+                write_pair(dxf, 70, if *closed { 1 } else { 0 });
+                for point in points {
+                    write_pair(dxf, 0, "VERTEX");
+                    write_pair(dxf, 8, layer);
+                    write_point(dxf, 10, page.point(*point));
+                }
+                write_pair(dxf, 0, "SEQEND");
+                write_pair(dxf, 8, layer);
+            }
+            Entity::Circle {
+                layer,
+                center,
+                radius,
+            } => {
+                write_pair(dxf, 0, "CIRCLE");
+                write_pair(dxf, 8, layer);
+                write_point(dxf, 10, page.point(*center));
+                write_pair(dxf, 40, radius * page.scale);
+            }
+            Entity::Text {
+                position,
+                height,
+                angle,
+                anchor,
+                content,
+            } => {
+                let justify = match anchor.as_str() {
+                    "middle" => 1,
+                    "end" => 2,
+                    _ => 0,
+                };
+                let position = page.point(*position);
+
+                write_pair(dxf, 0, "TEXT");
+                write_pair(dxf, 8, TEXT_LAYER);
+                write_point(dxf, 10, position);
+                write_pair(dxf, 40, height * page.scale);
+                write_pair(dxf, 1, content);
+                // DXF angles turn counterclockwise with y up
+                if *angle != 0.0 {
+                    write_pair(dxf, 50, -angle);
+                }
+                if justify != 0 {
+                    write_pair(dxf, 72, justify);
+                    write_point(dxf, 11, position);
+                }
+            }
+        }
+    }
+}
+
+fn write_pair(dxf: &mut String, code: u16, value: impl std::fmt::Display) {
+    let _ = write!(dxf, "{}\n{}\n", code, value);
+}
+
+fn write_point(dxf: &mut String, code: u16, (x, y): (f32, f32)) {
+    write_pair(dxf, code, x);
+    write_pair(dxf, code + 10, y);
+    write_pair(dxf, code + 20, 0.0);
+}
+
+/// Walk a node and its children, adding an entity for every shape and text
+fn collect(node: &dyn svg::Node, parent: Matrix, entities: &mut Vec<Entity>) {
+    let Some(attributes) = node.get_attributes() else {
+        return;
+    };
+    let number = |name: &str| {
+        attributes
+            .get(name)
+            .and_then(|value| value.parse::<f32>().ok())
+    };
+    let points = |name: &str| -> Vec<(f32, f32)> {
+        let coords: Vec<f32> = attributes
+            .get(name)
+            .map(|value| {
+                value
+                    .split([' ', ','])
+                    .filter_map(|v| v.parse().ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        coords.chunks_exact(2).map(|p| (p[0], p[1])).collect()
+    };
+
+    let matrix = match attributes.get("transform") {
+        Some(transform) => parent.then(Matrix::parse(transform)),
+        None => parent,
+    };
+    let place = |points: Vec<(f32, f32)>| -> Vec<(f32, f32)> {
+        points.into_iter().map(|p| matrix.apply(p)).collect()
+    };
+
+    match node.get_name() {
+        "g" | "svg" | "a" => {
+            for child in node.get_children().into_iter().flatten() {
+                collect(child.as_ref(), matrix, entities);
+            }
+        }
+        "rect" => {
+            let (x, y) = (number("x").unwrap_or(0.0), number("y").unwrap_or(0.0));
+            let (Some(w), Some(h)) = (number("width"), number("height")) else {
+                return;
+            };
+            entities.push(Entity::Polyline {
+                layer: OUTLINE_LAYER,
+                points: place(vec![(x, y), (x + w, y), (x + w, y + h), (x, y + h)]),
+                closed: true,
+            });
+        }
+        "polygon" => entities.push(Entity::Polyline {
+            layer: OUTLINE_LAYER,
+            points: place(points("points")),
+            closed: true,
+        }),
+        "path" => {
+            // Only straight outlines, as used for cut corner boxes
+            let data = attributes
+                .get("d")
+                .map(|d| d.to_string())
+                .unwrap_or_default();
+            let coords: Vec<f32> = data
+                .split(|c: char| c.is_ascii_alphabetic() || c == ' ' || c == ',')
+                .filter_map(|v| v.parse().ok())
+                .collect();
+            let curved = data.contains(|c: char| "CcQqAaSsTt".contains(c));
+            if curved || coords.len() < 4 {
+                return;
+            }
+            entities.push(Entity::Polyline {
+                layer: OUTLINE_LAYER,
+                points: place(coords.chunks_exact(2).map(|p| (p[0], p[1])).collect()),
+                closed: data.contains(['Z', 'z']),
+            });
+        }
+        "polyline" => {
+            let points = place(points("points"));
+            match points.as_slice() {
+                [from, to] => entities.push(Entity::Line {
+                    layer: LEADER_LAYER,
+                    from: *from,
+                    to: *to,
+                }),
+                [_, _, ..] => entities.push(Entity::Polyline {
+                    layer: LEADER_LAYER,
+                    points,
+                    closed: false,
+                }),
+                _ => {}
+            }
+        }
+        "line" => {
+            let from = (number("x1").unwrap_or(0.0), number("y1").unwrap_or(0.0));
+            let to = (number("x2").unwrap_or(0.0), number("y2").unwrap_or(0.0));
+            entities.push(Entity::Line {
+                layer: LEADER_LAYER,
+                from: matrix.apply(from),
+                to: matrix.apply(to),
+            });
+        }
+        "circle" => {
+            let center = (number("cx").unwrap_or(0.0), number("cy").unwrap_or(0.0));
+            entities.push(Entity::Circle {
+                layer: LEADER_LAYER,
+                center: matrix.apply(center),
+                radius: number("r").unwrap_or(0.0) * matrix.scale(),
+            });
+        }
+        "text" => collect_text(node, matrix, entities),
+        _ => {}
+    }
+}
+
+/// One TEXT per run of a text element, its own text first, then each tspan
+fn collect_text(node: &dyn svg::Node, matrix: Matrix, entities: &mut Vec<Entity>) {
+    let Some(attributes) = node.get_attributes() else {
+        return;
+    };
+    let number = |attributes: &svg::node::Attributes, name: &str| {
+        attributes
+            .get(name)
+            .and_then(|value| value.parse::<f32>().ok())
+    };
+
+    let x = number(attributes, "x").unwrap_or(0.0);
+    let mut y = number(attributes, "y").unwrap_or(0.0);
+    let height = number(attributes, "font-size").unwrap_or(12.0);
+    let anchor = attributes
+        .get("text-anchor")
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+
+    let mut text = |position: (f32, f32), content: String| {
+        let content = unescape(content.trim());
+        if !content.is_empty() {
+            entities.push(Entity::Text {
+                position: matrix.apply(position),
+                height: height * matrix.scale(),
+                angle: matrix.angle(),
+                anchor: anchor.clone(),
+                content,
+            });
+        }
+    };
+
+    for child in node.get_children().into_iter().flatten() {
+        match child.get_attributes() {
+            // Bare text node
+            None => text((x, y), child.to_string()),
+            Some(span) => {
+                y = number(span, "y").unwrap_or(y) + number(span, "dy").unwrap_or(0.0);
+                let content = child
+                    .get_children()
+                    .into_iter()
+                    .flatten()
+                    .map(|node| node.to_string())
+                    .collect();
+                text((number(span, "x").unwrap_or(x), y), content);
+            }
+        }
+    }
+}
+
+/// Text nodes print escaped, DXF wants the characters themselves
+fn unescape(content: &str) -> String {
+    content
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::types::{JustifyX, JustifyY, Side, WireType};
+
+    #[test]
+    fn test_matrix_parse() {
+        let matrix = Matrix::parse("translate(10,20) rotate(90)");
+        let (x, y) = matrix.apply((5.0, 0.0));
+        assert!((x - 10.0).abs() < 1e-4 && (y - 25.0).abs() < 1e-4);
+        assert!((matrix.angle() - 90.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_dxf_entities() {
+        let commands = [
+            Command::Wire {
+                wire_type: WireType::Digital,
+                color: "black".to_string(),
+                opacity: 1.0,
+                thickness: 2.0,
+                amplitude: None,
+                terminal: None,
+                start_gap: None,
+                stroke_opacity: None,
+                dash: None,
+            },
+            Command::Draw,
+            Command::Box {
+                theme: "PLAIN".to_string(),
+                x: 10.0,
+                y: 10.0,
+                box_width: Some(40.0),
+                box_height: Some(20.0),
+                x_justify: None,
+                y_justify: None,
+                message: Some("Board".to_string()),
+                text_anchor: None,
+            },
+            Command::Anchor { x: 100.0, y: 100.0 },
+            Command::PinSet {
+                side: Side::Right,
+                packed: true,
+                justify_x: JustifyX::Center,
+                justify_y: JustifyY::Center,
+                line_step: 25.0,
+                pin_width: 10.0,
+                group_width: 40.0,
+                leader_offset: 40.0,
+                column_gap: 5.0,
+                column_gap_percent: false,
+                leader_h_step: 0.0,
+                angle: None,
+                units: None,
+                advance: None,
+                first_number: None,
+                box_gap: None,
+                frame: None,
+                title: None,
+            },
+            Command::Pin {
+                wire: Some(WireType::Digital),
+                pin_type: None,
+                group: None,
+                attributes: Vec::new(),
+                leader: None,
+            },
+            Command::Pin {
+                wire: Some(WireType::Digital),
+                pin_type: None,
+                group: None,
+                attributes: Vec::new(),
+                leader: None,
+            },
+        ];
+
+        let path = std::env::temp_dir().join("pinout_entities.dxf");
+        generate_dxf(&commands, path.to_str().unwrap()).unwrap();
+        let dxf = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let count = |entity: &str| {
+            dxf.lines()
+                .collect::<Vec<_>>()
+                .windows(2)
+                .filter(|pair| pair[0] == "0" && pair[1] == entity)
+                .count()
+        };
+        assert!(
+            dxf.starts_with("0\nSECTION\n2\nHEADER\n9\n$ACADVER\n1\nAC1009\n0\nENDSEC\n"),
+            "{}",
+            dxf
+        );
+        assert!(dxf.ends_with("0\nENDSEC\n0\nEOF\n"), "{}", dxf);
+        assert_eq!(count("POLYLINE"), 1, "{}", dxf);
+        assert_eq!(count("VERTEX"), 4, "{}", dxf);
+        assert_eq!(count("SEQEND"), 1, "{}", dxf);
+        assert_eq!(count("LINE"), 2, "{}", dxf);
+        assert_eq!(count("TEXT"), 1, "{}", dxf);
+        assert!(dxf.contains("\n1\nBoard\n"), "{}", dxf);
+    }
+
+    #[test]
+    fn test_dxf_rotated_page() {
+        let mut renderer = SvgRenderer::new();
+        renderer.set_rotated(true);
+        renderer
+            .process_commands(&[
+                Command::Draw,
+                Command::Box {
+                    theme: "PLAIN".to_string(),
+                    x: 10.0,
+                    y: 10.0,
+                    box_width: Some(40.0),
+                    box_height: Some(20.0),
+                    x_justify: None,
+                    y_justify: None,
+                    message: None,
+                    text_anchor: None,
+                },
+            ])
+            .unwrap();
+        let dxf = to_dxf(&renderer);
+
+        // A4 turned upright, 210mm wide and 297mm high. The box hugs the top right corner,
+        // its y values are flipped against the taller side.
+        let lines: Vec<&str> = dxf.lines().collect();
+        let vertices: Vec<(f32, f32)> = lines
+            .windows(8)
+            .filter(|entry| entry[0] == "0" && entry[1] == "VERTEX")
+            .map(|entry| (entry[5].parse().unwrap(), entry[7].parse().unwrap()))
+            .collect();
+        assert_eq!(vertices.len(), 4, "{}", dxf);
+        let scale = 210.0 / 2480.0;
+        for (x, y) in vertices {
+            assert!(
+                (2450.0 * scale - 0.01..=2470.0 * scale + 0.01).contains(&x),
+                "{}",
+                x
+            );
+            assert!(((3507.0 - 50.0) * scale - 0.01..=(3507.0 - 10.0) * scale + 0.01).contains(&y));
+        }
+    }
+}
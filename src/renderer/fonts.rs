@@ -0,0 +1,203 @@
+use crate::renderer::svg::RenderError;
+use base64::{Engine, engine::general_purpose};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where fonts may be fetched from. Any other URL could read local files or other hosts.
+const FONT_HOSTS: [&str; 2] = [
+    "https://fonts.googleapis.com/",
+    "https://fonts.gstatic.com/",
+];
+
+/// Source of remote font data, swappable so tests and offline builds can provide their own
+pub trait FontFetcher {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, RenderError>;
+}
+
+/// Fetches fonts over the network by calling out to `curl`
+#[cfg(feature = "network")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CurlFetcher;
+
+#[cfg(feature = "network")]
+impl FontFetcher for CurlFetcher {
+    fn fetch(&self, url: &str) -> Result<Vec<u8>, RenderError> {
+        check_font_url(url)?;
+
+        // Google serves woff2 only to browsers that advertise support for it. Redirects stay
+        // on https, and the URL can never be taken for an option.
+        let output = std::process::Command::new("curl")
+            .args([
+                "-sfL",
+                "--proto",
+                "=https",
+                "--proto-redir",
+                "=https",
+                "-A",
+                "Mozilla/5.0 AppleWebKit/537.36 Chrome/120.0",
+                "--",
+                url,
+            ])
+            .output()?;
+
+        if !output.status.success() {
+            return Err(RenderError::IoError(std::io::Error::other(format!(
+                "curl failed to fetch {}: {}",
+                url, output.status
+            ))));
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+/// Check that a URL is on Google Fonts, the only place fonts are fetched from, and can be
+/// quoted in a CSS `url('...')` without ending it
+pub fn check_font_url(url: &str) -> Result<(), RenderError> {
+    let quotable = !url.contains(|c: char| {
+        matches!(c, '\'' | '"' | '(' | ')' | '\\') || c.is_whitespace() || c.is_control()
+    });
+    if !quotable {
+        Err(RenderError::SvgError(format!(
+            "Font URL {} has a quote, bracket, backslash or space in it",
+            url
+        )))
+    } else if FONT_HOSTS.iter().any(|host| url.starts_with(host)) {
+        Ok(())
+    } else {
+        Err(RenderError::SvgError(format!(
+            "Font URL {} is not on {}",
+            url,
+            FONT_HOSTS.join(" or ")
+        )))
+    }
+}
+
+/// Downloads a Google Fonts stylesheet and every font it references, returning the
+/// stylesheet with the fonts inlined as base64 data URLs.
+///
+/// Downloads are kept in `cache_dir` when set, so later renders work offline.
+pub fn embed_google_font(
+    link: &str,
+    fetcher: &dyn FontFetcher,
+    cache_dir: Option<&Path>,
+) -> Result<String, RenderError> {
+    check_font_url(link)?;
+    let css = fetch_cached(link, fetcher, cache_dir)?;
+    let css = String::from_utf8_lossy(&css);
+
+    let mut embedded = String::with_capacity(css.len());
+    let mut rest = css.as_ref();
+
+    while let Some(start) = rest.find("url(") {
+        let end = rest[start..]
+            .find(')')
+            .map(|end| start + end)
+            .ok_or_else(|| {
+                RenderError::SvgError(format!("Unterminated url() in font stylesheet {}", link))
+            })?;
+
+        let url = rest[start + 4..end].trim().trim_matches(['"', '\'']);
+        check_font_url(url)?;
+        let data = fetch_cached(url, fetcher, cache_dir)?;
+
+        embedded.push_str(&rest[..start]);
+        embedded.push_str(&format!(
+            "url(data:{};base64,{})",
+            font_mime_type(url),
+            general_purpose::STANDARD.encode(data)
+        ));
+        rest = &rest[end + 1..];
+    }
+    embedded.push_str(rest);
+
+    Ok(embedded)
+}
+
+fn fetch_cached(
+    url: &str,
+    fetcher: &dyn FontFetcher,
+    cache_dir: Option<&Path>,
+) -> Result<Vec<u8>, RenderError> {
+    let cache_path = cache_dir.map(|dir| cache_file(dir, url));
+
+    if let Some(path) = &cache_path {
+        if let Ok(data) = fs::read(path) {
+            return Ok(data);
+        }
+    }
+
+    let data = fetcher.fetch(url)?;
+
+    if let Some(path) = &cache_path {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, &data)?;
+    }
+
+    Ok(data)
+}
+
+/// Named by the URL's FNV-1a hash, which unlike `DefaultHasher` stays the same across Rust
+/// releases, so a cache keeps working after a toolchain upgrade
+fn cache_file(dir: &Path, url: &str) -> PathBuf {
+    let hash = url.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    dir.join(format!("{:016x}", hash))
+}
+
+fn font_mime_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("woff2") => "font/woff2",
+        Some("woff") => "font/woff",
+        Some("otf") => "font/otf",
+        _ => "font/ttf",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_font_url() {
+        assert!(check_font_url("https://fonts.googleapis.com/css2?family=Roboto").is_ok());
+        assert!(check_font_url("https://fonts.gstatic.com/s/roboto/v30/font.woff2").is_ok());
+        for url in [
+            "file:///etc/passwd",
+            "http://fonts.googleapis.com/css2?family=Roboto",
+            "https://fonts.googleapis.com.example.com/css2",
+            "-o/tmp/out https://fonts.googleapis.com/",
+            "https://fonts.googleapis.com/css2?family=A');} text { display: none",
+        ] {
+            assert!(check_font_url(url).is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_font_outside_google_not_fetched() {
+        struct Stylesheet;
+        impl FontFetcher for Stylesheet {
+            fn fetch(&self, url: &str) -> Result<Vec<u8>, RenderError> {
+                assert_eq!(url, "https://fonts.googleapis.com/css2?family=Roboto");
+                Ok(b"src: url(file:///etc/passwd)".to_vec())
+            }
+        }
+
+        let link = "https://fonts.googleapis.com/css2?family=Roboto";
+        assert!(embed_google_font(link, &Stylesheet, None).is_err());
+        assert!(embed_google_font("file:///etc/passwd", &Stylesheet, None).is_err());
+    }
+
+    #[test]
+    fn test_cache_file_is_stable() {
+        let path = cache_file(
+            Path::new("cache"),
+            "https://fonts.googleapis.com/css2?family=Roboto",
+        );
+        assert_eq!(path, Path::new("cache").join("15e2d215bd1cbe7e"));
+    }
+}
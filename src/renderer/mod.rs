@@ -1 +1,7 @@
+#[cfg(feature = "validate-output")]
+pub mod check;
+pub mod dxf;
+pub mod fonts;
+#[cfg(feature = "outline-text")]
+pub mod outline;
 pub mod svg;
@@ -0,0 +1,224 @@
+use crate::renderer::svg::RenderError;
+use std::fmt::Write;
+use svg::node::element::{Group, Path, Text};
+use svg::node::{Attributes, Value};
+use ttf_parser::{Face, OutlineBuilder};
+
+/// Presentation attributes carried over from a text element to its outlines
+const PAINT_ATTRIBUTES: [&str; 5] = [
+    "fill",
+    "stroke",
+    "stroke-width",
+    "stroke-opacity",
+    "opacity",
+];
+
+/// Turns text elements into glyph outlines, so output looks the same without the font installed
+#[derive(Debug, Clone)]
+pub struct TextOutliner {
+    font: Vec<u8>,
+}
+
+/// A piece of text sharing one position and paint, i.e. a text node or a tspan
+struct Run {
+    content: String,
+    x: Option<f32>,
+    y: Option<f32>,
+    dy: f32,
+    attributes: Attributes,
+}
+
+impl TextOutliner {
+    /// Use the given TrueType or OpenType font data for every outline
+    pub fn new(font: Vec<u8>) -> Result<Self, RenderError> {
+        Face::parse(&font, 0)
+            .map_err(|e| RenderError::SvgError(format!("Invalid outline font: {}", e)))?;
+        Ok(TextOutliner { font })
+    }
+
+    /// Convert a text element into a group holding one path per text node or tspan
+    pub fn outline(&self, text: &Text) -> Group {
+        // Checked when the outliner was created
+        let face = Face::parse(&self.font, 0).expect("outline font was validated");
+        let attributes = text.get_attributes();
+        let font_size = number(attributes, "font-size").unwrap_or(12.0);
+        let scale = font_size / face.units_per_em() as f32;
+        let anchor = attributes
+            .get("text-anchor")
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "start".to_string());
+
+        let mut group = Group::new();
+        for name in PAINT_ATTRIBUTES.iter().chain(["transform"].iter()) {
+            if let Some(value) = attributes.get(*name) {
+                group = group.set(*name, value.clone());
+            }
+        }
+
+        let runs = collect_runs(text);
+        let mut pen_x = number(attributes, "x").unwrap_or(0.0);
+        let mut pen_y = number(attributes, "y").unwrap_or(0.0);
+
+        // Anchoring applies to each chunk of runs that starts at an explicit x
+        let mut index = 0;
+        while index < runs.len() {
+            let end = runs[index + 1..]
+                .iter()
+                .position(|run| run.x.is_some())
+                .map_or(runs.len(), |offset| index + 1 + offset);
+
+            let chunk_width: f32 = runs[index..end]
+                .iter()
+                .map(|run| advance(&face, &run.content) * scale)
+                .sum();
+
+            if let Some(x) = runs[index].x {
+                pen_x = x;
+            }
+            pen_x -= match anchor.as_str() {
+                "middle" => chunk_width / 2.0,
+                "end" => chunk_width,
+                _ => 0.0,
+            };
+
+            for run in &runs[index..end] {
+                pen_y = run.y.unwrap_or(pen_y) + run.dy;
+
+                let mut builder = PathBuilder {
+                    data: String::new(),
+                    x: pen_x,
+                    y: pen_y,
+                    scale,
+                };
+                pen_x += outline_run(&face, &run.content, &mut builder);
+
+                if builder.data.is_empty() {
+                    continue;
+                }
+
+                let mut path = Path::new().set("d", builder.data);
+                for name in PAINT_ATTRIBUTES {
+                    if let Some(value) = run.attributes.get(name) {
+                        path = path.set(name, value.clone());
+                    }
+                }
+                group = group.add(path);
+            }
+
+            index = end;
+        }
+
+        group
+    }
+}
+
+/// Flatten the text element and its tspans into positioned runs
+fn collect_runs(text: &Text) -> Vec<Run> {
+    let mut runs = Vec::new();
+    for child in text.get_children() {
+        match child.get_attributes() {
+            // Bare text node
+            None => runs.push(Run {
+                content: unescape(&child.to_string()),
+                x: None,
+                y: None,
+                dy: 0.0,
+                attributes: Attributes::new(),
+            }),
+            Some(attributes) => runs.push(Run {
+                content: child
+                    .get_children()
+                    .into_iter()
+                    .flatten()
+                    .map(|node| unescape(&node.to_string()))
+                    .collect(),
+                x: number(attributes, "x"),
+                y: number(attributes, "y"),
+                dy: number(attributes, "dy").unwrap_or(0.0),
+                attributes: attributes.clone(),
+            }),
+        }
+    }
+    runs
+}
+
+fn number(attributes: &Attributes, name: &str) -> Option<f32> {
+    attributes
+        .get(name)
+        .and_then(|value: &Value| value.parse::<f32>().ok())
+}
+
+/// Width of the text in font units
+fn advance(face: &Face, content: &str) -> f32 {
+    content
+        .chars()
+        .filter_map(|c| face.glyph_index(c))
+        .filter_map(|glyph| face.glyph_hor_advance(glyph))
+        .map(f32::from)
+        .sum()
+}
+
+/// Append the outline of every glyph to the builder, returning the width drawn
+fn outline_run(face: &Face, content: &str, builder: &mut PathBuilder) -> f32 {
+    let start = builder.x;
+    for c in content.chars() {
+        let Some(glyph) = face.glyph_index(c) else {
+            continue;
+        };
+        face.outline_glyph(glyph, builder);
+        builder.x += f32::from(face.glyph_hor_advance(glyph).unwrap_or(0)) * builder.scale;
+    }
+    builder.x - start
+}
+
+/// Text nodes print escaped, outlines need the characters themselves
+fn unescape(content: &str) -> String {
+    content
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Writes glyph outlines as SVG path data, placed at the pen position
+struct PathBuilder {
+    data: String,
+    x: f32,
+    y: f32,
+    scale: f32,
+}
+
+impl PathBuilder {
+    /// Font units point up from the baseline, SVG units point down
+    fn point(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.x + x * self.scale, self.y - y * self.scale)
+    }
+}
+
+impl OutlineBuilder for PathBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.point(x, y);
+        let _ = write!(self.data, "M{} {}", x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.point(x, y);
+        let _ = write!(self.data, "L{} {}", x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (x1, y1) = self.point(x1, y1);
+        let (x, y) = self.point(x, y);
+        let _ = write!(self.data, "Q{} {} {} {}", x1, y1, x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (x1, y1) = self.point(x1, y1);
+        let (x2, y2) = self.point(x2, y2);
+        let (x, y) = self.point(x, y);
+        let _ = write!(self.data, "C{} {} {} {} {} {}", x1, y1, x2, y2, x, y);
+    }
+
+    fn close(&mut self) {
+        self.data.push('Z');
+    }
+}
@@ -1,16 +1,26 @@
+use crate::parser::csv::parse_pin_command;
 use crate::parser::types::{
-    Command, FontBoldness, FontSlant, FontStretch, JustifyX, JustifyY, Phase, PinType, Side,
-    WireType,
+    BoxShape, Command, Corner, FontBoldness, FontSlant, FontStretch, GroupShape, JustifyX,
+    JustifyY, Phase, PinType, Side, Terminal, Unit, WireType,
 };
+#[cfg(feature = "validate-output")]
+use crate::renderer::check::{OutputError, check_svg};
+use crate::renderer::fonts::{self, FontFetcher};
+#[cfg(feature = "outline-text")]
+use crate::renderer::outline::TextOutliner;
 use base64::{Engine, engine::general_purpose};
+use csv::StringRecord;
 use image::ImageFormat;
-use std::collections::HashMap;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use svg::Document;
 use svg::node::element::{
-    Circle, Definitions, Group, Image, Polygon, Polyline, Rectangle, TSpan, Text,
+    Circle, Definitions, Group, Image, Marker, Path as SvgPath, Polygon, Polyline, Rectangle,
+    Style, Symbol, TSpan, Text, Use,
 };
 use svg::node::{Text as TextNode, Value};
 use thiserror::Error;
@@ -139,6 +149,287 @@ pub enum RenderError {
 
     #[error("Missing required command data: {0}")]
     MissingData(String),
+
+    #[error("Input limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    #[error("PNG rasterization error: {0}")]
+    RasterError(String),
+}
+
+/// Box theme used for the attribute boxes of every pin
+const PIN_BOX_THEME: &str = "BOX_SKEWED";
+
+/// Separates alternate values of a pin attribute, which are stacked in its box
+const PIN_VALUE_DELIMITER: char = '|';
+
+/// Size of a BOX whose command and theme leave out a dimension, see
+/// [`SvgRenderer::set_default_box_size`]
+const DEFAULT_BOX_SIZE: (f32, f32) = (80.0, 20.0);
+
+/// Environment variable with the default page size, see [`RenderOptions::from_env`]
+pub const PAGE_ENV: &str = "PINOUT_PAGE";
+
+/// Environment variable with the default DPI, see [`RenderOptions::from_env`]
+pub const DPI_ENV: &str = "PINOUT_DPI";
+
+/// Box theme drawn in place of one that isn't defined, set up with `BOX, DEFAULT, ...`
+const DEFAULT_BOX_THEME: &str = "BOX_DEFAULT";
+
+/// Colors of boxes where neither their themes, DEFAULT nor BOX_DEFAULT set one
+const DEFAULT_BOX_COLORS: [(&str, &str); 3] = [
+    ("BORDER COLOR", "red"),
+    ("FILL COLOR", "blue"),
+    ("FONT COLOR", "yellow"),
+];
+
+/// Attribute marking the row of a radial pin set an element belongs to, until it is placed
+const RADIAL_ROW: &str = "data-radial-row";
+
+/// Token in box text that starts a new line, written as backslash n in the CSV
+const DEFAULT_LINE_BREAK: &str = "\\n";
+
+/// Space between a pin set and the frame drawn around it
+const PIN_SET_FRAME_PADDING: f32 = 10.0;
+
+/// Non-fatal problems found while rendering
+#[derive(Debug, Clone, PartialEq, Error)]
+pub enum RenderWarning {
+    #[error("Box at ({},{}) overlaps box at ({},{})", .first.x, .first.y, .second.x, .second.y)]
+    BoxOverlap { first: Extent, second: Extent },
+
+    #[error("Boxes for {label} have zero width, is box theme {theme} defined?")]
+    ZeroWidthBox { label: String, theme: String },
+
+    #[error("Box theme {theme} has no size, drawn at the default {width}x{height}")]
+    DefaultBoxSize {
+        theme: String,
+        width: f32,
+        height: f32,
+    },
+
+    #[error("Box with theme {theme} has zero size and will not be visible")]
+    ZeroSizeBox { theme: String },
+
+    #[error("Theme {theme} is defined more than once, the last definition is used")]
+    DuplicateTheme { theme: String },
+
+    #[error("Could not embed Google font {link}: {reason}")]
+    FontFetch { link: String, reason: String },
+
+    #[error("Anchor at ({x},{y}) is replaced before anything is drawn from it")]
+    UnusedAnchor { x: f32, y: f32 },
+
+    #[error("Box theme {theme} is not defined, drawn with BOX_DEFAULT")]
+    MissingBoxTheme { theme: String },
+}
+
+impl RenderWarning {
+    /// Content that doesn't render at all counts as an error, content drawn over other
+    /// content as an overflow
+    pub fn issue_kind(&self) -> IssueKind {
+        match self {
+            RenderWarning::ZeroWidthBox { .. } | RenderWarning::ZeroSizeBox { .. } => {
+                IssueKind::Error
+            }
+            RenderWarning::BoxOverlap { .. } => IssueKind::Overflow,
+            RenderWarning::DefaultBoxSize { .. }
+            | RenderWarning::DuplicateTheme { .. }
+            | RenderWarning::FontFetch { .. }
+            | RenderWarning::UnusedAnchor { .. }
+            | RenderWarning::MissingBoxTheme { .. } => IssueKind::Warning,
+        }
+    }
+}
+
+/// How serious a collected diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    Error,
+    Warning,
+    Overflow,
+}
+
+/// Count of the diagnostics collected during a render, by kind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IssueSummary {
+    pub errors: usize,
+    pub warnings: usize,
+    pub overflows: usize,
+}
+
+/// A pin group, its indicator color and the pins drawn in it, in drawing order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupSummary {
+    pub name: String,
+    pub color: String,
+    pub pins: Vec<String>,
+}
+
+/// Every renderer option in one place. The defaults match a plain `SvgRenderer::new()`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    /// Wrap pin groups and pin function boxes in Inkscape layers
+    pub inkscape_layers: bool,
+    /// Give elements ids and record them against the command that drew them
+    pub source_tracking: bool,
+    /// Page width and height in pixels instead of millimeters
+    pub pixel_dimensions: bool,
+    /// Multiplier for the displayed page size, the viewBox is unchanged
+    pub device_pixel_ratio: f32,
+    /// `shape-rendering` hint for the page, left to the viewer when None
+    pub shape_rendering: Option<String>,
+    /// Size of BOX commands without a width or height of their own
+    pub default_box_size: (f32, f32),
+    /// Label boxes and pins with their coordinates
+    pub debug_labels: bool,
+    /// Warn when a box overlaps an earlier one
+    pub overlap_warnings: bool,
+    /// Outline the empty cells of unpacked pin sets
+    pub empty_cell_outlines: bool,
+    /// Black on white boxes and pins, whatever the themes say
+    pub high_contrast: bool,
+    /// Color and stroke width of a light outline behind box and message text, none when None
+    pub text_halo: Option<(String, f32)>,
+    /// Draw identical leaders and pin type markers as uses of one shared symbol
+    pub pin_symbols: bool,
+    /// Token that breaks box text onto a new line
+    pub line_break: String,
+    /// Turn the page a quarter turn clockwise
+    pub rotated: bool,
+    /// Largest IMAGE, ICON or PINDATA file in bytes, unlimited when None
+    pub max_embed_size: Option<u64>,
+    /// Layers to draw, all of them when None
+    pub enabled_layers: Option<Vec<String>>,
+    /// Page size until a PAGE command sets one, A4-L when None
+    pub page: Option<String>,
+    /// DPI until a DPI command sets one, 300 when None
+    pub dpi: Option<u32>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            inkscape_layers: false,
+            source_tracking: false,
+            pixel_dimensions: false,
+            device_pixel_ratio: 1.0,
+            shape_rendering: None,
+            default_box_size: DEFAULT_BOX_SIZE,
+            debug_labels: false,
+            overlap_warnings: false,
+            empty_cell_outlines: false,
+            high_contrast: false,
+            text_halo: None,
+            pin_symbols: false,
+            line_break: DEFAULT_LINE_BREAK.to_string(),
+            rotated: false,
+            max_embed_size: None,
+            enabled_layers: None,
+            page: None,
+            dpi: None,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Default options with the page size and DPI taken from `PINOUT_PAGE` and `PINOUT_DPI`
+    /// when they are set, so a batch of files can share them. PAGE and DPI commands in a
+    /// file still win.
+    pub fn from_env() -> Result<Self, RenderError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Same as [`Self::from_env`], looking the variables up with `var` instead
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, RenderError> {
+        let dpi = match var(DPI_ENV) {
+            Some(dpi) => Some(
+                dpi.trim()
+                    .parse::<u32>()
+                    .map_err(|_| RenderError::SvgError(format!("Invalid {}: {}", DPI_ENV, dpi)))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            page: var(PAGE_ENV),
+            dpi,
+            ..Self::default()
+        })
+    }
+}
+
+/// Rectangle covered by a drawn element, in page pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Extent {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Extent {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Extent {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Smallest rectangle covering both
+    pub fn union(&self, other: &Extent) -> Extent {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Extent {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+
+    /// True when the two rectangles share some area, touching edges do not count
+    pub fn intersects(&self, other: &Extent) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+/// Handler for a custom command, returning the SVG nodes to add to the document
+///
+/// The record holds the command name in field 0, followed by its options.
+pub type CommandHandler =
+    Box<dyn Fn(&StringRecord) -> Result<Vec<Box<dyn svg::Node>>, RenderError>>;
+
+/// Gradient from a low to a high color over a range of pin attribute values
+#[derive(Debug, Clone, Copy)]
+struct ColorScale {
+    min: f32,
+    max: f32,
+    low: [u8; 3],
+    high: [u8; 3],
+}
+
+impl ColorScale {
+    /// Interpolated color for a value, clamped to the ends of the scale
+    fn color_at(&self, value: f32) -> String {
+        let t = ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+        let channel = |i: usize| {
+            (self.low[i] as f32 + (self.high[i] as f32 - self.low[i] as f32) * t).round() as u8
+        };
+        format!("#{:02x}{:02x}{:02x}", channel(0), channel(1), channel(2))
+    }
+}
+
+/// Child counts of the document, its layers and the leader group, taken before drawing
+struct ElementMarks {
+    document: usize,
+    layers: Vec<usize>,
+    leaders: usize,
 }
 
 pub struct SvgRenderer {
@@ -154,9 +445,71 @@ pub struct SvgRenderer {
     offset_y: f32,
     line_settings: HashMap<String, Value>,
     message_settings: HashMap<String, Value>,
+    message_tab_stops: Vec<f32>,
+    message_tab: usize,
     current_text: Option<Text>,
     pin_func_types: Vec<String>,
     definitions: Definitions,
+    google_fonts: Vec<String>,
+    sheet_number: usize,
+    sheet_count: usize,
+    handlers: HashMap<String, CommandHandler>,
+    inkscape_layers: bool,
+    layers: Vec<(String, Group)>,
+    box_extents: Vec<Extent>,
+    warn_overlaps: bool,
+    empty_cell_outlines: bool,
+    high_contrast: bool,
+    text_halo: Option<(String, f32)>,
+    pin_symbols: bool,
+    symbol_keys: Vec<String>,
+    arrow_markers: bool,
+    line_break: String,
+    auto_orient: bool,
+    group_pins: Vec<(String, Vec<String>)>,
+    current_layer: Option<String>,
+    rotated: bool,
+    // Whether the document's content is wrapped in the group turning it onto the page
+    content_rotated: bool,
+    max_embed_size: Option<u64>,
+    embedded_bytes: usize,
+    enabled_layers: Option<HashSet<String>>,
+    warnings: Vec<RenderWarning>,
+    pin_row: usize,
+    pin_count: usize,
+    font_fetcher: Option<Box<dyn FontFetcher>>,
+    font_cache_dir: Option<PathBuf>,
+    pixel_dimensions: bool,
+    device_pixel_ratio: f32,
+    debug_labels: bool,
+    track_sources: bool,
+    source_map: HashMap<usize, Vec<String>>,
+    // Index of the command being run
+    current_command: usize,
+    pin_set_extent: Option<Extent>,
+    pin_set_first_child: usize,
+    // Where a radial pin set started drawing, and the first box of each of its rows
+    radial: Option<ElementMarks>,
+    radial_rows: Vec<usize>,
+    leader_layer: Option<usize>,
+    // Box theme, padding and the SHELL command's index of the pending shell
+    shell: Option<(String, f32, usize)>,
+    // Index of the command that started the current pin set, its frame is drawn for it
+    pin_set_source: usize,
+    // Commands of the most recent pin set, and where it started, so MIRROR can replay them
+    pin_set_commands: Vec<Command>,
+    pin_set_origin: (f32, f32, f32),
+    default_box_size: (f32, f32),
+    color_scales: HashMap<Option<String>, ColorScale>,
+    match_rules: Vec<(Regex, String)>,
+    #[cfg(feature = "outline-text")]
+    text_outliner: Option<TextOutliner>,
+}
+
+impl Default for SvgRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SvgRenderer {
@@ -192,20 +545,81 @@ impl SvgRenderer {
             offset_y: 0.0,
             line_settings: HashMap::new(),
             message_settings: HashMap::new(),
+            message_tab_stops: Vec::new(),
+            message_tab: 0,
             current_text: None,
             pin_func_types: Vec::new(),
             definitions: Definitions::new(),
+            google_fonts: Vec::new(),
+            sheet_number: 1,
+            sheet_count: 1,
+            handlers: HashMap::new(),
+            inkscape_layers: false,
+            layers: Vec::new(),
+            box_extents: Vec::new(),
+            warn_overlaps: false,
+            empty_cell_outlines: false,
+            high_contrast: false,
+            text_halo: None,
+            pin_symbols: false,
+            symbol_keys: Vec::new(),
+            arrow_markers: false,
+            line_break: DEFAULT_LINE_BREAK.to_string(),
+            auto_orient: false,
+            group_pins: Vec::new(),
+            current_layer: None,
+            rotated: false,
+            content_rotated: false,
+            max_embed_size: None,
+            embedded_bytes: 0,
+            enabled_layers: None,
+            warnings: Vec::new(),
+            pin_row: 0,
+            pin_count: 0,
+            #[cfg(feature = "network")]
+            font_fetcher: Some(Box::new(fonts::CurlFetcher)),
+            #[cfg(not(feature = "network"))]
+            font_fetcher: None,
+            font_cache_dir: None,
+            pixel_dimensions: false,
+            device_pixel_ratio: 1.0,
+            debug_labels: false,
+            track_sources: false,
+            source_map: HashMap::new(),
+            current_command: 0,
+            pin_set_extent: None,
+            pin_set_first_child: 0,
+            radial: None,
+            radial_rows: Vec::new(),
+            leader_layer: None,
+            shell: None,
+            pin_set_source: 0,
+            pin_set_commands: Vec::new(),
+            pin_set_origin: (0.0, 0.0, 0.0),
+            default_box_size: DEFAULT_BOX_SIZE,
+            color_scales: HashMap::new(),
+            match_rules: Vec::new(),
+            #[cfg(feature = "outline-text")]
+            text_outliner: None,
         }
     }
 
+    /// Draw `commands` onto the page. Calling it again draws more onto the same page, the
+    /// definitions block, rotation and layers are then set up once more rather than twice.
     pub fn process_commands(&mut self, commands: &[Command]) -> Result<(), RenderError> {
+        self.reopen_document();
+
         let mut phase = Phase::Setup;
+        // An anchor nothing has been drawn from yet
+        let mut pending_anchor: Option<(f32, f32)> = None;
 
-        for command in commands {
+        for (index, command) in commands.iter().enumerate() {
             match (command, phase) {
                 (Command::Draw, Phase::Setup) => {
                     // Transition from Setup to Draw phase
                     self.check_boxes()?;
+                    let box_warnings = self.validate_box_widths();
+                    self.warnings.extend(box_warnings);
                     phase = Phase::Draw;
                 }
                 (cmd, current_phase) => {
@@ -217,7 +631,24 @@ impl SvgRenderer {
                         });
                     }
 
+                    // Commands on a disabled layer are skipped, only LAYER itself still applies
+                    if !matches!(cmd, Command::Layer { .. }) && !self.layer_enabled() {
+                        continue;
+                    }
+
+                    if let (Command::Anchor { .. }, Some((x, y))) = (cmd, pending_anchor) {
+                        self.warnings.push(RenderWarning::UnusedAnchor { x, y });
+                    }
+                    pending_anchor = match cmd {
+                        Command::Anchor { x, y } => Some((*x, *y)),
+                        _ => None,
+                    };
+
+                    self.current_command = index;
                     self.execute_command(cmd)?;
+                    if self.track_sources {
+                        self.tag_new_elements(index);
+                    }
                 }
             }
         }
@@ -227,12 +658,92 @@ impl SvgRenderer {
             self.end_message()?;
         }
 
-        // Add definitions to document
-        self.document = self.document.clone().add(self.definitions.clone());
+        // Close off the last pin set
+        self.finish_pin_set();
+
+        if self.auto_orient {
+            self.orient_to_content()?;
+        }
+
+        // Add any Inkscape layers to document
+        if !self.layers.is_empty() {
+            svg::Node::assign(
+                &mut self.document,
+                "xmlns:inkscape",
+                "http://www.inkscape.org/namespaces/inkscape",
+            );
+            for (_, layer) in std::mem::take(&mut self.layers) {
+                svg::Node::append(&mut self.document, layer);
+            }
+        }
+
+        // Turn everything drawn a quarter turn clockwise onto the swapped page
+        if self.rotated {
+            let content = std::mem::take(self.document.get_children_mut());
+            let rotated = content.into_iter().fold(
+                Group::new().set(
+                    "transform",
+                    format!("translate({} 0) rotate(90)", self.page_resolution.1),
+                ),
+                |group, node| group.add(node),
+            );
+            svg::Node::append(&mut self.document, rotated);
+            self.content_rotated = true;
+        }
+
+        // Definitions go first, so styles and fonts are in place before any text uses them
+        self.document
+            .get_children_mut()
+            .insert(0, Box::new(self.definitions.clone()));
 
         Ok(())
     }
 
+    /// Undo what finishing an earlier `process_commands` did to the document: take out the
+    /// definitions, unwrap the rotated content and hand the Inkscape layers back for drawing
+    fn reopen_document(&mut self) {
+        let children = self.document.get_children_mut();
+        children.retain(|node| node.get_name() != "defs");
+
+        if std::mem::take(&mut self.content_rotated) {
+            if let Some(mut rotated) = children.pop() {
+                children.extend(
+                    rotated
+                        .get_children_mut()
+                        .map(std::mem::take)
+                        .unwrap_or_default(),
+                );
+            }
+        }
+
+        let (layers, content): (Vec<_>, Vec<_>) =
+            std::mem::take(children).into_iter().partition(|node| {
+                node.get_attributes()
+                    .and_then(|attributes| attributes.get("inkscape:groupmode"))
+                    .is_some_and(|mode| &**mode == "layer")
+            });
+        *children = content;
+        for mut node in layers {
+            let mut layer = Group::new();
+            for (name, value) in node.get_attributes().into_iter().flatten() {
+                svg::Node::assign(&mut layer, name.as_str(), value.clone());
+            }
+            for child in node
+                .get_children_mut()
+                .map(std::mem::take)
+                .unwrap_or_default()
+            {
+                svg::Node::append(&mut layer, child);
+            }
+            let name = layer
+                .get_attributes()
+                .get("inkscape:label")
+                .map(|label| label.to_string())
+                .unwrap_or_default();
+            self.layers.push((name, layer));
+        }
+    }
+
     fn get_command_phase(&self, command: &Command) -> Phase {
         match command {
             Command::Draw => Phase::Setup, // Special case handled separately
@@ -255,9 +766,12 @@ impl SvgRenderer {
             Command::Type { .. } => Phase::Setup,
             Command::Wire { .. } => Phase::Setup,
             Command::Group { .. } => Phase::Setup,
+            Command::ColorScale { .. } => Phase::Setup,
+            Command::Match { .. } => Phase::Setup,
             Command::BoxTheme { .. } => Phase::Setup,
             Command::TextFont { .. } => Phase::Setup,
             Command::Page { .. } => Phase::Setup,
+            Command::AutoOrient => Phase::Setup,
             Command::Dpi { .. } => Phase::Setup,
 
             // Draw phase commands
@@ -267,15 +781,30 @@ impl SvgRenderer {
             Command::Anchor { .. } => Phase::Draw,
             Command::PinSet { .. } => Phase::Draw,
             Command::Pin { .. } => Phase::Draw,
+            Command::PinData { .. } => Phase::Draw,
             Command::PinText { .. } => Phase::Draw,
             Command::Box { .. } => Phase::Draw,
             Command::Message { .. } => Phase::Draw,
             Command::Text { .. } => Phase::Draw,
             Command::EndMessage => Phase::Draw,
+            Command::PageBreak => Phase::Draw,
+            Command::Note { .. } => Phase::Draw,
+            Command::SheetInfo { .. } => Phase::Draw,
+            Command::ScaleBar { .. } => Phase::Draw,
+            Command::GroupTable { .. } => Phase::Draw,
+            Command::Layer { .. } => Phase::Draw,
+            Command::Zebra { .. } => Phase::Draw,
+            Command::Header { .. } => Phase::Draw,
+            Command::Mirror { .. } => Phase::Draw,
+            Command::Shell { .. } => Phase::Draw,
+            Command::Watermark { .. } => Phase::Draw,
+            Command::Custom { .. } => Phase::Draw,
         }
     }
 
     fn execute_command(&mut self, command: &Command) -> Result<(), RenderError> {
+        self.record_pin_set_command(command);
+
         match command {
             // Setup phase commands
             Command::Labels {
@@ -404,24 +933,53 @@ impl SvgRenderer {
                 thickness,
             ),
 
-            Command::Page { page_name } => self.set_page_size(page_name),
+            Command::Page { page_name, size } => self.set_page_size(page_name, *size),
+            Command::AutoOrient => {
+                self.auto_orient = true;
+                Ok(())
+            }
             Command::Dpi { dpi } => self.set_dpi(*dpi),
             Command::Type {
                 pin_type,
                 color,
                 opacity,
-            } => self.set_pin_type(*pin_type, color, *opacity),
+                arrow,
+            } => self.set_pin_type(*pin_type, color, *opacity, *arrow),
             Command::Wire {
                 wire_type,
                 color,
                 opacity,
                 thickness,
-            } => self.set_wire_type(*wire_type, color, *opacity, *thickness),
+                amplitude,
+                terminal,
+                start_gap,
+                stroke_opacity,
+                dash,
+            } => self.set_wire_type(
+                *wire_type,
+                color,
+                *opacity,
+                *thickness,
+                *amplitude,
+                *terminal,
+                *start_gap,
+                *stroke_opacity,
+                dash.as_deref(),
+            ),
             Command::Group {
                 name,
                 color,
                 opacity,
-            } => self.set_group(name, color, *opacity),
+                shape,
+            } => self.set_group(name, color, *opacity, *shape),
+            Command::ColorScale {
+                min,
+                max,
+                color_low,
+                color_high,
+                label,
+            } => self.set_color_scale(*min, *max, color_low, color_high, label),
+            Command::Match { pattern, theme } => self.add_match_rule(pattern, theme),
             Command::BoxTheme {
                 name,
                 border_color,
@@ -435,6 +993,7 @@ impl SvgRenderer {
                 box_cr_y,
                 box_skew,
                 box_skew_offset,
+                shape,
             } => self.define_box(
                 name,
                 border_color,
@@ -448,6 +1007,7 @@ impl SvgRenderer {
                 *box_cr_y,
                 *box_skew,
                 *box_skew_offset,
+                *shape,
             ),
             Command::TextFont {
                 theme_name,
@@ -471,10 +1031,7 @@ impl SvgRenderer {
 
             // Draw phase commands
             Command::Draw => Ok(()), // Already handled in process_commands
-            Command::GoogleFont { _link } => {
-                // todo!("handle font implementation")
-                Ok(())
-            }
+            Command::GoogleFont { link } => self.embed_google_font(link),
             Command::Image {
                 name,
                 x,
@@ -486,7 +1043,12 @@ impl SvgRenderer {
                 cw,
                 ch,
                 rot,
-            } => self.write_image(name, *x, *y, *w, *h, *cx, *cy, *cw, *ch, *rot),
+                pivot_x,
+                pivot_y,
+                anchor,
+            } => self.write_image(
+                name, *x, *y, *w, *h, *cx, *cy, *cw, *ch, *rot, *pivot_x, *pivot_y, *anchor,
+            ),
             Command::Icon {
                 name,
                 x,
@@ -506,7 +1068,15 @@ impl SvgRenderer {
                 group_width,
                 leader_offset,
                 column_gap,
+                column_gap_percent,
                 leader_h_step,
+                angle,
+                units,
+                advance,
+                first_number,
+                box_gap,
+                frame,
+                title,
             } => self.start_pin_set(
                 *side,
                 *packed,
@@ -517,14 +1087,29 @@ impl SvgRenderer {
                 *group_width,
                 *leader_offset,
                 *column_gap,
+                *column_gap_percent,
                 *leader_h_step,
+                *angle,
+                *units,
+                *advance,
+                *first_number,
+                *box_gap,
+                frame.as_deref(),
+                title.as_deref(),
             ),
             Command::Pin {
                 wire,
                 pin_type,
                 group,
                 attributes,
-            } => self.write_pin(*wire, *pin_type, group, attributes),
+                leader,
+            } => self.write_pin(*wire, *pin_type, group, attributes, *leader),
+            Command::PinData {
+                path,
+                delimiter,
+                decimal_separator,
+                max_rows,
+            } => self.write_pin_data(path, *delimiter, *decimal_separator, *max_rows),
             Command::PinText {
                 wire,
                 pin_type,
@@ -532,7 +1117,10 @@ impl SvgRenderer {
                 msg_theme,
                 label,
                 message,
-            } => self.write_pin_text(*wire, *pin_type, pin_group, msg_theme, label, message),
+                color,
+            } => self.write_pin_text(
+                *wire, *pin_type, pin_group, msg_theme, label, message, color,
+            ),
             Command::Box {
                 theme,
                 x,
@@ -542,6 +1130,7 @@ impl SvgRenderer {
                 x_justify,
                 y_justify,
                 message,
+                text_anchor,
             } => self.draw_box(
                 theme,
                 *x,
@@ -551,6 +1140,7 @@ impl SvgRenderer {
                 *x_justify,
                 *y_justify,
                 message,
+                *text_anchor,
             ),
             Command::Message {
                 x,
@@ -560,8 +1150,19 @@ impl SvgRenderer {
                 font_size,
                 x_justify,
                 y_justify,
-            } => self
-                .start_text_message(*x, *y, *line_step, font, *font_size, *x_justify, *y_justify),
+                tab_stops,
+                max_height,
+            } => self.start_text_message(
+                *x,
+                *y,
+                *line_step,
+                font,
+                *font_size,
+                *x_justify,
+                *y_justify,
+                tab_stops,
+                *max_height,
+            ),
             Command::Text {
                 edge_color,
                 color,
@@ -569,6 +1170,25 @@ impl SvgRenderer {
                 new_line,
             } => self.write_text(edge_color, color, message, *new_line),
             Command::EndMessage => self.end_message(),
+            Command::PageBreak => Ok(()), // Sheets are split up front by render_sheets
+            Command::Note { text } => self.write_note(text),
+            Command::SheetInfo { x, y } => self.write_sheet_info(*x, *y),
+            Command::ScaleBar { x, y, length_mm } => self.write_scale_bar(*x, *y, *length_mm),
+            Command::GroupTable { x, y } => self.write_group_table(*x, *y),
+            Command::Layer { name } => {
+                self.current_layer = name.clone();
+                Ok(())
+            }
+            Command::Zebra { color } => self.set_zebra(color),
+            Command::Header { theme } => self.write_header(theme),
+            Command::Shell { theme, padding } => self.set_shell(theme, *padding),
+            Command::Mirror { offset } => self.mirror_pin_set(*offset),
+            Command::Watermark {
+                text,
+                opacity,
+                angle,
+            } => self.write_watermark(text, *opacity, angle.unwrap_or(0.0)),
+            Command::Custom { name, fields } => self.write_custom(name, fields),
         }
     }
 
@@ -655,6 +1275,19 @@ impl SvgRenderer {
         Ok(())
     }
 
+    /// Get the entries of a named theme being defined, warning if it was defined before
+    fn define_theme(&mut self, theme_entry: String) -> &mut HashMap<String, ThemeValue> {
+        if self.themes.contains_key(&theme_entry) {
+            self.warnings.push(RenderWarning::DuplicateTheme {
+                theme: theme_entry.clone(),
+            });
+        }
+
+        // Later definitions replace earlier ones, entries they leave out are not kept
+        self.themes.insert(theme_entry.clone(), HashMap::new());
+        self.themes.get_mut(&theme_entry).unwrap()
+    }
+
     fn set_theme_value(&mut self, theme: &str, entry: &str, value: ThemeValue) {
         if let Some(theme_map) = self.themes.get_mut(theme) {
             theme_map.insert(entry.to_string(), value);
@@ -710,11 +1343,12 @@ impl SvgRenderer {
         pin_type: PinType,
         color: &str,
         opacity: f32,
+        arrow: bool,
     ) -> Result<(), RenderError> {
         let theme_entry = format!("PINTYPE_{}", pin_type);
 
         // Create or get the theme map
-        let theme_map = self.themes.entry(theme_entry).or_insert_with(HashMap::new);
+        let theme_map = self.themes.entry(theme_entry).or_default();
 
         // Set the color and opacity
         theme_map.insert(
@@ -722,21 +1356,27 @@ impl SvgRenderer {
             ThemeValue::String(color.to_string()),
         );
         theme_map.insert("OPACITY".to_string(), ThemeValue::Float(opacity));
+        theme_map.insert("ARROW".to_string(), ThemeValue::Int(arrow as u32));
 
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn set_wire_type(
         &mut self,
         wire_type: WireType,
         color: &str,
         opacity: f32,
         thickness: f32,
+        amplitude: Option<f32>,
+        terminal: Option<Terminal>,
+        start_gap: Option<f32>,
+        stroke_opacity: Option<f32>,
+        dash: Option<&str>,
     ) -> Result<(), RenderError> {
         let theme_entry = format!("PINWIRE_{}", wire_type);
 
-        // Create or get the theme map
-        let theme_map = self.themes.entry(theme_entry).or_insert_with(HashMap::new);
+        let theme_map = self.define_theme(theme_entry);
 
         // Set the color, opacity, and thickness
         theme_map.insert(
@@ -746,14 +1386,83 @@ impl SvgRenderer {
         theme_map.insert("OPACITY".to_string(), ThemeValue::Float(opacity));
         theme_map.insert("THICKNESS".to_string(), ThemeValue::Float(thickness));
 
+        // Waveform amplitude, falls back to half the group width when not set
+        if let Some(amplitude) = amplitude {
+            theme_map.insert("AMPLITUDE".to_string(), ThemeValue::Float(amplitude));
+        }
+
+        if let Some(terminal) = terminal {
+            theme_map.insert(
+                "TERMINAL".to_string(),
+                ThemeValue::String(terminal.to_string()),
+            );
+        }
+
+        if let Some(start_gap) = start_gap {
+            theme_map.insert("START GAP".to_string(), ThemeValue::Float(start_gap));
+        }
+
+        // Only the leader's stroke, on top of the overall opacity
+        if let Some(stroke_opacity) = stroke_opacity {
+            theme_map.insert(
+                "STROKE OPACITY".to_string(),
+                ThemeValue::Float(stroke_opacity),
+            );
+        }
+
+        if let Some(dash) = dash {
+            theme_map.insert("DASH".to_string(), ThemeValue::String(dash.to_string()));
+        }
+
+        Ok(())
+    }
+
+    fn set_color_scale(
+        &mut self,
+        min: f32,
+        max: f32,
+        color_low: &str,
+        color_high: &str,
+        label: &Option<String>,
+    ) -> Result<(), RenderError> {
+        let parse = |color: &str| {
+            parse_hex_color(color).ok_or_else(|| {
+                RenderError::SvgError(format!(
+                    "COLORSCALE colors must be #rrggbb or #rgb, got {}",
+                    color
+                ))
+            })
+        };
+
+        let scale = ColorScale {
+            min,
+            max,
+            low: parse(color_low)?,
+            high: parse(color_high)?,
+        };
+        self.color_scales.insert(label.clone(), scale);
+
         Ok(())
     }
 
-    fn set_group(&mut self, name: &str, color: &str, opacity: f32) -> Result<(), RenderError> {
+    fn add_match_rule(&mut self, pattern: &str, theme: &str) -> Result<(), RenderError> {
+        let regex = Regex::new(pattern).map_err(|e| {
+            RenderError::SvgError(format!("Invalid MATCH pattern {}: {}", pattern, e))
+        })?;
+        self.match_rules.push((regex, theme.to_string()));
+        Ok(())
+    }
+
+    fn set_group(
+        &mut self,
+        name: &str,
+        color: &str,
+        opacity: f32,
+        shape: Option<GroupShape>,
+    ) -> Result<(), RenderError> {
         let theme_entry = format!("GROUP_{}", name);
 
-        // Create or get the theme map
-        let theme_map = self.themes.entry(theme_entry).or_insert_with(HashMap::new);
+        let theme_map = self.define_theme(theme_entry);
 
         // Set the color and opacity
         theme_map.insert(
@@ -761,10 +1470,20 @@ impl SvgRenderer {
             ThemeValue::String(color.to_string()),
         );
         theme_map.insert("OPACITY".to_string(), ThemeValue::Float(opacity));
+        theme_map.insert(
+            "SHAPE".to_string(),
+            ThemeValue::String(shape.unwrap_or(GroupShape::Circle).to_string()),
+        );
+
+        // Groups are listed in the order they were first defined
+        if !self.group_pins.iter().any(|(group, _)| group == name) {
+            self.group_pins.push((name.to_string(), Vec::new()));
+        }
 
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn define_box(
         &mut self,
         name: &str,
@@ -779,11 +1498,11 @@ impl SvgRenderer {
         box_cr_y: f32,
         box_skew: f32,
         box_skew_offset: f32,
+        shape: Option<BoxShape>,
     ) -> Result<(), RenderError> {
         let theme_entry = format!("BOX_{}", name);
 
-        // Create or get the theme map
-        let theme_map = self.themes.entry(theme_entry).or_insert_with(HashMap::new);
+        let theme_map = self.define_theme(theme_entry);
 
         // Set all box theme parameters
         theme_map.insert(
@@ -809,10 +1528,14 @@ impl SvgRenderer {
             "SKEW OFFSET".to_string(),
             ThemeValue::Float(box_skew_offset),
         );
+        if let Some(shape) = shape {
+            theme_map.insert("SHAPE".to_string(), ThemeValue::String(shape.to_string()));
+        }
 
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn define_text_font(
         &mut self,
         theme_name: &str,
@@ -826,8 +1549,7 @@ impl SvgRenderer {
     ) -> Result<(), RenderError> {
         let theme_entry = format!("FONT_{}", theme_name);
 
-        // Create or get the theme map
-        let theme_map = self.themes.entry(theme_entry).or_insert_with(HashMap::new);
+        let theme_map = self.define_theme(theme_entry);
 
         // Set all text font parameters
         theme_map.insert("FONT".to_string(), ThemeValue::String(font.to_string()));
@@ -847,12 +1569,31 @@ impl SvgRenderer {
         Ok(())
     }
 
-    fn set_page_size(&mut self, page_name: &str) -> Result<(), RenderError> {
-        let page_dimensions = match page_name.trim() {
-            "A4-P" => (210.0, 297.0), // mm (portrait)
-            "A4-L" => (297.0, 210.0), // mm (landscape)
-            "A3-P" => (297.0, 420.0), // mm (portrait)
-            "A3-L" => (420.0, 297.0), // mm (landscape)
+    fn set_page_size(
+        &mut self,
+        page_name: &str,
+        size: Option<(f32, f32)>,
+    ) -> Result<(), RenderError> {
+        let page_dimensions = match (page_name.trim(), size) {
+            ("A4-P", _) => (210.0, 297.0),     // mm (portrait)
+            ("A4-L", _) => (297.0, 210.0),     // mm (landscape)
+            ("A3-P", _) => (297.0, 420.0),     // mm (portrait)
+            ("A3-L", _) => (420.0, 297.0),     // mm (landscape)
+            ("A5-P", _) => (148.0, 210.0),     // mm (portrait)
+            ("A5-L", _) => (210.0, 148.0),     // mm (landscape)
+            ("LETTER-P", _) => (215.9, 279.4), // mm (portrait)
+            ("LETTER-L", _) => (279.4, 215.9), // mm (landscape)
+            ("LEGAL-P", _) => (215.9, 355.6),  // mm (portrait)
+            ("LEGAL-L", _) => (355.6, 215.9),  // mm (landscape)
+            ("CUSTOM", Some((width, height))) => {
+                if width <= 0.0 || height <= 0.0 {
+                    return Err(RenderError::SvgError(format!(
+                        "Custom page size must be positive: {}x{}mm",
+                        width, height
+                    )));
+                }
+                (width, height)
+            }
             _ => {
                 return Err(RenderError::SvgError(format!(
                     "Unknown page type: {}",
@@ -861,7 +1602,7 @@ impl SvgRenderer {
             }
         };
 
-        self.page_type = page_name.to_string();
+        self.page_type = page_name.trim().to_string();
         self.page_dimensions = page_dimensions;
 
         // Recalculate resolution in pixels based on DPI
@@ -871,21 +1612,82 @@ impl SvgRenderer {
         );
 
         // Update the document dimensions
-        self.document = self
-            .document
-            .clone()
-            .set(
-                "viewBox",
-                (0, 0, self.page_resolution.0, self.page_resolution.1),
-            )
-            .set("width", format!("{}mm", self.page_dimensions.0))
-            .set("height", format!("{}mm", self.page_dimensions.1));
+        self.update_dimensions();
 
         Ok(())
     }
 
+    /// Switch to the portrait or landscape page of the current family, whichever fits the
+    /// drawn boxes at the larger scale and so wastes the least space
+    fn orient_to_content(&mut self) -> Result<(), RenderError> {
+        let Some(content) = self
+            .box_extents
+            .iter()
+            .copied()
+            .reduce(|all, extent| all.union(&extent))
+        else {
+            return Ok(());
+        };
+        if content.width <= 0.0 || content.height <= 0.0 {
+            return Ok(());
+        }
+
+        let (width, height) = (
+            self.page_dimensions.0.max(self.page_dimensions.1),
+            self.page_dimensions.0.min(self.page_dimensions.1),
+        );
+        let landscape_fit = (width / content.width).min(height / content.height);
+        let portrait_fit = (height / content.width).min(width / content.height);
+
+        // A custom page has no named family, its sides are swapped directly
+        if self.page_type == "CUSTOM" {
+            let size = if landscape_fit >= portrait_fit {
+                (width, height)
+            } else {
+                (height, width)
+            };
+            return self.set_page_size("CUSTOM", Some(size));
+        }
+
+        let family = self
+            .page_type
+            .rsplit_once('-')
+            .map_or(self.page_type.as_str(), |(family, _)| family)
+            .to_string();
+        let orientation = if landscape_fit >= portrait_fit {
+            "L"
+        } else {
+            "P"
+        };
+        self.set_page_size(&format!("{}-{}", family, orientation), None)
+    }
+
+    fn update_dimensions(&mut self) {
+        // The device pixel ratio scales the displayed size only, never the viewBox
+        let ratio = self.device_pixel_ratio;
+
+        let (resolution, dimensions) = self.page_size();
+
+        let (width, height) = if self.pixel_dimensions {
+            (
+                format!("{}px", resolution.0 as f32 * ratio),
+                format!("{}px", resolution.1 as f32 * ratio),
+            )
+        } else {
+            (
+                format!("{}mm", dimensions.0 * ratio),
+                format!("{}mm", dimensions.1 * ratio),
+            )
+        };
+
+        let document = &mut self.document;
+        svg::Node::assign(document, "viewBox", (0, 0, resolution.0, resolution.1));
+        svg::Node::assign(document, "width", width);
+        svg::Node::assign(document, "height", height);
+    }
+
     fn set_dpi(&mut self, dpi: u32) -> Result<(), RenderError> {
-        if dpi < 50 || dpi > 1200 {
+        if !(50..=1200).contains(&dpi) {
             return Err(RenderError::SvgError(
                 "DPI must be between 50 and 1200".to_string(),
             ));
@@ -900,15 +1702,7 @@ impl SvgRenderer {
         );
 
         // Update the document dimensions
-        self.document = self
-            .document
-            .clone()
-            .set(
-                "viewBox",
-                (0, 0, self.page_resolution.0, self.page_resolution.1),
-            )
-            .set("width", format!("{}mm", self.page_dimensions.0))
-            .set("height", format!("{}mm", self.page_dimensions.1));
+        self.update_dimensions();
 
         Ok(())
     }
@@ -929,6 +1723,7 @@ impl SvgRenderer {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn write_image(
         &mut self,
         name: &str,
@@ -941,6 +1736,9 @@ impl SvgRenderer {
         cw: Option<f32>,
         ch: Option<f32>,
         rot: Option<f32>,
+        pivot_x: Option<f32>,
+        pivot_y: Option<f32>,
+        anchor: Option<(JustifyX, JustifyY)>,
     ) -> Result<(), RenderError> {
         let path = Path::new(name);
         if !path.exists() {
@@ -949,16 +1747,17 @@ impl SvgRenderer {
                 name
             )));
         }
+        self.check_embed_size(path)?;
 
         // Load the image
         let mut img = image::open(path)?;
 
         // Apply crop if all crop parameters are provided
-        let img = if cx.is_some() && cy.is_some() && cw.is_some() && ch.is_some() {
-            let cx = cx.unwrap() as u32;
-            let cy = cy.unwrap() as u32;
-            let cw = cw.unwrap() as u32;
-            let ch = ch.unwrap() as u32;
+        let img = if let (Some(cx), Some(cy), Some(cw), Some(ch)) = (cx, cy, cw, ch) {
+            let cx = cx as u32;
+            let cy = cy as u32;
+            let cw = cw as u32;
+            let ch = ch as u32;
 
             // Check if crop coordinates are valid
             if cx + cw > img.width() || cy + ch > img.height() {
@@ -988,13 +1787,24 @@ impl SvgRenderer {
         let img_width = img.width();
         let img_height = img.height();
 
-        // Calculate position (center of image)
+        // Calculate position of the anchor, the center of the image by default
         let x = get_size(x, self.page_resolution.0 as f32, Some(0.0));
         let y = get_size(y, self.page_resolution.1 as f32, Some(0.0));
 
         // Adjust position to top-left corner for SVG image element
-        let x = x - (img_width as f32 / 2.0);
-        let y = y - (img_height as f32 / 2.0);
+        let (anchor_x, anchor_y) = anchor.unwrap_or((JustifyX::Center, JustifyY::Center));
+        let x = x - img_width as f32
+            * match anchor_x {
+                JustifyX::Left => 0.0,
+                JustifyX::Center => 0.5,
+                JustifyX::Right => 1.0,
+            };
+        let y = y - img_height as f32
+            * match anchor_y {
+                JustifyY::Top => 0.0,
+                JustifyY::Center => 0.5,
+                JustifyY::Bottom => 1.0,
+            };
 
         // Convert image to PNG and encode as base64
         let mut buffer: Vec<u8> = Vec::new();
@@ -1003,6 +1813,7 @@ impl SvgRenderer {
         img.write_to(&mut cursor, ImageFormat::Png)?;
         let encoded = general_purpose::STANDARD.encode(&buffer);
         let data_url = format!("data:image/png;base64,{}", encoded);
+        self.embedded_bytes += buffer.len();
 
         // Create the image element
         let mut image = Image::new()
@@ -1014,11 +1825,15 @@ impl SvgRenderer {
 
         // Apply rotation if specified
         if let Some(rot) = rot {
-            // Calculate center of image for rotation
-            let center_x = x + (img_width as f32 / 2.0);
-            let center_y = y + (img_height as f32 / 2.0);
-
-            // Apply rotation transform around the center
+            // Rotate around the pivot, or the center of the image when not given
+            let center_x = pivot_x.map_or(x + (img_width as f32 / 2.0), |px| {
+                get_size(Some(px), self.page_resolution.0 as f32, None)
+            });
+            let center_y = pivot_y.map_or(y + (img_height as f32 / 2.0), |py| {
+                get_size(Some(py), self.page_resolution.1 as f32, None)
+            });
+
+            // Apply rotation transform around the pivot
             image = image.set(
                 "transform",
                 format!("rotate({} {} {})", rot, center_x, center_y),
@@ -1026,8 +1841,26 @@ impl SvgRenderer {
         }
 
         // Add the image to the document
-        self.document = self.document.clone().add(image);
+        svg::Node::append(&mut self.document, image);
+
+        Ok(())
+    }
+
+    /// Refuse files larger than the embed limit, before reading them in
+    fn check_embed_size(&self, path: &Path) -> Result<(), RenderError> {
+        let Some(max) = self.max_embed_size else {
+            return Ok(());
+        };
 
+        let size = std::fs::metadata(path)?.len();
+        if size > max {
+            return Err(RenderError::LimitExceeded(format!(
+                "{} is {} bytes, more than the {} byte embed limit",
+                path.display(),
+                size,
+                max
+            )));
+        }
         Ok(())
     }
 
@@ -1047,9 +1880,10 @@ impl SvgRenderer {
                 name
             )));
         }
+        self.check_embed_size(path)?;
 
         // Check if it's an SVG file
-        if path.extension().map_or(false, |ext| ext != "svg") {
+        if path.extension().is_some_and(|ext| ext != "svg") {
             return Err(RenderError::SvgError(
                 "Icon must be an SVG file".to_string(),
             ));
@@ -1066,6 +1900,7 @@ impl SvgRenderer {
         // Encode the SVG content as base64
         let encoded = general_purpose::STANDARD.encode(svg_content.as_bytes());
         let data_url = format!("data:image/svg+xml;base64,{}", encoded);
+        self.embedded_bytes += svg_content.len();
 
         // Calculate position and dimensions
         let x = get_size(x, self.page_resolution.0 as f32, Some(0.0));
@@ -1099,12 +1934,14 @@ impl SvgRenderer {
         }
 
         // Add the image to the document
-        self.document = self.document.clone().add(image);
+        svg::Node::append(&mut self.document, image);
 
         Ok(())
     }
 
     fn move_anchor(&mut self, x: f32, y: f32) -> Result<(), RenderError> {
+        // An explicit anchor replaces any pending auto-advance
+        self.line_settings.remove("ADVANCE");
         self.anchor_x = x;
         self.anchor_y = y;
         self.offset_x = 0.0;
@@ -1113,6 +1950,7 @@ impl SvgRenderer {
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn start_pin_set(
         &mut self,
         side: Side,
@@ -1124,18 +1962,59 @@ impl SvgRenderer {
         group_width: f32,
         leader_offset: f32,
         column_gap: f32,
+        column_gap_percent: bool,
         leader_h_step: f32,
+        angle: Option<f32>,
+        units: Option<Unit>,
+        advance: Option<f32>,
+        first_number: Option<u32>,
+        box_gap: Option<f32>,
+        frame: Option<&str>,
+        title: Option<&str>,
     ) -> Result<(), RenderError> {
+        // Close off the previous pin set
+        if !self.line_settings.is_empty() {
+            self.finish_pin_set();
+        }
+        self.pin_set_origin = (self.anchor_x, self.anchor_y, self.offset_y);
+
         // Clear existing line settings
         self.line_settings.clear();
+        self.pin_row = 0;
+        self.pin_set_extent = None;
+        self.pin_set_first_child = self.document.get_children().len();
+        self.pin_set_source = self.current_command;
+
+        // Leaders go in a group of their own beneath the pin set, so boxes always cover them
+        self.leader_layer = Some(self.pin_set_first_child);
+        svg::Node::append(&mut self.document, Group::new());
+
+        // Geometry given in millimeters is converted to pixels at the current DPI
+        let scale = match units.unwrap_or(Unit::Px) {
+            Unit::Px => 1.0,
+            Unit::Mm => self.dpi as f32 / 25.4,
+        };
+        let line_step = line_step * scale;
+        let pin_width = pin_width * scale;
+        let group_width = group_width * scale;
+        let leader_offset = leader_offset * scale;
+        // A percentage gap is resolved against each box's width, see inc_offset_x
+        let (column_gap, gap_percent) = if column_gap_percent {
+            (0.0, Some(column_gap))
+        } else {
+            (column_gap * scale, None)
+        };
+        let leader_h_step = leader_h_step * scale;
 
-        // Convert enums to strings for storage
+        // Radial rows are drawn as right side rows, then turned into place by finish_pin_set
         let side_str = match side {
             Side::Left => "LEFT",
-            Side::Right => "RIGHT",
+            Side::Right | Side::Radial => "RIGHT",
             Side::Top => "TOP",
             Side::Bottom => "BOTTOM",
         };
+        self.radial_rows.clear();
+        self.radial = (side == Side::Radial).then(|| self.element_marks());
 
         let justify_x_str = match justify_x {
             JustifyX::Left => "LEFT",
@@ -1168,35 +2047,617 @@ impl SvgRenderer {
         self.line_settings
             .insert("LEADER".into(), leader_offset.into());
         self.line_settings.insert("GAP".into(), column_gap.into());
+        if let Some(percent) = gap_percent {
+            self.line_settings
+                .insert("GAPPERCENT".into(), percent.into());
+        }
         self.line_settings
             .insert("HSTEP".into(), leader_h_step.into());
+        // A radial set's angle turns the whole set, its leaders stay on the radii
+        if side == Side::Radial {
+            self.line_settings
+                .insert("RADIAL".into(), angle.unwrap_or(0.0).into());
+            self.line_settings.insert("ANGLE".into(), 0.0.into());
+        } else {
+            self.line_settings
+                .insert("ANGLE".into(), angle.unwrap_or(0.0).into());
+        }
+        if let Some(advance) = advance {
+            self.line_settings
+                .insert("ADVANCE".into(), (advance * scale).into());
+        }
+        if let Some(first_number) = first_number {
+            self.line_settings
+                .insert("PIN NUMBER".into(), first_number.into());
+        }
+        if let Some(box_gap) = box_gap {
+            self.line_settings
+                .insert("BOX GAP".into(), (box_gap * scale).into());
+        }
+        if let Some(frame) = frame {
+            self.line_settings.insert("FRAME".into(), frame.into());
+        }
+        if let Some(title) = title {
+            self.line_settings.insert("TITLE".into(), title.into());
+        }
         Ok(())
     }
 
-    fn write_pin(
-        &mut self,
-        wire: Option<WireType>,
-        pin_type: Option<PinType>,
-        group: &Option<String>,
-        attributes: &[String],
-    ) -> Result<(), RenderError> {
+    fn embed_google_font(&mut self, link: &str) -> Result<(), RenderError> {
+        // The link is quoted into a style, so it must not be able to end the quotes
+        fonts::check_font_url(link)?;
+
+        // A font linked more than once is only loaded once
+        if self.google_fonts.iter().any(|loaded| loaded == link) {
+            return Ok(());
+        }
+        self.google_fonts.push(link.to_string());
+
+        // Without a fetcher, or when fetching fails, the viewer loads the font itself
+        let import = Style::new(format!("@import url('{}');", link));
+        let Some(fetcher) = &self.font_fetcher else {
+            svg::Node::append(&mut self.definitions, import);
+            return Ok(());
+        };
+
+        match fonts::embed_google_font(link, fetcher.as_ref(), self.font_cache_dir.as_deref()) {
+            Ok(css) => {
+                self.embedded_bytes += data_url_bytes(&css);
+                svg::Node::append(&mut self.definitions, Style::new(css));
+            }
+            Err(err) => {
+                self.warnings.push(RenderWarning::FontFetch {
+                    link: link.to_string(),
+                    reason: err.to_string(),
+                });
+                svg::Node::append(&mut self.definitions, import);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_zebra(&mut self, color: &str) -> Result<(), RenderError> {
         if self.line_settings.is_empty() {
             return Err(RenderError::SvgError(
-                "Line not setup with prior PINSET!".to_string(),
+                "ZEBRA needs a prior PINSET!".to_string(),
             ));
         }
 
-        // Print the pin icon and leader line, and get the box offset
-        let mut box_offset_x = self.print_pin(pin_type, wire, group)?;
+        self.line_settings.insert("ZEBRA".into(), color.into());
+        Ok(())
+    }
 
-        // Get line height from settings
-        let line_height = self
-            .line_settings
-            .get("LINESTEP")
-            .unwrap()
-            .parse::<f32>()
+    fn write_header(&mut self, theme: &Option<String>) -> Result<(), RenderError> {
+        if self.line_settings.is_empty() {
+            return Err(RenderError::SvgError(
+                "HEADER needs a prior PINSET!".to_string(),
+            ));
+        }
+
+        // Header boxes are colored by a box theme, or the theme of their own label
+        let header_theme = match theme {
+            Some(theme) if theme.starts_with("BOX_") => Some(theme.clone()),
+            Some(theme) => Some(format!("BOX_{}", theme)),
+            None => None,
+        };
+        if let Some(theme) = &header_theme {
+            if !self.themes.contains_key(theme) {
+                return Err(RenderError::SvgError(format!(
+                    "Error: HEADER box theme {} is not defined",
+                    theme
+                )));
+            }
+        }
+
+        let line_height = self
+            .line_settings
+            .get("LINESTEP")
+            .unwrap()
+            .parse::<f32>()
+            .unwrap_or(10.0);
+        let side = self
+            .line_settings
+            .get("SIDE")
+            .cloned()
+            .unwrap_or(Value::from("LEFT"));
+        let justify_x = self
+            .line_settings
+            .get("JUSTIFY X")
+            .unwrap_or(&Value::from("CENTER"))
+            .to_string();
+        let justify_y = self
+            .line_settings
+            .get("JUSTIFY Y")
+            .unwrap_or(&Value::from("CENTER"))
+            .to_string();
+
+        // Line the columns up with the pin rows, whose boxes start past the pin and leader
+        let pin_span = ["GROUPWIDTH", "LEADER", "BOX GAP"]
+            .iter()
+            .filter_map(|entry| self.line_settings.get(*entry))
+            .filter_map(|value| value.parse::<f32>().ok())
+            .sum::<f32>();
+        let mut box_offset_x = if side.contains("LEFT") {
+            -pin_span
+        } else {
+            pin_span
+        };
+
+        let first_extent = self.box_extents.len();
+        for label in self.pin_func_types.clone() {
+            let (x, y) = self.get_pin_box_xy(box_offset_x, PIN_BOX_THEME, line_height);
+            self.text_box(
+                x,
+                y,
+                None,
+                None,
+                PIN_BOX_THEME,
+                header_theme.as_deref().unwrap_or(&label),
+                &label,
+                &justify_x,
+                &justify_y,
+                0.0,
+                None,
+                None,
+                None,
+            )?;
+            box_offset_x = self.inc_offset_x(box_offset_x, &side, PIN_BOX_THEME);
+        }
+
+        self.pin_set_extent = self.box_extents[first_extent..]
+            .iter()
+            .copied()
+            .chain(self.pin_set_extent)
+            .reduce(|a, b| a.union(&b));
+
+        self.offset_y += line_height;
+
+        Ok(())
+    }
+
+    fn write_pin_data(
+        &mut self,
+        path: &str,
+        delimiter: u8,
+        decimal_separator: char,
+        max_rows: Option<usize>,
+    ) -> Result<(), RenderError> {
+        self.check_embed_size(Path::new(path))?;
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(delimiter)
+            .from_path(path)
+            .map_err(|e| {
+                RenderError::SvgError(format!("Failed to read pin data {}: {}", path, e))
+            })?;
+
+        let mut rows = 0;
+        for result in reader.records() {
+            let row = result.map_err(|e| {
+                RenderError::SvgError(format!("Invalid pin data in {}: {}", path, e))
+            })?;
+
+            if row.get(0).is_none_or(|s| s.trim().starts_with('#')) {
+                continue;
+            }
+
+            rows += 1;
+            if let Some(max) = max_rows.filter(|&max| rows > max) {
+                return Err(RenderError::LimitExceeded(format!(
+                    "{} has more than {} pin rows",
+                    path, max
+                )));
+            }
+
+            // Rows hold the same fields as a PIN command
+            let record: StringRecord = std::iter::once("PIN").chain(row.iter()).collect();
+            let pin = parse_pin_command(&record, decimal_separator).map_err(|e| {
+                RenderError::SvgError(format!("Invalid pin data in {}: {}", path, e))
+            })?;
+
+            if let Command::Pin {
+                wire,
+                pin_type,
+                group,
+                attributes,
+                leader,
+            } = pin
+            {
+                self.write_pin(wire, pin_type, &group, &attributes, leader)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Keep the commands that make up the current pin set
+    fn record_pin_set_command(&mut self, command: &Command) {
+        match command {
+            Command::PinSet { .. } => {
+                // The origin is taken in start_pin_set, once the previous set has advanced
+                self.pin_set_commands = vec![command.clone()];
+            }
+            Command::Pin { .. }
+            | Command::PinData { .. }
+            | Command::PinText { .. }
+            | Command::Zebra { .. }
+            | Command::Header { .. }
+            | Command::Shell { .. }
+                if !self.pin_set_commands.is_empty() =>
+            {
+                self.pin_set_commands.push(command.clone());
+            }
+            _ => {}
+        }
+    }
+
+    fn mirror_pin_set(&mut self, offset: f32) -> Result<(), RenderError> {
+        let commands = std::mem::take(&mut self.pin_set_commands);
+        if commands.is_empty() {
+            return Err(RenderError::SvgError(
+                "MIRROR needs a prior PINSET!".to_string(),
+            ));
+        }
+
+        // Close the original first, so its auto-advance can't move the mirrored set
+        self.finish_pin_set();
+        self.line_settings.clear();
+
+        // Reflect the anchor across the axis, and start where the original set did
+        let (anchor_x, anchor_y, offset_y) = self.pin_set_origin;
+        self.anchor_x = anchor_x + offset * 2.0;
+        self.anchor_y = anchor_y;
+        self.offset_x = 0.0;
+        self.offset_y = offset_y;
+
+        for command in &commands {
+            let mut command = command.clone();
+            if let Command::PinSet {
+                side, justify_x, ..
+            } = &mut command
+            {
+                *side = match *side {
+                    Side::Left => Side::Right,
+                    Side::Right => Side::Left,
+                    other => other,
+                };
+                *justify_x = match *justify_x {
+                    JustifyX::Left => JustifyX::Right,
+                    JustifyX::Right => JustifyX::Left,
+                    other => other,
+                };
+            }
+            self.execute_command(&command)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_shell(&mut self, theme: &str, padding: Option<f32>) -> Result<(), RenderError> {
+        let box_theme = if theme.starts_with("BOX_") {
+            theme.to_string()
+        } else {
+            format!("BOX_{}", theme)
+        };
+
+        if !self.themes.contains_key(&box_theme) {
+            return Err(RenderError::SvgError(format!(
+                "Box {} used for SHELL, but not defined!",
+                theme
+            )));
+        }
+
+        // Drawn once the pin set is complete, see finish_pin_set
+        self.shell = Some((box_theme, padding.unwrap_or(10.0), self.current_command));
+        Ok(())
+    }
+
+    /// Draw the pending shell, if any, behind the pin set that just ended
+    fn finish_pin_set(&mut self) {
+        self.place_radial_pins();
+
+        // Drop the leader group of a pin set without leaders
+        if let (Some(index), true) = (self.leader_layer, self.leaders().is_empty()) {
+            self.document.get_children_mut().remove(index);
+        }
+        self.leader_layer = None;
+
+        let Some(extent) = self.pin_set_extent else {
+            return;
+        };
+
+        // The set spans from its anchor, where pins and leaders start, to its farthest box
+        let set_extent = extent.union(&Extent::new(self.anchor_x, self.anchor_y, 0.0, 0.0));
+
+        // Start the next set to the right of this one, back at the top
+        if let Some(gap) = self
+            .line_settings
+            .get("ADVANCE")
+            .and_then(|value| value.parse::<f32>().ok())
+        {
+            self.anchor_x += set_extent.width + gap;
+            self.offset_x = 0.0;
+            self.offset_y = 0.0;
+        }
+
+        let frame = self
+            .line_settings
+            .get("FRAME")
+            .map(|value| value.to_string());
+        let title = self
+            .line_settings
+            .get("TITLE")
+            .map(|value| value.to_string());
+        if frame.is_some() || title.is_some() {
+            self.write_pin_set_frame(set_extent, frame, title);
+        }
+
+        let Some((box_theme, padding, source)) = self.shell.take() else {
+            return;
+        };
+
+        let shell = Rectangle::new()
+            .set("x", extent.x - padding)
+            .set("y", extent.y - padding)
+            .set("width", extent.width + padding * 2.0)
+            .set("height", extent.height + padding * 2.0)
+            .set("rx", self.get_theme(&box_theme, "CORNER RX", 0.0f32))
+            .set("ry", self.get_theme(&box_theme, "CORNER RY", 0.0f32))
+            .set(
+                "stroke",
+                self.get_theme(&box_theme, "BORDER COLOR", "black".to_string()),
+            )
+            .set(
+                "stroke-width",
+                self.get_theme(&box_theme, "BORDER WIDTH", 1.0f32),
+            )
+            .set(
+                "stroke-opacity",
+                self.get_opacity(&box_theme, "BORDER OPACITY", 1.0),
+            )
+            .set(
+                "fill",
+                self.get_theme(&box_theme, "FILL COLOR", "none".to_string()),
+            )
+            .set("fill-opacity", self.get_opacity(&box_theme, "OPACITY", 1.0));
+
+        self.document
+            .get_children_mut()
+            .insert(self.pin_set_first_child, Box::new(shell));
+        self.tag_deferred_element(source, self.pin_set_first_child);
+        self.pin_set_extent = None;
+    }
+
+    /// Centre the next row of a radial pin set on the anchor, where it is drawn pointing right
+    fn start_radial_row(&mut self) {
+        if self.radial.is_none() {
+            return;
+        }
+        let line_step = self
+            .line_settings
+            .get("LINESTEP")
+            .and_then(|value| value.parse::<f32>().ok())
+            .unwrap_or(10.0);
+        self.offset_x = 0.0;
+        self.offset_y = -line_step / 2.0;
+        self.radial_rows.push(self.box_extents.len());
+    }
+
+    /// Mark what a radial row drew, so finish_pin_set can turn it into place
+    fn tag_radial_row(&mut self, marks: &ElementMarks) {
+        if self.radial.is_none() {
+            return;
+        }
+        let row = self.radial_rows.len() - 1;
+        for node in self.new_elements(marks) {
+            if let Some(attributes) = node.get_attributes_mut() {
+                attributes.insert(RADIAL_ROW.to_string(), row.into());
+            }
+        }
+    }
+
+    /// Turn the rows of a radial pin set to evenly spaced angles around the anchor. Pins sit
+    /// on a circle that keeps neighbours a line step apart, and rows on the left half are
+    /// turned around their boxes and text so the labels read upright.
+    fn place_radial_pins(&mut self) {
+        let Some(marks) = self.radial.take() else {
+            return;
+        };
+        let rows = std::mem::take(&mut self.radial_rows);
+        if rows.is_empty() {
+            return;
+        }
+
+        let setting = |entry: &str| {
+            self.line_settings
+                .get(entry)
+                .and_then(|value| value.parse::<f32>().ok())
+                .unwrap_or(0.0)
+        };
+        let start = setting("RADIAL");
+        let group_width = setting("GROUPWIDTH");
+        let radius = if rows.len() > 1 {
+            setting("LINESTEP") / (2.0 * (std::f32::consts::PI / rows.len() as f32).sin())
+        } else {
+            0.0
+        };
+        let (center_x, center_y) = (self.anchor_x, self.anchor_y);
+        let shift = radius - group_width / 2.0;
+        let row_angle = |row: usize| start + 360.0 * row as f32 / rows.len() as f32;
+
+        for node in self.new_elements(&marks) {
+            let name = node.get_name().to_string();
+            let Some(attributes) = node.get_attributes_mut() else {
+                continue;
+            };
+            let Some(row) = attributes
+                .remove(RADIAL_ROW)
+                .and_then(|row| row.parse::<usize>().ok())
+            else {
+                continue;
+            };
+            let angle = row_angle(row);
+
+            let mut own = attributes.get("transform").map(|value| value.to_string());
+            if ((angle + 180.0).rem_euclid(360.0) - 180.0).abs() > 90.0 {
+                let number = |name| {
+                    attributes
+                        .get(name)
+                        .and_then(|value| value.parse::<f32>().ok())
+                        .unwrap_or(0.0)
+                };
+                match name.as_str() {
+                    // Boxes are placed by their centre, a half turn keeps them in place
+                    "g" if own.is_some() => {
+                        own = own.map(|own| format!("{} rotate(180)", own));
+                    }
+                    "text" => {
+                        let flip = format!("rotate(180 {} {})", number("x"), number("y"));
+                        own = Some(own.map_or(flip.clone(), |own| format!("{} {}", flip, own)));
+                        let anchor = match attributes.get("text-anchor").map(|value| &**value) {
+                            Some("start") => Some("end"),
+                            Some("end") => Some("start"),
+                            _ => None,
+                        };
+                        if let Some(anchor) = anchor {
+                            attributes.insert("text-anchor".to_string(), anchor.into());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            let place = format!(
+                "rotate({} {} {}) translate({} 0)",
+                angle, center_x, center_y, shift
+            );
+            let transform = own.map_or(place.clone(), |own| format!("{} {}", place, own));
+            attributes.insert("transform".to_string(), transform.into());
+        }
+
+        // Boxes are checked for overlaps where they ended up
+        let boxes = self.box_extents.split_off(rows[0]);
+        for (index, extent) in boxes.into_iter().enumerate() {
+            let row = rows.partition_point(|first| *first <= rows[0] + index) - 1;
+            let extent = turn_extent(extent, (center_x, center_y), row_angle(row), shift);
+            self.track_box(extent);
+        }
+
+        // The set covers the circle its farthest row reaches around
+        if let Some(extent) = self.pin_set_extent {
+            let reach = extent.x + extent.width - center_x + shift.max(0.0);
+            self.pin_set_extent = Some(Extent::new(
+                center_x - reach,
+                center_y - reach,
+                reach * 2.0,
+                reach * 2.0,
+            ));
+        }
+    }
+
+    /// Rounded frame behind a completed pin set, with its title above the top left corner
+    fn write_pin_set_frame(
+        &mut self,
+        extent: Extent,
+        color: Option<String>,
+        title: Option<String>,
+    ) {
+        let padding = PIN_SET_FRAME_PADDING;
+        let frame = Rectangle::new()
+            .set("class", "pinset-frame")
+            .set("x", extent.x - padding)
+            .set("y", extent.y - padding)
+            .set("width", extent.width + padding * 2.0)
+            .set("height", extent.height + padding * 2.0)
+            .set("rx", padding / 2.0)
+            .set("ry", padding / 2.0)
+            .set("stroke", color.unwrap_or_else(|| "black".to_string()))
+            .set("stroke-width", 1.0)
+            .set("fill", "none");
+        self.document
+            .get_children_mut()
+            .insert(self.pin_set_first_child, Box::new(frame));
+        self.tag_deferred_element(self.pin_set_source, self.pin_set_first_child);
+
+        if let Some(title) = title {
+            let font_size = self.get_theme("DEFAULT", "FONT SIZE", 10.0f32);
+            let text = Text::new("")
+                .set("class", "pinset-title")
+                .set("x", extent.x - padding)
+                .set("y", extent.y - padding - font_size / 2.0)
+                .set("font-size", font_size)
+                .set(
+                    "font-family",
+                    self.get_theme("DEFAULT", "FONT", "sans-serif".to_string()),
+                )
+                .set("font-weight", "bold")
+                .set(
+                    "fill",
+                    self.get_theme("DEFAULT", "FONT COLOR", "black".to_string()),
+                )
+                .add(TextNode::new(title));
+            svg::Node::append(&mut self.document, text);
+            let last = self.document.get_children().len() - 1;
+            self.tag_deferred_element(self.pin_set_source, last);
+        }
+    }
+
+    fn write_pin(
+        &mut self,
+        wire: Option<WireType>,
+        pin_type: Option<PinType>,
+        group: &Option<String>,
+        attributes: &[String],
+        leader: Option<f32>,
+    ) -> Result<(), RenderError> {
+        if self.line_settings.is_empty() {
+            return Err(RenderError::SvgError(
+                "Line not setup with prior PINSET!".to_string(),
+            ));
+        }
+
+        // Print the pin icon and leader line, and get the box offset
+        self.start_radial_row();
+        let marks = self.element_marks();
+        let row_first_child = self.document.get_children().len();
+        let row_first_leader = self.leaders().len();
+        let mut box_offset_x = self.print_pin(pin_type, wire, group, leader)?;
+        self.tally_group_pin(group, attributes.first());
+
+        // Remember where this row's boxes start, for zebra shading
+        self.pin_row += 1;
+        let first_child = self.document.get_children().len();
+        let first_extent = self.box_extents.len();
+
+        // Get line height from settings
+        let line_height = self
+            .line_settings
+            .get("LINESTEP")
+            .unwrap()
+            .parse::<f32>()
             .unwrap_or(10.0);
 
+        // Labels follow the leader angle, flipped so they never read upside down
+        let text_angle = upright_angle(self.leader_angle());
+
+        // The first MATCH rule the pin's name fits themes all of its boxes
+        let matched_theme = attributes.first().and_then(|name| {
+            self.match_rules
+                .iter()
+                .find(|(regex, _)| regex.is_match(name))
+                .map(|(_, theme)| theme.clone())
+        });
+        if let Some(theme) = &matched_theme {
+            if !self.themes.contains_key(theme) {
+                return Err(RenderError::SvgError(format!(
+                    "Error: MATCH theme {} is not defined",
+                    theme
+                )));
+            }
+        }
+
         // Process each attribute (columns after the pin type, wire, and group)
         for (index, attr) in attributes.iter().enumerate() {
             if index < self.pin_func_types.len() {
@@ -1204,7 +2665,7 @@ impl SvgRenderer {
 
                 if !attr.is_empty() {
                     // Calculate position for the text box
-                    let (x, y) = self.get_pin_box_xy(box_offset_x, "BOX_SKEWED", line_height);
+                    let (x, y) = self.get_pin_box_xy(box_offset_x, PIN_BOX_THEME, line_height);
 
                     // Get justification settings before borrowing self mutably
                     let justify_x = self
@@ -1218,17 +2679,29 @@ impl SvgRenderer {
                         .unwrap_or(&Value::from("CENTER"))
                         .to_string();
 
+                    // Numeric values on a color scale pick their own fill
+                    let scaled_fill = self
+                        .color_scales
+                        .get(&Some(pin_func.clone()))
+                        .or_else(|| self.color_scales.get(&None))
+                        .zip(attr.trim().parse::<f32>().ok())
+                        .map(|(scale, value)| scale.color_at(value));
+
                     // Draw the text box
                     self.text_box(
                         x,
                         y,
                         None,
                         None,
-                        "BOX_SKEWED",
-                        &pin_func,
+                        PIN_BOX_THEME,
+                        matched_theme.as_deref().unwrap_or(&pin_func),
                         attr,
                         &justify_x,
                         &justify_y,
+                        text_angle,
+                        None,
+                        Some(PIN_VALUE_DELIMITER),
+                        scaled_fill,
                     )?;
 
                     // Increment the box offset for the next box
@@ -1237,13 +2710,33 @@ impl SvgRenderer {
                         .get("SIDE")
                         .cloned()
                         .unwrap_or(Value::from("LEFT"));
-                    box_offset_x = self.inc_offset_x(box_offset_x, &side, "BOX_SKEWED");
+                    box_offset_x = self.inc_offset_x(box_offset_x, &side, PIN_BOX_THEME);
                 } else if self
                     .line_settings
                     .get("PACK")
                     .unwrap_or(&Value::from("UNPACKED"))
                     .eq_ignore_ascii_case("UNPACKED")
                 {
+                    if self.empty_cell_outlines {
+                        let (x, y) = self.get_pin_box_xy(box_offset_x, &pin_func, line_height);
+                        let size = |entry| {
+                            self.get_box_theme(&pin_func, entry, "0")
+                                .parse::<f32>()
+                                .unwrap_or(0.0)
+                        };
+                        let outline = Rectangle::new()
+                            .set("x", x)
+                            .set("y", y)
+                            .set("width", size("WIDTH"))
+                            .set("height", size("HEIGHT"))
+                            .set("class", "empty-cell")
+                            .set("fill", "none")
+                            .set("stroke", "grey")
+                            .set("stroke-opacity", 0.3)
+                            .set("stroke-width", 1);
+                        self.add_to_layer(&pin_func, outline);
+                    }
+
                     // If not packed, still increment the offset for empty boxes
                     let side = self
                         .line_settings
@@ -1255,12 +2748,46 @@ impl SvgRenderer {
             }
         }
 
-        // Increment vertical offset for the next pin
-        self.offset_y += line_height;
+        // Grow the pin set's extent by everything drawn on this row
+        let row_extent = self.document.get_children()[row_first_child..]
+            .iter()
+            .chain(&self.leaders()[row_first_leader..])
+            .filter_map(|node| node_extent(node.as_ref()))
+            .chain(self.box_extents[first_extent..].iter().copied())
+            .chain(self.pin_set_extent)
+            .reduce(|a, b| a.union(&b));
+        self.pin_set_extent = row_extent;
+
+        self.set_pin_data(&marks, wire, pin_type, group, attributes.first());
+
+        // Shade every other row behind its boxes
+        let zebra = self
+            .line_settings
+            .get("ZEBRA")
+            .map(|color| color.to_string());
+        let row_extent = self.box_extents[first_extent..]
+            .iter()
+            .copied()
+            .reduce(|a, b| a.union(&b));
+        if let (Some(color), Some(extent), 0) = (zebra, row_extent, self.pin_row % 2) {
+            let stripe = Rectangle::new()
+                .set("x", extent.x)
+                .set("y", extent.y)
+                .set("width", extent.width)
+                .set("height", extent.height)
+                .set("fill", color);
+            self.document
+                .get_children_mut()
+                .insert(first_child, Box::new(stripe));
+        }
+        self.tag_radial_row(&marks);
+
+        self.next_pin_row(line_height);
 
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn write_pin_text(
         &mut self,
         wire: Option<WireType>,
@@ -1269,6 +2796,7 @@ impl SvgRenderer {
         msg_theme: &str,
         label: &Option<String>,
         message: &str,
+        color: &Option<String>,
     ) -> Result<(), RenderError> {
         if self.line_settings.is_empty() {
             return Err(RenderError::SvgError(
@@ -1277,7 +2805,10 @@ impl SvgRenderer {
         }
 
         // Print the pin icon and leader line, and get the box offset
-        let mut box_offset_x = self.print_pin(pin_type, wire, pin_group)?;
+        self.start_radial_row();
+        let marks = self.element_marks();
+        let mut box_offset_x = self.print_pin(pin_type, wire, pin_group, None)?;
+        self.tally_group_pin(pin_group, label.as_ref());
 
         // Get line height from settings
         let line_height = self
@@ -1287,6 +2818,9 @@ impl SvgRenderer {
             .parse::<f32>()
             .unwrap_or(10.0);
 
+        // Labels follow the leader angle, flipped so they never read upside down
+        let text_angle = upright_angle(self.leader_angle());
+
         // If a label is provided, draw the first box with the label
         if let Some(label_text) = label {
             if !label_text.is_empty() {
@@ -1294,7 +2828,7 @@ impl SvgRenderer {
                 let pin_func = self.pin_func_types[0].clone(); // First pin function type
 
                 // Calculate position for the text box
-                let (x, y) = self.get_pin_box_xy(box_offset_x, "BOX_SKEWED", line_height);
+                let (x, y) = self.get_pin_box_xy(box_offset_x, PIN_BOX_THEME, line_height);
 
                 // Get justification settings before borrowing self mutably
                 let justify_x = self
@@ -1314,11 +2848,15 @@ impl SvgRenderer {
                     y,
                     None,
                     None,
-                    "BOX_SKEWED",
+                    PIN_BOX_THEME,
                     &pin_func,
                     label_text,
                     &justify_x,
                     &justify_y,
+                    text_angle,
+                    None,
+                    None,
+                    None,
                 )?;
 
                 // Increment the box offset for the text
@@ -1328,7 +2866,7 @@ impl SvgRenderer {
                     .cloned()
                     .unwrap_or(Value::from("LEFT"));
                 if side.contains("RIGHT") {
-                    box_offset_x = self.inc_offset_x(box_offset_x, &side, "BOX_SKEWED");
+                    box_offset_x = self.inc_offset_x(box_offset_x, &side, PIN_BOX_THEME);
                 }
             }
         }
@@ -1337,15 +2875,18 @@ impl SvgRenderer {
         if !message.is_empty() {
             // Get font settings from the theme
             let font_theme = msg_theme;
-            let font = self.get_theme(&font_theme, "FONT", "sans-serif".to_string());
-            let font_size = self.get_theme(&font_theme, "FONT SIZE", 10.0f32);
-            let font_color = self.get_theme(&font_theme, "FONT COLOR", "black".to_string());
-            let font_slant = self.get_theme(&font_theme, "FONT SLANT", "normal".to_string());
-            let font_bold = self.get_theme(&font_theme, "FONT BOLD", "normal".to_string());
-            let font_stretch = self.get_theme(&font_theme, "FONT STRETCH", "normal".to_string());
+            let font = self.get_theme(font_theme, "FONT", "sans-serif".to_string());
+            let font_size = self.get_theme(font_theme, "FONT SIZE", 10.0f32);
+            let font_color = match color {
+                Some(color) => color.clone(),
+                None => self.get_theme(font_theme, "FONT COLOR", "black".to_string()),
+            };
+            let font_slant = self.get_theme(font_theme, "FONT SLANT", "normal".to_string());
+            let font_bold = self.get_theme(font_theme, "FONT BOLD", "normal".to_string());
+            let font_stretch = self.get_theme(font_theme, "FONT STRETCH", "normal".to_string());
 
             // Calculate position for the text
-            let (x, y) = self.get_pin_box_xy(box_offset_x, "BOX_SKEWED", line_height);
+            let (x, y) = self.get_pin_box_xy(box_offset_x, PIN_BOX_THEME, line_height);
             // Adjust X position for the gap
             let side = self
                 .line_settings
@@ -1372,7 +2913,7 @@ impl SvgRenderer {
             };
 
             // Create text element
-            let text_elem = Text::new("") // TODO this can corrup nodes
+            let mut text_elem = Text::new("") // TODO this can corrup nodes
                 .set("x", x)
                 .set("y", y + (line_height / 2.0))
                 .set("font-size", font_size)
@@ -1384,16 +2925,26 @@ impl SvgRenderer {
                 .set("text-anchor", text_anchor)
                 .add(TextNode::new(message));
 
+            if text_angle != 0.0 {
+                text_elem = text_elem.set(
+                    "transform",
+                    format!("rotate({} {} {})", text_angle, x, y + (line_height / 2.0)),
+                );
+            }
+
             // Add text to document
-            self.document = self.document.clone().add(text_elem);
+            svg::Node::append(&mut self.document, text_elem);
         }
 
-        // Increment vertical offset for the next pin
-        self.offset_y += line_height;
+        self.set_pin_data(&marks, wire, pin_type, pin_group, label.as_ref());
+        self.tag_radial_row(&marks);
+
+        self.next_pin_row(line_height);
 
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn draw_box(
         &mut self,
         theme: &str,
@@ -1403,7 +2954,8 @@ impl SvgRenderer {
         box_height: Option<f32>,
         x_justify: Option<JustifyX>,
         y_justify: Option<JustifyY>,
-        text: &Option<String>,
+        message: &Option<String>,
+        text_anchor: Option<JustifyX>,
     ) -> Result<(), RenderError> {
         // Get the box theme name (add BOX_ prefix if not already there)
         let box_theme = if theme.starts_with("BOX_") {
@@ -1412,6 +2964,17 @@ impl SvgRenderer {
             format!("BOX_{}", theme)
         };
 
+        // An undefined theme is drawn, colors included, with the default box
+        let (box_theme, pin_func) =
+            if self.themes.contains_key(&box_theme) || box_theme == DEFAULT_BOX_THEME {
+                (box_theme, theme)
+            } else {
+                self.warnings.push(RenderWarning::MissingBoxTheme {
+                    theme: theme.to_string(),
+                });
+                (DEFAULT_BOX_THEME.to_string(), DEFAULT_BOX_THEME)
+            };
+
         // Convert justify options to strings
         let x_justify_str = match x_justify {
             Some(JustifyX::Left) => "LEFT",
@@ -1420,6 +2983,12 @@ impl SvgRenderer {
             None => "CENTER", // Default
         };
 
+        let text_anchor_str = text_anchor.map(|anchor| match anchor {
+            JustifyX::Left => "LEFT",
+            JustifyX::Right => "RIGHT",
+            JustifyX::Center => "CENTER",
+        });
+
         let y_justify_str = match y_justify {
             Some(JustifyY::Top) => "TOP",
             Some(JustifyY::Bottom) => "BOTTOM",
@@ -1427,23 +2996,49 @@ impl SvgRenderer {
             None => "CENTER", // Default
         };
 
+        // Missing dimensions come from the theme, then the renderer default
+        let theme_width = box_width.or_else(|| self.get_theme_float(&box_theme, "WIDTH"));
+        let theme_height = box_height.or_else(|| self.get_theme_float(&box_theme, "HEIGHT"));
+        let (width, height) = (
+            theme_width.unwrap_or(self.default_box_size.0),
+            theme_height.unwrap_or(self.default_box_size.1),
+        );
+
+        if theme_width.is_none() || theme_height.is_none() {
+            self.warnings.push(RenderWarning::DefaultBoxSize {
+                theme: theme.to_string(),
+                width,
+                height,
+            });
+        }
+        if width <= 0.0 || height <= 0.0 {
+            self.warnings.push(RenderWarning::ZeroSizeBox {
+                theme: theme.to_string(),
+            });
+        }
+
         // Draw the text box
-        let text_content = text.as_deref().unwrap_or("");
+        let text_content = message.as_deref().unwrap_or("");
         self.text_box(
             x,
             y,
-            box_width,
-            box_height,
+            Some(width),
+            Some(height),
             &box_theme,
-            theme,
+            pin_func,
             text_content,
             x_justify_str,
             y_justify_str,
+            0.0,
+            text_anchor_str,
+            None,
+            None,
         )?;
 
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn start_text_message(
         &mut self,
         x: Option<f32>,
@@ -1453,12 +3048,29 @@ impl SvgRenderer {
         font_size: Option<f32>,
         x_justify: Option<JustifyX>,
         y_justify: Option<JustifyY>,
+        tab_stops: &Option<Vec<f32>>,
+        max_height: Option<f32>,
     ) -> Result<(), RenderError> {
         // End any previous message
         self.end_message()?;
 
         // Set message settings
         self.message_settings.insert("NEWLINE".into(), false.into());
+        self.message_tab = 0;
+        // Tab stops belong to this message only
+        self.message_tab_stops = tab_stops.clone().unwrap_or_default();
+
+        // A max height of 0 lifts the limit
+        match max_height {
+            Some(height) if height > 0.0 => {
+                self.message_settings
+                    .insert("MAXHEIGHT".into(), height.into());
+            }
+            Some(_) => {
+                self.message_settings.remove("MAXHEIGHT");
+            }
+            None => {}
+        }
 
         // Set x and y if provided
         if let Some(x_val) = x {
@@ -1487,8 +3099,11 @@ impl SvgRenderer {
                 .insert("FONT".into(), "sans-serif".into()); // Default
         }
 
-        // Set font size if provided
-        if let Some(size) = font_size {
+        // Set font size if provided, else take it from a named font theme
+        let theme_size = font
+            .as_ref()
+            .and_then(|f| self.get_theme_float(&self.get_font_theme(f), "FONT SIZE"));
+        if let Some(size) = font_size.or(theme_size) {
             self.message_settings.insert("FONTSIZE".into(), size.into());
         } else if !self.message_settings.contains_key("FONTSIZE") {
             self.message_settings.insert("FONTSIZE".into(), 12.0.into()); // Default
@@ -1584,7 +3199,8 @@ impl SvgRenderer {
             .unwrap()
             .parse::<f32>()
             .unwrap_or(12.0);
-        let font_family = self.get_theme(&font_theme, "FONT", "sans-serif".to_string());
+        // A font that names no theme is used as the family itself
+        let font_family = self.get_theme(&font_theme, "FONT", font_name.to_string());
         let stroke = self.get_theme(&font_theme, "OUTLINE COLOR", "none".to_string());
         let fill = self.get_theme(&font_theme, "FONT COLOR", "black".to_string());
         let font_style = self.get_theme(&font_theme, "FONT SLANT", "normal".to_string());
@@ -1706,17 +3322,45 @@ impl SvgRenderer {
                     .unwrap_or(0.0);
 
             tspan = tspan.set("x", x).set("y", y);
+            self.message_tab = 0;
         }
 
+        // Tabs, as a tab character or a \t marker, start a run at the next tab stop
+        let message = message.replace("\\t", "\t");
+        let mut runs = message.split('\t');
+
         // Set text properties
         tspan = tspan
             .set("stroke", edge_color)
-            .set("fill", color)
-            .add(TextNode::new(message));
+            .set("fill", color.clone())
+            .add(TextNode::new(runs.next().unwrap_or_default()));
+        let mut tspans = vec![tspan];
+
+        let message_x = self
+            .message_settings
+            .get("X")
+            .unwrap()
+            .parse::<f32>()
+            .unwrap_or(0.0);
+        for run in runs {
+            let mut tab_tspan = TSpan::new("")
+                .set("stroke", edge_color)
+                .set("fill", color.clone());
+
+            // Past the last stop the text just carries on
+            if let Some(stop) = self.message_tab_stops.get(self.message_tab) {
+                tab_tspan = tab_tspan.set("x", message_x + stop);
+                self.message_tab += 1;
+            }
+
+            tspans.push(tab_tspan.add(TextNode::new(run)));
+        }
 
-        // Add tspan to current text element
+        // Add tspans to current text element
         if let Some(ref mut text) = self.current_text {
-            *text = text.clone().add(tspan);
+            for tspan in tspans {
+                svg::Node::append(text, tspan);
+            }
         }
 
         // Set newline flag if needed
@@ -1727,13 +3371,279 @@ impl SvgRenderer {
         Ok(())
     }
 
-    fn end_message(&mut self) -> Result<(), RenderError> {
-        if let Some(text) = self.current_text.take() {
-            self.document = self.document.clone().add(text);
+    fn write_watermark(&mut self, text: &str, opacity: f32, angle: f32) -> Result<(), RenderError> {
+        let font = self.get_theme("DEFAULT", "FONT", "sans-serif".to_string());
+        let (cx, cy) = (
+            self.page_resolution.0 as f32 / 2.0,
+            self.page_resolution.1 as f32 / 2.0,
+        );
+
+        let watermark = Text::new("")
+            .set("x", cx)
+            .set("y", cy)
+            .set("font-size", self.page_resolution.1 as f32 / 5.0)
+            .set("font-family", font)
+            .set("font-weight", "bold")
+            .set("fill", "grey")
+            .set("opacity", normalize_opacity(opacity))
+            .set("text-anchor", "middle")
+            .set("dominant-baseline", "middle")
+            .set("transform", format!("rotate({} {} {})", angle, cx, cy))
+            .add(TextNode::new(text));
+
+        // Beneath everything else on the page
+        self.document
+            .get_children_mut()
+            .insert(0, Box::new(watermark));
+        self.pin_set_first_child += 1;
+        if let Some(index) = &mut self.leader_layer {
+            *index += 1;
         }
+
         Ok(())
     }
 
+    /// Show a "#@" comment at the current anchor, in the default font
+    fn write_note(&mut self, text: &str) -> Result<(), RenderError> {
+        let font = self.get_theme("DEFAULT", "FONT", "sans-serif".to_string());
+        let font_size = self.get_theme("DEFAULT", "FONT SIZE", 10.0f32);
+
+        let note = Text::new("")
+            .set("x", self.anchor_x)
+            .set("y", self.anchor_y)
+            .set("class", "note")
+            .set("font-size", font_size)
+            .set("font-family", font)
+            .set("font-style", "italic")
+            .set("fill", "grey")
+            .add(TextNode::new(text));
+        svg::Node::append(&mut self.document, note);
+
+        Ok(())
+    }
+
+    /// Count a drawn pin against its group, by the pin's name
+    fn tally_group_pin(&mut self, group: &Option<String>, name: Option<&String>) {
+        let (Some(group), Some(name)) = (group, name.filter(|name| !name.is_empty())) else {
+            return;
+        };
+        if let Some((_, pins)) = self.group_pins.iter_mut().find(|(g, _)| g == group) {
+            pins.push(name.clone());
+        }
+    }
+
+    fn write_group_table(&mut self, x: f32, y: f32) -> Result<(), RenderError> {
+        let font = self.get_theme("DEFAULT", "FONT", "sans-serif".to_string());
+        let font_size = self.get_theme("DEFAULT", "FONT SIZE", 10.0f32);
+        let font_color = self.get_theme("DEFAULT", "FONT COLOR", "black".to_string());
+        let row_height = font_size * 1.5;
+
+        let mut table = Group::new().set("class", "group-table");
+        for (row, group) in self.group_summary().into_iter().enumerate() {
+            let row_y = y + row as f32 * row_height;
+
+            let swatch = Rectangle::new()
+                .set("x", x)
+                .set("y", row_y)
+                .set("width", font_size)
+                .set("height", font_size)
+                .set("fill", group.color)
+                .set("stroke", "black")
+                .set("stroke-width", 0.5);
+            let text = Text::new("")
+                .set("x", x + font_size * 1.5)
+                .set("y", row_y + font_size * 0.85)
+                .set("font-size", font_size)
+                .set("font-family", font.clone())
+                .set("fill", font_color.clone())
+                .add(TextNode::new(format!(
+                    "{}: {}",
+                    group.name,
+                    group.pins.join(", ")
+                )));
+
+            table = table.add(swatch).add(text);
+        }
+
+        svg::Node::append(&mut self.document, table);
+
+        Ok(())
+    }
+
+    fn write_sheet_info(&mut self, x: f32, y: f32) -> Result<(), RenderError> {
+        let font = self.get_theme("DEFAULT", "FONT", "sans-serif".to_string());
+        let font_size = self.get_theme("DEFAULT", "FONT SIZE", 10.0f32);
+        let font_color = self.get_theme("DEFAULT", "FONT COLOR", "black".to_string());
+
+        let text_elem = Text::new("")
+            .set("x", x)
+            .set("y", y)
+            .set("font-size", font_size)
+            .set("font-family", font)
+            .set("fill", font_color)
+            .add(TextNode::new(format!(
+                "Sheet {} of {}",
+                self.sheet_number, self.sheet_count
+            )));
+
+        svg::Node::append(&mut self.document, text_elem);
+
+        Ok(())
+    }
+
+    /// Horizontal bar `length_mm` long at the current DPI, from the origin to the right
+    fn write_scale_bar(&mut self, x: f32, y: f32, length_mm: f32) -> Result<(), RenderError> {
+        let font = self.get_theme("DEFAULT", "FONT", "sans-serif".to_string());
+        let font_size = self.get_theme("DEFAULT", "FONT SIZE", 10.0f32);
+        let font_color = self.get_theme("DEFAULT", "FONT COLOR", "black".to_string());
+
+        let length = length_mm * self.dpi as f32 / 25.4;
+        let tick = font_size / 2.0;
+        let line = |x1: f32, y1: f32, x2: f32, y2: f32| {
+            Polyline::new().set("points", format!("{},{} {},{}", x1, y1, x2, y2))
+        };
+
+        let bar = Group::new()
+            .set("class", "scalebar")
+            .set("stroke", font_color.clone())
+            .set("stroke-width", 1.0)
+            .add(line(x, y, x + length, y))
+            .add(line(x, y - tick, x, y + tick))
+            .add(line(x + length, y - tick, x + length, y + tick))
+            .add(
+                Text::new("")
+                    .set("x", x + length / 2.0)
+                    .set("y", y - tick - 2.0)
+                    .set("font-size", font_size)
+                    .set("font-family", font)
+                    .set("fill", font_color)
+                    .set("stroke", "none")
+                    .set("text-anchor", "middle")
+                    .add(TextNode::new(format!("{}mm", length_mm))),
+            );
+
+        svg::Node::append(&mut self.document, bar);
+
+        Ok(())
+    }
+
+    fn write_custom(&mut self, name: &str, fields: &[String]) -> Result<(), RenderError> {
+        let handler = self
+            .handlers
+            .get(name)
+            .ok_or_else(|| RenderError::SvgError(format!("Unknown command: {}", name)))?;
+
+        let record: StringRecord = std::iter::once(name)
+            .chain(fields.iter().map(|s| s.as_str()))
+            .collect();
+
+        for node in handler(&record)? {
+            // Keep ids set by the handler, tag_new_elements only names elements without one
+            let id = node
+                .get_attributes()
+                .and_then(|attributes| attributes.get("id"))
+                .filter(|_| self.track_sources)
+                .map(|id| id.to_string());
+            if let Some(id) = id {
+                self.source_map
+                    .entry(self.current_command)
+                    .or_default()
+                    .push(id);
+            }
+            svg::Node::append(&mut self.document, node);
+        }
+
+        Ok(())
+    }
+
+    fn end_message(&mut self) -> Result<(), RenderError> {
+        if let Some(mut text) = self.current_text.take() {
+            self.fit_message(&mut text);
+            let text = self.text_node(text);
+            svg::Node::append(&mut self.document, text);
+        }
+        Ok(())
+    }
+
+    /// Shrink a message taller than its MAXHEIGHT, scaling font size and line step alike
+    fn fit_message(&self, text: &mut Text) {
+        let Some(max_height) = self
+            .message_settings
+            .get("MAXHEIGHT")
+            .and_then(|height| height.parse::<f32>().ok())
+        else {
+            return;
+        };
+        let line_step = self
+            .message_settings
+            .get("LINESTEP")
+            .and_then(|step| step.parse::<f32>().ok())
+            .unwrap_or(15.0);
+
+        // Every line after the first starts with a tspan positioned on its own y
+        let number = |attributes: &svg::node::Attributes, name: &str| {
+            attributes
+                .get(name)
+                .and_then(|value| value.parse::<f32>().ok())
+        };
+        let lines = 1 + text
+            .get_children()
+            .iter()
+            .filter(|node| node.get_attributes().is_some_and(|a| a.contains_key("y")))
+            .count();
+
+        let height = lines as f32 * line_step;
+        if height <= max_height {
+            return;
+        }
+        let scale = max_height / height;
+
+        let attributes = text.get_attributes_mut();
+        let top = number(attributes, "y").unwrap_or(0.0);
+        if let Some(font_size) = number(attributes, "font-size") {
+            attributes.insert("font-size".to_string(), (font_size * scale).into());
+        }
+
+        for node in text.get_children_mut() {
+            if let Some(attributes) = node.get_attributes_mut() {
+                if let Some(y) = number(attributes, "y") {
+                    attributes.insert("y".to_string(), (top + (y - top) * scale).into());
+                }
+            }
+        }
+    }
+
+    /// Box and message text, as glyph outlines when an outline font is set. With a text halo
+    /// a stroked copy of the text is drawn behind it.
+    fn text_node(&self, text: Text) -> Box<dyn svg::Node> {
+        let Some((color, width)) = &self.text_halo else {
+            return self.glyphs(text);
+        };
+
+        let halo = text
+            .clone()
+            .set("class", "halo")
+            .set("fill", color.clone())
+            .set("stroke", color.clone())
+            .set("stroke-opacity", 1.0)
+            .set("stroke-width", *width)
+            .set("stroke-linejoin", "round");
+
+        let mut group = Group::new();
+        svg::Node::append(&mut group, self.glyphs(halo));
+        svg::Node::append(&mut group, self.glyphs(text));
+        Box::new(group)
+    }
+
+    fn glyphs(&self, text: Text) -> Box<dyn svg::Node> {
+        #[cfg(feature = "outline-text")]
+        if let Some(outliner) = &self.text_outliner {
+            return Box::new(outliner.outline(&text));
+        }
+
+        Box::new(text)
+    }
+
     /// Get theme value of any supported type
     fn get_theme<T>(&self, theme_name: &str, entry: &str, default: T) -> T
     where
@@ -1761,6 +3671,17 @@ impl SvgRenderer {
         default
     }
 
+    /// Get a numeric theme value, or None when neither the theme nor DEFAULT has the entry
+    fn get_theme_float(&self, theme_name: &str, entry: &str) -> Option<f32> {
+        let value = self.get_theme(theme_name, entry, f32::NAN);
+        (!value.is_nan()).then_some(value)
+    }
+
+    /// Get an opacity theme value normalized to the 0.0 - 1.0 range SVG expects
+    fn get_opacity(&self, theme_name: &str, entry: &str, default: f32) -> f32 {
+        normalize_opacity(self.get_theme(theme_name, entry, default))
+    }
+
     fn get_font_theme(&self, font_name: &str) -> String {
         if self.themes.contains_key(font_name) {
             font_name.to_string()
@@ -1769,6 +3690,7 @@ impl SvgRenderer {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn text_box(
         &mut self,
         x: f32,
@@ -1780,22 +3702,58 @@ impl SvgRenderer {
         text_content: &str,
         x_justify_str: &str,
         y_justify_str: &str,
+        text_angle: f32,
+        text_anchor_str: Option<&str>,
+        value_delimiter: Option<char>,
+        fill_override: Option<String>,
     ) -> Result<f32, RenderError> {
         // Get theme values
-        let border_color = self.get_theme(pin_func, "BORDER COLOR", "red".to_string());
+        let border_color = self.get_theme(
+            pin_func,
+            "BORDER COLOR",
+            self.default_box_color("BORDER COLOR"),
+        );
         let border_width = self.get_theme(pin_func, "BORDER WIDTH", 1.0f32);
-        let border_opacity = self.get_theme(pin_func, "BORDER OPACITY", 1.0f32);
-        let fill_color = self.get_theme(pin_func, "FILL COLOR", "blue".to_string());
-        let opacity = self.get_theme(pin_func, "OPACITY", 50.0f32);
+        let border_opacity = self.get_opacity(pin_func, "BORDER OPACITY", 1.0);
+        let fill_color = fill_override.unwrap_or_else(|| {
+            self.get_theme(pin_func, "FILL COLOR", self.default_box_color("FILL COLOR"))
+        });
+        let opacity = self.get_opacity(pin_func, "OPACITY", 1.0);
         let font = self.get_theme(pin_func, "FONT", "sans-serif".to_string());
         let fontsize = self.get_theme(pin_func, "FONT SIZE", 10.0f32);
-        let fontcolor = self.get_theme(pin_func, "FONT COLOR", "yellow".to_string());
+        let fontcolor =
+            self.get_theme(pin_func, "FONT COLOR", self.default_box_color("FONT COLOR"));
         let fontslant = self.get_theme(pin_func, "FONT SLANT", "normal".to_string());
         let fontbold = self.get_theme(pin_func, "FONT BOLD", "normal".to_string());
         let fontstretch = self.get_theme(pin_func, "FONT STRETCH", "normal".to_string());
         let fontoutline = self.get_theme(pin_func, "FONT OUTLINE", fontcolor.clone());
         let fontoutthick = self.get_theme(pin_func, "FONT OUTLINE THICKNESS", 0.0f32);
 
+        // High contrast replaces every theme color, keeping fonts and sizes
+        let (border_color, border_width, border_opacity, fill_color, opacity) =
+            if self.high_contrast {
+                (
+                    "black".to_string(),
+                    border_width.max(1.0),
+                    1.0,
+                    "white".to_string(),
+                    1.0,
+                )
+            } else {
+                (
+                    border_color,
+                    border_width,
+                    border_opacity,
+                    fill_color,
+                    opacity,
+                )
+            };
+        let (fontcolor, fontoutline, fontoutthick) = if self.high_contrast {
+            ("black".to_string(), "black".to_string(), 0.0)
+        } else {
+            (fontcolor, fontoutline, fontoutthick)
+        };
+
         let w = box_width.unwrap_or_else(|| self.get_theme(box_theme, "WIDTH", 0.0f32));
         let h = box_height.unwrap_or_else(|| self.get_theme(box_theme, "HEIGHT", 0.0f32));
         let corner_rx = self.get_theme(box_theme, "CORNER RX", 0.0f32);
@@ -1809,6 +3767,14 @@ impl SvgRenderer {
             _ => ("middle", 0.0), // CENTER
         };
 
+        // An explicit anchor changes how text sits on its position, not where it is
+        let xanchor = match text_anchor_str {
+            Some("LEFT") => "start",
+            Some("RIGHT") => "end",
+            Some(_) => "middle",
+            None => xanchor,
+        };
+
         let yalign = match y_justify_str {
             "TOP" => -(h / 2.0) + fontsize,
             "BOTTOM" => (h / 2.0) - (fontsize / 2.0),
@@ -1818,76 +3784,124 @@ impl SvgRenderer {
         // Create group
         let mut boxgroup = Group::new();
 
-        // Create rectangle
-        let mut rect = Rectangle::new()
-            .set("x", (0.0 - w) / 2.0)
-            .set("y", (0.0 - h) / 2.0)
-            .set("width", w)
-            .set("height", h)
-            .set("rx", corner_rx)
-            .set("ry", corner_ry)
-            .set("stroke", border_color)
-            .set("fill-opacity", opacity) // Convert percentage to decimal
-            .set("fill", fill_color)
-            .set("stroke-width", border_width)
-            .set("stroke-opacity", border_opacity);
+        // Create rectangle, or a path for shapes with cut corners
+        let shape = self.get_theme(box_theme, "SHAPE", "RECTANGLE".to_string());
+        let mut outline: Box<dyn svg::Node> = match shape.as_str() {
+            "RECTANGLE" => Box::new(
+                Rectangle::new()
+                    .set("x", (0.0 - w) / 2.0)
+                    .set("y", (0.0 - h) / 2.0)
+                    .set("width", w)
+                    .set("height", h)
+                    .set("rx", corner_rx)
+                    .set("ry", corner_ry),
+            ),
+            _ => {
+                // Corner radius sets the size of the cut
+                let cut = if corner_rx > 0.0 {
+                    corner_rx
+                } else {
+                    w.min(h) / 4.0
+                };
+                Box::new(SvgPath::new().set("d", cut_box_path(w, h, cut, &shape)))
+            }
+        };
 
-        // Apply skew if needed
-        if skew != 0.0 {
-            rect = rect.set("transform", format!("skewX({})", skew));
-        }
+        if let Some(attributes) = outline.get_attributes_mut() {
+            for (name, value) in [
+                ("stroke", Value::from(border_color)),
+                ("fill-opacity", Value::from(opacity)),
+                ("fill", Value::from(fill_color)),
+                ("stroke-width", Value::from(border_width)),
+                ("stroke-opacity", Value::from(border_opacity)),
+            ] {
+                attributes.insert(name.to_string(), value);
+            }
 
-        boxgroup = boxgroup.add(rect);
+            // Apply skew if needed
+            if skew != 0.0 {
+                attributes.insert(
+                    "transform".to_string(),
+                    Value::from(format!("skewX({})", skew)),
+                );
+            }
+        }
 
-        // Add text if content exists
-        if !text_content.is_empty() {
-            let fontoutopacity = if fontoutthick > 0.0 { 1.0 } else { 0.0 };
+        boxgroup = boxgroup.add(outline);
 
-            // Split content by "\\n" for multi-line support
-            let lines: Vec<&str> = text_content.split("\\n").collect();
+        // Stack multi-value text, one tspan per value
+        let values: Vec<&str> = match value_delimiter {
+            Some(delimiter) if text_content.contains(delimiter) => {
+                text_content.split(delimiter).map(str::trim).collect()
+            }
+            _ => Vec::new(),
+        };
 
-            let (yalign1, yalign2) = if lines.len() == 1 {
-                (yalign, -1.0) // Single line
-            } else {
-                (yalign - (h / 5.0), yalign + (h / 5.0)) // Multi-line
-            };
+        if !values.is_empty() {
+            let fontoutopacity = if fontoutthick > 0.0 { 1.0 } else { 0.0 };
 
-            // Add first line
-            let text1 = Text::new("")
+            let mut text = Text::new("")
                 .set("x", xalign)
-                .set("y", yalign1)
+                .set("y", yalign - (values.len() - 1) as f32 * fontsize / 2.0)
                 .set("font-size", fontsize)
-                .set("font-family", font.clone())
-                .set("fill", fontcolor.clone())
-                .set("font-style", fontslant.clone())
-                .set("font-weight", fontbold.clone())
-                .set("font-stretch", fontstretch.clone())
-                .set("stroke", fontoutline.clone())
+                .set("font-family", font)
+                .set("fill", fontcolor)
+                .set("font-style", fontslant)
+                .set("font-weight", fontbold)
+                .set("font-stretch", fontstretch)
+                .set("stroke", fontoutline)
                 .set("stroke-opacity", fontoutopacity)
                 .set("stroke-width", fontoutthick)
-                .set("text-anchor", xanchor)
-                .add(TextNode::new(lines[0]));
+                .set("text-anchor", xanchor);
+
+            for (index, value) in values.iter().enumerate() {
+                let dy = if index == 0 { 0.0 } else { fontsize };
+                text = text.add(
+                    TSpan::new("")
+                        .set("x", xalign)
+                        .set("dy", dy)
+                        .add(TextNode::new(*value)),
+                );
+            }
+
+            if text_angle != 0.0 {
+                text = text.set("transform", format!("rotate({})", text_angle));
+            }
 
-            boxgroup = boxgroup.add(text1);
+            boxgroup = boxgroup.add(self.text_node(text));
+        } else if !text_content.is_empty() {
+            let fontoutopacity = if fontoutthick > 0.0 { 1.0 } else { 0.0 };
 
-            // Add second line if it exists
-            if yalign2 >= 0.0 && lines.len() > 1 {
-                let text2 = Text::new("")
+            // Split content on the line break token for multi-line support, line breaks held
+            // in quoted CSV fields count as well
+            let lines: Vec<&str> = text_content
+                .split(self.line_break.as_str())
+                .flat_map(|line| line.split('\n'))
+                .collect();
+
+            // Lines are a font size apart, centred as a block on the justified position
+            let first_y = yalign - (lines.len() - 1) as f32 * fontsize / 2.0;
+            for (index, line) in lines.iter().enumerate() {
+                let mut text = Text::new("")
                     .set("x", xalign)
-                    .set("y", yalign2)
+                    .set("y", first_y + index as f32 * fontsize)
                     .set("font-size", fontsize)
-                    .set("font-family", font)
-                    .set("fill", fontcolor)
-                    .set("font-style", fontslant)
-                    .set("font-weight", fontbold)
-                    .set("font-stretch", fontstretch)
-                    .set("stroke", fontoutline)
+                    .set("font-family", font.clone())
+                    .set("fill", fontcolor.clone())
+                    .set("font-style", fontslant.clone())
+                    .set("font-weight", fontbold.clone())
+                    .set("font-stretch", fontstretch.clone())
+                    .set("stroke", fontoutline.clone())
                     .set("stroke-opacity", fontoutopacity)
                     .set("stroke-width", fontoutthick)
                     .set("text-anchor", xanchor)
-                    .add(TextNode::new(lines[1]));
+                    .add(TextNode::new(*line));
+
+                if text_angle != 0.0 {
+                    text = text.set("transform", format!("rotate({})", text_angle));
+                }
 
-                boxgroup = boxgroup.add(text2);
+                boxgroup = boxgroup.add(self.text_node(text));
             }
         }
 
@@ -1898,11 +3912,291 @@ impl SvgRenderer {
         );
 
         // Add to document
-        self.document = self.document.clone().add(boxgroup);
+        self.add_to_layer(pin_func, boxgroup);
+        self.track_box(Extent::new(x, y, w, h));
+        self.debug_label(x, y);
 
         Ok(w) // Return width as in the original signature
     }
 
+    /// Remember where a box was drawn, checking it against earlier boxes if asked to
+    fn track_box(&mut self, extent: Extent) {
+        // Radial rows all start on top of each other, they are checked once turned into place
+        if self.warn_overlaps && self.radial.is_none() {
+            for previous in &self.box_extents {
+                if previous.intersects(&extent) {
+                    self.warnings.push(RenderWarning::BoxOverlap {
+                        first: *previous,
+                        second: extent,
+                    });
+                }
+            }
+        }
+
+        self.box_extents.push(extent);
+    }
+
+    /// Where the document, its Inkscape layers and the leader group end so far
+    fn element_marks(&self) -> ElementMarks {
+        ElementMarks {
+            document: self.document.get_children().len(),
+            layers: self
+                .layers
+                .iter()
+                .map(|(_, layer)| layer.get_children().len())
+                .collect(),
+            leaders: self.leaders().len(),
+        }
+    }
+
+    /// Every element appended since the marks were taken, wherever it was drawn
+    fn new_elements(&mut self, marks: &ElementMarks) -> Vec<&mut Box<dyn svg::Node>> {
+        let leader_layer = self.leader_layer;
+        let mut new_elements: Vec<&mut Box<dyn svg::Node>> = Vec::new();
+        let children = self.document.get_children_mut();
+        let first = marks.document.min(children.len());
+        let (before, after) = children.split_at_mut(first);
+        // A leader group from before the marks only gained leaders past its own mark
+        if let Some(leaders) = leader_layer
+            .and_then(|index| before.get_mut(index))
+            .and_then(|group| group.get_children_mut())
+        {
+            let first = marks.leaders.min(leaders.len());
+            new_elements.extend(leaders[first..].iter_mut());
+        }
+        for (i, node) in after.iter_mut().enumerate() {
+            // The leader group is bookkeeping, only the leaders in it come from commands
+            if Some(first + i) == leader_layer {
+                new_elements.extend(node.get_children_mut().into_iter().flatten());
+            } else {
+                new_elements.push(node);
+            }
+        }
+        for (i, (_, layer)) in self.layers.iter_mut().enumerate() {
+            let layer = layer.get_children_mut();
+            let first = marks.layers.get(i).copied().unwrap_or(0).min(layer.len());
+            new_elements.extend(layer[first..].iter_mut());
+        }
+        new_elements
+    }
+
+    /// Elements no command has been credited with yet. Everything a tracked command draws
+    /// gets an id, so these are the ones drawn since, wherever they were inserted.
+    fn untagged_elements(&mut self) -> Vec<&mut Box<dyn svg::Node>> {
+        let leader_layer = self.leader_layer;
+        let children = self.document.get_children_mut().iter_mut().enumerate();
+        let nodes = children.flat_map(|(i, node)| {
+            if Some(i) == leader_layer {
+                node.get_children_mut().into_iter().flatten().collect()
+            } else {
+                vec![node]
+            }
+        });
+        let layers = self
+            .layers
+            .iter_mut()
+            .flat_map(|(_, layer)| layer.get_children_mut().iter_mut());
+        nodes
+            .chain(layers)
+            .filter(|node| {
+                node.get_attributes()
+                    .is_some_and(|attributes| !attributes.contains_key("id"))
+            })
+            .collect()
+    }
+
+    /// Mark everything a pin row drew with data attributes, so scripts can find pins by
+    /// group, type, wire or name
+    fn set_pin_data(
+        &mut self,
+        marks: &ElementMarks,
+        wire: Option<WireType>,
+        pin_type: Option<PinType>,
+        group: &Option<String>,
+        name: Option<&String>,
+    ) {
+        let data: Vec<(&str, String)> = [
+            ("data-group", group.clone()),
+            ("data-type", pin_type.map(|pin_type| pin_type.to_string())),
+            ("data-wire", wire.map(|wire| wire.to_string())),
+            ("data-pin", name.filter(|name| !name.is_empty()).cloned()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value?)))
+        .collect();
+        if data.is_empty() {
+            return;
+        }
+
+        for node in self.new_elements(marks) {
+            if let Some(attributes) = node.get_attributes_mut() {
+                for (name, value) in &data {
+                    attributes.insert(name.to_string(), value.clone().into());
+                }
+            }
+        }
+    }
+
+    /// Give ids to the elements a command added, and record them against its index
+    fn tag_new_elements(&mut self, index: usize) {
+        let mut ids = self.source_map.remove(&index).unwrap_or_default();
+        for node in self.untagged_elements() {
+            if let Some(attributes) = node.get_attributes_mut() {
+                let id = format!("cmd{}-{}", index, ids.len());
+                attributes.insert("id".to_string(), id.clone().into());
+                ids.push(id);
+            }
+        }
+
+        if !ids.is_empty() {
+            self.source_map.insert(index, ids);
+        }
+    }
+
+    /// Give an id to an element drawn for an earlier command, like the shell of a finished
+    /// pin set, and record it against that command rather than the one being run
+    fn tag_deferred_element(&mut self, source: usize, position: usize) {
+        if !self.track_sources {
+            return;
+        }
+        let node = &mut self.document.get_children_mut()[position];
+        let Some(attributes) = node.get_attributes_mut() else {
+            return;
+        };
+        let ids = self.source_map.entry(source).or_default();
+        let id = format!("cmd{}-{}", source, ids.len());
+        attributes.insert("id".to_string(), id.clone().into());
+        ids.push(id);
+    }
+
+    /// Annotate a computed position with its coordinates, when debug labels are on
+    fn debug_label(&mut self, x: f32, y: f32) {
+        if !self.debug_labels {
+            return;
+        }
+
+        let label = Text::new("")
+            .set("x", x)
+            .set("y", y - 1.0)
+            .set("class", "debug-label")
+            .set("font-size", 6)
+            .set("font-family", "monospace")
+            .set("fill", "magenta")
+            .add(TextNode::new(format!("{},{}", x, y)));
+        svg::Node::append(&mut self.document, label);
+    }
+
+    /// Leader lines and terminals drawn so far in the current pin set
+    fn leaders(&self) -> &[Box<dyn svg::Node>] {
+        self.leader_layer
+            .and_then(|index| self.document.get_children()[index].get_children())
+            .map_or(&[], |leaders| leaders.as_slice())
+    }
+
+    /// Arrowheads for the pin ends of leaders, tip on the end for inputs and pointing back
+    /// along the leader for outputs. They scale with the leader's stroke width.
+    fn add_arrow_markers(&mut self) {
+        if self.arrow_markers {
+            return;
+        }
+        self.arrow_markers = true;
+
+        for (id, points) in [
+            ("pin-arrow-in", "0,0 10,5 0,10"),
+            ("pin-arrow-out", "10,0 0,5 10,10"),
+        ] {
+            let marker = Marker::new()
+                .set("id", id)
+                .set("viewBox", "0 0 10 10")
+                .set("refX", 10)
+                .set("refY", 5)
+                .set("markerWidth", 4)
+                .set("markerHeight", 4)
+                .set("orient", "auto-start-reverse")
+                .add(
+                    Polygon::new()
+                        .set("points", points)
+                        .set("fill", "context-stroke"),
+                );
+            svg::Node::append(&mut self.definitions, marker);
+        }
+    }
+
+    /// With pin symbols on, move the geometry of a pin element placed by its transform into a
+    /// shared symbol, and return a use of that symbol at the same transform
+    fn pin_symbol(&mut self, mut node: Box<dyn svg::Node>) -> Box<dyn svg::Node> {
+        if !self.pin_symbols {
+            return node;
+        }
+        let Some(transform) = node
+            .get_attributes_mut()
+            .and_then(|attributes| attributes.remove("transform"))
+        else {
+            return node;
+        };
+
+        let key = node.to_string();
+        let index = match self.symbol_keys.iter().position(|known| *known == key) {
+            Some(index) => index,
+            None => {
+                let index = self.symbol_keys.len();
+                self.symbol_keys.push(key);
+                // Leaders reach above and left of their origin, so nothing may be clipped
+                let mut symbol = Symbol::new()
+                    .set("id", format!("pin-symbol-{}", index))
+                    .set("overflow", "visible");
+                svg::Node::append(&mut symbol, node);
+                svg::Node::append(&mut self.definitions, symbol);
+                index
+            }
+        };
+
+        Box::new(
+            Use::new()
+                .set("href", format!("#pin-symbol-{}", index))
+                .set("transform", transform),
+        )
+    }
+
+    /// Add a leader line or terminal beneath the boxes of the current pin set
+    fn add_leader<T>(&mut self, node: T)
+    where
+        T: Into<Box<dyn svg::Node>>,
+    {
+        let layer = self
+            .leader_layer
+            .and_then(|index| self.document.get_children_mut()[index].get_children_mut());
+        match layer {
+            Some(leaders) => leaders.push(node.into()),
+            None => svg::Node::append(&mut self.document, node),
+        }
+    }
+
+    /// Add a node to the named Inkscape layer, or straight to the document when layers are off
+    fn add_to_layer<T>(&mut self, layer_name: &str, node: T)
+    where
+        T: Into<Box<dyn svg::Node>>,
+    {
+        if !self.inkscape_layers {
+            svg::Node::append(&mut self.document, node);
+            return;
+        }
+
+        let index = match self.layers.iter().position(|(name, _)| name == layer_name) {
+            Some(index) => index,
+            None => {
+                let layer = Group::new()
+                    .set("inkscape:groupmode", "layer")
+                    .set("inkscape:label", layer_name);
+                self.layers.push((layer_name.to_string(), layer));
+                self.layers.len() - 1
+            }
+        };
+
+        let layer = std::mem::take(&mut self.layers[index].1);
+        self.layers[index].1 = layer.add(node);
+    }
+
     fn get_box_theme(&self, theme: &str, entry: &str, default: &str) -> String {
         let box_theme = if !theme.starts_with("BOX_") {
             // Get the box name from the theme's "BOXES" entry
@@ -1917,16 +4211,22 @@ impl SvgRenderer {
         };
 
         if !self.themes.contains_key(&box_theme) {
-            // eprintln!("ERROR: BOX Theme {} not known!", box_theme);
-            return default.to_string();
+            return self.get_theme(DEFAULT_BOX_THEME, entry, default.to_string());
         }
 
         self.get_theme(&box_theme, entry, default.to_string())
     }
 
-    fn get_pin_box_xy(&self, box_offset_x: f32, theme: &str, line_height: f32) -> (f32, f32) {
-        let mut x = self.anchor_x + self.offset_x + box_offset_x;
+    /// Color of BOX_DEFAULT, or of the built-in default box where that doesn't set it
+    fn default_box_color(&self, entry: &str) -> String {
+        let builtin = DEFAULT_BOX_COLORS
+            .iter()
+            .find(|(name, _)| *name == entry)
+            .map_or("black", |(_, color)| color);
+        self.get_theme(DEFAULT_BOX_THEME, entry, builtin.to_string())
+    }
 
+    fn get_pin_box_xy(&self, box_offset_x: f32, theme: &str, line_height: f32) -> (f32, f32) {
         // On the Left side we need to pre-decrement the X coordinate
         // otherwise we align to the wrong box edge.
         let side = self
@@ -1934,13 +4234,15 @@ impl SvgRenderer {
             .get("SIDE")
             .unwrap_or(&Value::from("LEFT"))
             .to_string();
-        if side.contains("LEFT") {
+        let x = if side.contains("LEFT") {
             let box_width = self
                 .get_box_theme(theme, "WIDTH", "0")
                 .parse::<f32>()
                 .unwrap_or(0.0);
-            x = x - box_width;
-        }
+            self.anchor_x - self.offset_x + box_offset_x - box_width
+        } else {
+            self.anchor_x + self.offset_x + box_offset_x
+        };
 
         let mut y = self.anchor_y + self.offset_y;
         let box_height = self
@@ -1955,15 +4257,34 @@ impl SvgRenderer {
             .to_string();
 
         if justify_y == "CENTER" {
-            y = y + ((line_height - box_height) / 2.0);
+            y += (line_height - box_height) / 2.0;
         } else if justify_y == "BOTTOM" {
-            y = y + (line_height - box_height);
+            y += line_height - box_height;
         }
         // For "TOP", no adjustment needed (pass)
 
         (x, y)
     }
 
+    /// Move down to the next pin row, and out by the set's leader step so its leader clears
+    /// the one above. The step builds up until the next ANCHOR.
+    fn next_pin_row(&mut self, line_height: f32) {
+        self.offset_y += line_height;
+        self.offset_x += self
+            .line_settings
+            .get("HSTEP")
+            .and_then(|step| step.parse::<f32>().ok())
+            .unwrap_or(0.0);
+    }
+
+    /// Angle of the leader lines in the current pin set, in degrees
+    fn leader_angle(&self) -> f32 {
+        self.line_settings
+            .get("ANGLE")
+            .and_then(|angle| angle.parse::<f32>().ok())
+            .unwrap_or(0.0)
+    }
+
     fn inc_offset_x(&self, box_offset_x: f32, side: &str, pin_func: &str) -> f32 {
         let gap = self
             .line_settings
@@ -1977,6 +4298,13 @@ impl SvgRenderer {
             .parse::<f32>()
             .unwrap_or(0.0);
 
+        // Percentage gaps are relative to the box width
+        let gap = self
+            .line_settings
+            .get("GAPPERCENT")
+            .and_then(|percent| percent.parse::<f32>().ok())
+            .map_or(gap, |percent| box_width * percent / 100.0);
+
         let x_span = gap + box_width;
 
         if side.contains("LEFT") {
@@ -1988,12 +4316,62 @@ impl SvgRenderer {
         }
     }
 
+    /// Label the leader running out from the pin edge at (x, y) with the set's next pin number
+    fn print_pin_number(&mut self, side: &str, x: f32, y: f32, leader_offset: f32) {
+        let Some(number) = self
+            .line_settings
+            .get("PIN NUMBER")
+            .and_then(|value| value.parse::<u32>().ok())
+        else {
+            return;
+        };
+        self.line_settings
+            .insert("PIN NUMBER".into(), (number + 1).into());
+
+        // Midway along the leader, following its angle
+        let angle = self.leader_angle();
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let half = if side.contains("LEFT") {
+            -leader_offset / 2.0
+        } else {
+            leader_offset / 2.0
+        };
+        let mid_x = x + half * cos;
+        let mid_y = y + half * sin;
+
+        let font = self.get_theme("DEFAULT", "FONT", "sans-serif".to_string());
+        let font_size = self.get_theme("DEFAULT", "FONT SIZE", 10.0f32) * 0.8;
+        let font_color = self.get_theme("DEFAULT", "FONT COLOR", "black".to_string());
+
+        // Sits just above the line, reading upright
+        let mut label = Text::new("")
+            .set("x", mid_x)
+            .set("y", mid_y - 2.0)
+            .set("class", "pin-number")
+            .set("text-anchor", "middle")
+            .set("font-size", font_size)
+            .set("font-family", font)
+            .set("fill", font_color)
+            .add(TextNode::new(number.to_string()));
+        if angle != 0.0 {
+            label = label.set(
+                "transform",
+                format!("rotate({} {} {})", upright_angle(angle), mid_x, mid_y),
+            );
+        }
+
+        self.add_leader(label);
+    }
+
     fn print_pin(
         &mut self,
         pin_type: Option<PinType>,
         wire: Option<WireType>,
         group: &Option<String>,
+        leader: Option<f32>,
     ) -> Result<f32, RenderError> {
+        self.pin_count += 1;
+
         let pin_width = self
             .line_settings
             .get("PINWIDTH")
@@ -2008,12 +4386,14 @@ impl SvgRenderer {
             .parse::<f32>()
             .unwrap_or(20.0);
 
-        let leader_offset = self
+        // A pin's own leader length wins over the pin set's, which still places the boxes
+        let set_leader_offset = self
             .line_settings
             .get("LEADER")
             .unwrap()
             .parse::<f32>()
             .unwrap_or(20.0);
+        let leader_offset = leader.unwrap_or(set_leader_offset);
 
         let line_step = self
             .line_settings
@@ -2036,25 +4416,66 @@ impl SvgRenderer {
         };
 
         let pin_center_y = self.anchor_y + self.offset_y + (line_step / 2.0);
+        self.debug_label(pin_center_x, pin_center_y);
 
         // Draw group circle if group is specified
         if let Some(group_name) = group {
             let group_theme = format!("GROUP_{}", group_name);
             if self.themes.contains_key(&group_theme) {
-                let fill_color = self.get_theme(&group_theme, "FILL COLOR", "black".to_string());
-                let fill_opacity = self.get_theme(&group_theme, "OPACITY", 1.0f32);
-
-                let circle = Circle::new()
-                    .set("cx", pin_center_x)
-                    .set("cy", pin_center_y)
-                    .set("r", group_width / 2.0)
-                    .set("stroke", "black")
-                    .set("stroke-width", "2")
-                    .set("stroke-opacity", "1")
-                    .set("fill", fill_color)
-                    .set("fill-opacity", fill_opacity);
-
-                self.document = self.document.clone().add(circle);
+                let (fill_color, fill_opacity) = if self.high_contrast {
+                    ("white".to_string(), 1.0)
+                } else {
+                    (
+                        self.get_theme(&group_theme, "FILL COLOR", "black".to_string()),
+                        self.get_opacity(&group_theme, "OPACITY", 1.0),
+                    )
+                };
+                let shape = self.get_theme(&group_theme, "SHAPE", "CIRCLE".to_string());
+                let radius = group_width / 2.0;
+
+                let mut indicator: Box<dyn svg::Node> = match shape.as_str() {
+                    "SQUARE" => Box::new(
+                        Rectangle::new()
+                            .set("x", pin_center_x - radius)
+                            .set("y", pin_center_y - radius)
+                            .set("width", group_width)
+                            .set("height", group_width),
+                    ),
+                    "HEXAGON" | "DIAMOND" => {
+                        let corners = if shape == "HEXAGON" { 6 } else { 4 };
+                        let points: Vec<String> = (0..corners)
+                            .map(|i| {
+                                let a = (i as f32) * std::f32::consts::TAU / (corners as f32);
+                                format!(
+                                    "{},{}",
+                                    pin_center_x + radius * a.cos(),
+                                    pin_center_y + radius * a.sin()
+                                )
+                            })
+                            .collect();
+                        Box::new(Polygon::new().set("points", points.join(" ")))
+                    }
+                    _ => Box::new(
+                        Circle::new()
+                            .set("cx", pin_center_x)
+                            .set("cy", pin_center_y)
+                            .set("r", radius),
+                    ),
+                };
+
+                if let Some(attributes) = indicator.get_attributes_mut() {
+                    for (name, value) in [
+                        ("stroke", Value::from("black")),
+                        ("stroke-width", Value::from("2")),
+                        ("stroke-opacity", Value::from("1")),
+                        ("fill", Value::from(fill_color)),
+                        ("fill-opacity", Value::from(fill_opacity)),
+                    ] {
+                        attributes.insert(name.to_string(), value);
+                    }
+                }
+
+                self.add_to_layer(&group_theme, indicator);
             } else {
                 return Err(RenderError::SvgError(format!(
                     "Error: PinGroup {} is not defined",
@@ -2065,17 +4486,29 @@ impl SvgRenderer {
 
         // Draw pin type indicator
         if let Some(pin_type_val) = pin_type {
+            let type_theme = format!("PINTYPE_{}", pin_type_val);
+            let (type_color, type_opacity) = if self.high_contrast {
+                ("black".to_string(), 1.0)
+            } else if self.themes.contains_key(&type_theme) {
+                (
+                    self.get_theme(&type_theme, "FILL COLOR", "black".to_string()),
+                    self.get_opacity(&type_theme, "OPACITY", 1.0),
+                )
+            } else {
+                ("black".to_string(), 1.0)
+            };
+
             match pin_type_val {
                 PinType::IO => {
                     let circle = Circle::new()
                         .set("cx", pin_center_x)
                         .set("cy", pin_center_y)
                         .set("r", pin_width / 2.0)
-                        .set("stroke", "black")
-                        .set("fill", "black")
-                        .set("opacity", "1");
+                        .set("stroke", type_color.clone())
+                        .set("fill", type_color)
+                        .set("opacity", type_opacity);
 
-                    self.document = self.document.clone().add(circle);
+                    svg::Node::append(&mut self.document, circle);
                 }
                 PinType::Input | PinType::Output => {
                     let triangle_edge_length = (pin_width / 2.0) * 3.0_f32.sqrt();
@@ -2107,42 +4540,62 @@ impl SvgRenderer {
 
                     let polygon = Polygon::new()
                         .set("points", points)
-                        .set("stroke", "black")
-                        .set("fill", "black")
-                        .set("opacity", "1")
+                        .set("stroke", type_color.clone())
+                        .set("fill", type_color)
+                        .set("opacity", type_opacity)
                         .set(
                             "transform",
                             format!("translate({},{})", pin_center_x, pin_center_y),
                         );
 
-                    self.document = self.document.clone().add(polygon);
+                    let marker = self.pin_symbol(Box::new(polygon));
+                    svg::Node::append(&mut self.document, marker);
                 }
             }
         }
 
-        // Draw leader line if leader_offset > 0
-        let return_pin_width = group_width + leader_offset;
+        // Draw leader line if leader_offset > 0, the first box starts past it and the box gap
+        let box_gap = self
+            .line_settings
+            .get("BOX GAP")
+            .and_then(|gap| gap.parse::<f32>().ok())
+            .unwrap_or(0.0);
+        let return_pin_width = group_width + set_leader_offset + box_gap;
 
         if leader_offset > 0.0 {
             if let Some(wire_type) = wire {
                 let wire_theme = format!("PINWIRE_{}", wire_type);
-                let color = self.get_theme(&wire_theme, "FILL COLOR", "black".to_string());
-                let opacity = self.get_theme(&wire_theme, "OPACITY", 1.0f32);
+                let (color, opacity) = if self.high_contrast {
+                    ("black".to_string(), 1.0)
+                } else {
+                    (
+                        self.get_theme(&wire_theme, "FILL COLOR", "black".to_string()),
+                        self.get_opacity(&wire_theme, "OPACITY", 1.0),
+                    )
+                };
                 let thickness = self.get_theme(&wire_theme, "THICKNESS", 1.0f32);
+                let amplitude = self.get_theme(&wire_theme, "AMPLITUDE", group_width / 2.0);
+                let terminal = self.get_theme(&wire_theme, "TERMINAL", "NONE".to_string());
+
+                // The gap moves the start of the leader away from the pin, its far end stays put
+                let start_gap = self
+                    .get_theme(&wire_theme, "START GAP", 0.0f32)
+                    .clamp(0.0, leader_offset);
+                let leader_length = leader_offset - start_gap;
 
                 let points = match wire_type {
                     WireType::Pwm => {
                         // Square wave
-                        let step = leader_offset / 4.0;
+                        let step = leader_length / 4.0;
                         format!(
                             "0,0 {step},0 {step},{} {},{} {},{} {},{} {},{} {},0",
-                            -group_width / 2.0,
+                            -amplitude,
                             step * 2.0,
-                            -group_width / 2.0,
+                            -amplitude,
                             step * 2.0,
-                            group_width / 2.0,
+                            amplitude,
                             step * 3.0,
-                            group_width / 2.0,
+                            amplitude,
                             step * 3.0,
                             0.0,
                             step * 4.0
@@ -2155,7 +4608,7 @@ impl SvgRenderer {
                         } else {
                             720.0
                         };
-                        let step = leader_offset / 4.0;
+                        let step = leader_length / 4.0;
                         let sine_width = step * 2.0;
 
                         let mut points_vec = vec![format!("0,0"), format!("{},0", step)];
@@ -2163,8 +4616,8 @@ impl SvgRenderer {
                         for i in 0..((sine_width * 10.0) as i32) {
                             let i_f = i as f32 / 10.0;
                             let x = i_f + step;
-                            let y = ((max_angle / sine_width) * i_f).to_radians().sin()
-                                * (-group_width / 2.0);
+                            let y =
+                                ((max_angle / sine_width) * i_f).to_radians().sin() * -amplitude;
                             points_vec.push(format!("{},{}", x, y));
                         }
                         points_vec.push(format!("{},0", step * 4.0));
@@ -2172,30 +4625,137 @@ impl SvgRenderer {
                         points_vec.join(" ")
                     }
                     _ => {
-                        // Power and Digital - just a line
-                        format!("0,0 {},0", leader_offset)
+                        // Power, Digital, Gnd and the rails of a Bus - just a line
+                        format!("0,0 {},0", leader_length)
                     }
                 };
 
                 let leader_x = if side.contains("LEFT") {
                     pin_center_x - (group_width / 2.0) - leader_offset
                 } else {
-                    pin_center_x + (group_width / 2.0)
+                    pin_center_x + (group_width / 2.0) + start_gap
                 };
 
-                let polyline = Polyline::new()
-                    .set("points", points)
-                    .set("fill", "none")
-                    .set("stroke", color)
+                // Angled leaders pivot around the end touching the pin
+                let angle = self.leader_angle();
+                let pivot_x = if side.contains("LEFT") {
+                    leader_length
+                } else {
+                    0.0
+                };
+                let mut transform = format!("translate({},{})", leader_x, pin_center_y);
+                if angle != 0.0 {
+                    transform = format!("{} rotate({} {} 0)", transform, angle, pivot_x);
+                }
+
+                let mut polyline = Polyline::new()
+                    .set("points", points)
+                    .set("fill", "none")
+                    .set("stroke", color.clone())
                     .set("opacity", opacity)
-                    .set("stroke-width", thickness)
-                    .set(
-                        "transform",
-                        format!("translate({},{})", leader_x, pin_center_y),
+                    .set("stroke-width", thickness);
+                if !self.high_contrast
+                    && self
+                        .get_theme_float(&wire_theme, "STROKE OPACITY")
+                        .is_some()
+                {
+                    polyline = polyline.set(
+                        "stroke-opacity",
+                        self.get_opacity(&wire_theme, "STROKE OPACITY", 1.0),
+                    );
+                }
+                let dash = self.get_theme(&wire_theme, "DASH", String::new());
+                if !dash.is_empty() {
+                    polyline = polyline.set("stroke-dasharray", dash);
+                }
+
+                // Inputs point into the chip and outputs away from it, at the pin's end
+                let arrow = pin_type.filter(|pin_type| {
+                    *pin_type != PinType::IO
+                        && self.get_theme(&format!("PINTYPE_{}", pin_type), "ARROW", 0u32) != 0
+                });
+                if let Some(pin_type) = arrow {
+                    self.add_arrow_markers();
+                    let marker = if pin_type == PinType::Input {
+                        "url(#pin-arrow-in)"
+                    } else {
+                        "url(#pin-arrow-out)"
+                    };
+                    let pin_end = if side.contains("LEFT") {
+                        "marker-end"
+                    } else {
+                        "marker-start"
+                    };
+                    polyline = polyline.set(pin_end, marker);
+                }
+
+                // A bus is two rails either side of the leader's centre line
+                let leader: Box<dyn svg::Node> = if wire_type == WireType::Bus {
+                    Box::new(
+                        Group::new()
+                            .set("transform", transform)
+                            .add(
+                                polyline
+                                    .clone()
+                                    .set("transform", format!("translate(0,{})", -thickness)),
+                            )
+                            .add(polyline.set("transform", format!("translate(0,{})", thickness))),
+                    )
+                } else {
+                    Box::new(polyline.set("transform", transform))
+                };
+                let leader = self.pin_symbol(leader);
+                self.add_leader(leader);
+
+                // The end of the leader away from the pin
+                let terminal_offset = if side.contains("LEFT") {
+                    -leader_length
+                } else {
+                    leader_length
+                };
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let terminal_x = leader_x + pivot_x + terminal_offset * cos;
+                let terminal_y = pin_center_y + terminal_offset * sin;
+
+                if wire_type == WireType::Gnd {
+                    let ground = ground_symbol(
+                        terminal_x,
+                        terminal_y,
+                        group_width / 2.0,
+                        &color,
+                        thickness,
+                        opacity,
                     );
+                    self.add_leader(ground);
+                }
+
+                // Draw the terminal marker at the end of the leader
+                if terminal != "NONE" {
+                    let terminal_fill = if terminal == "DOT" {
+                        color.clone()
+                    } else {
+                        "white".to_string()
+                    };
 
-                self.document = self.document.clone().add(polyline);
+                    let marker = Circle::new()
+                        .set("cx", terminal_x)
+                        .set("cy", terminal_y)
+                        .set("r", thickness * 1.5)
+                        .set("stroke", color)
+                        .set("stroke-width", thickness / 2.0)
+                        .set("fill", terminal_fill)
+                        .set("opacity", opacity);
+
+                    self.add_leader(marker);
+                }
             }
+
+            let pin_edge_x = if side.contains("LEFT") {
+                pin_center_x - (group_width / 2.0)
+            } else {
+                pin_center_x + (group_width / 2.0)
+            };
+            self.print_pin_number(&side, pin_edge_x, pin_center_y, leader_offset);
         }
 
         if side.contains("LEFT") {
@@ -2205,6 +4765,347 @@ impl SvgRenderer {
         }
     }
 
+    /// Register a handler for a command name the parser does not know
+    pub fn register_handler<F>(&mut self, name: &str, handler: F)
+    where
+        F: Fn(&StringRecord) -> Result<Vec<Box<dyn svg::Node>>, RenderError> + 'static,
+    {
+        self.handlers
+            .insert(name.trim().to_uppercase(), Box::new(handler));
+    }
+
+    /// Renderer with every option taken from `options`
+    pub fn new_with_options(options: &RenderOptions) -> Result<Self, RenderError> {
+        let mut renderer = Self::new();
+        renderer.set_inkscape_layers(options.inkscape_layers);
+        renderer.set_source_tracking(options.source_tracking);
+        renderer.set_pixel_dimensions(options.pixel_dimensions);
+        renderer.set_device_pixel_ratio(options.device_pixel_ratio)?;
+        if let Some(hint) = &options.shape_rendering {
+            renderer.set_shape_rendering(hint)?;
+        }
+        renderer.set_default_box_size(options.default_box_size.0, options.default_box_size.1);
+        renderer.set_debug_labels(options.debug_labels);
+        renderer.set_overlap_warnings(options.overlap_warnings);
+        renderer.set_empty_cell_outlines(options.empty_cell_outlines);
+        renderer.set_high_contrast(options.high_contrast);
+        if let Some((color, width)) = &options.text_halo {
+            renderer.set_text_halo(color, *width);
+        }
+        renderer.set_pin_symbols(options.pin_symbols);
+        renderer.set_line_break(&options.line_break)?;
+        renderer.set_rotated(options.rotated);
+        if let Some(bytes) = options.max_embed_size {
+            renderer.set_max_embed_size(bytes);
+        }
+        if let Some(layers) = &options.enabled_layers {
+            renderer.set_enabled_layers(layers.iter().cloned());
+        }
+        if let Some(dpi) = options.dpi {
+            renderer.set_dpi(dpi)?;
+        }
+        if let Some(page) = &options.page {
+            renderer.set_page_size(page, None)?;
+        }
+        Ok(renderer)
+    }
+
+    /// Renderer with its page size and DPI defaults read from the environment, see
+    /// [`RenderOptions::from_env`]
+    pub fn from_env() -> Result<Self, RenderError> {
+        Self::new_with_options(&RenderOptions::from_env()?)
+    }
+
+    /// Wrap pin groups and pin function boxes in Inkscape layers, one per category
+    pub fn set_inkscape_layers(&mut self, enabled: bool) {
+        self.inkscape_layers = enabled;
+    }
+
+    /// Check that every pin function label gets a box with some width
+    pub fn validate_box_widths(&self) -> Vec<RenderWarning> {
+        self.pin_func_types
+            .iter()
+            .filter(|_| {
+                self.get_box_theme(PIN_BOX_THEME, "WIDTH", "0")
+                    .parse::<f32>()
+                    .unwrap_or(0.0)
+                    <= 0.0
+            })
+            .map(|label| RenderWarning::ZeroWidthBox {
+                label: label.clone(),
+                theme: PIN_BOX_THEME.trim_start_matches("BOX_").to_string(),
+            })
+            .collect()
+    }
+
+    /// Tag drawn elements with ids and map each command index to them, see [`Self::source_map`]
+    pub fn set_source_tracking(&mut self, enabled: bool) {
+        self.track_sources = enabled;
+    }
+
+    /// Ids of the elements each command produced, keyed by its index in the command list
+    pub fn source_map(&self) -> HashMap<usize, Vec<String>> {
+        self.source_map.clone()
+    }
+
+    /// Fetch GOOGLEFONT links with this fetcher and embed the fonts in the document
+    pub fn set_font_fetcher<F>(&mut self, fetcher: F)
+    where
+        F: FontFetcher + 'static,
+    {
+        self.font_fetcher = Some(Box::new(fetcher));
+    }
+
+    /// Keep downloaded fonts in this directory, so later renders don't need the network
+    pub fn set_font_cache_dir<P: Into<PathBuf>>(&mut self, dir: P) {
+        self.font_cache_dir = Some(dir.into());
+    }
+
+    /// Draw box and message text as paths traced from this TrueType or OpenType font, so the
+    /// output no longer depends on fonts installed where it is viewed. The text stops being
+    /// editable, so this is off by default.
+    #[cfg(feature = "outline-text")]
+    pub fn set_text_outline_font(&mut self, font: Vec<u8>) -> Result<(), RenderError> {
+        self.text_outliner = Some(TextOutliner::new(font)?);
+        Ok(())
+    }
+
+    /// Check the rendered SVG for well-formedness, a usable viewBox, the attributes each shape
+    /// needs and valid transforms, e.g. before shipping it
+    #[cfg(feature = "validate-output")]
+    pub fn validate_output(&self) -> Result<(), Vec<OutputError>> {
+        check_svg(&self.to_string())
+    }
+
+    /// Give the page width and height in pixels at the current DPI instead of millimeters
+    pub fn set_pixel_dimensions(&mut self, enabled: bool) {
+        self.pixel_dimensions = enabled;
+        self.update_dimensions();
+    }
+
+    /// Token that splits box text into lines, a backslash followed by n by default
+    pub fn set_line_break(&mut self, token: &str) -> Result<(), RenderError> {
+        if token.is_empty() {
+            return Err(RenderError::SvgError(
+                "Line break token must not be empty".to_string(),
+            ));
+        }
+
+        self.line_break = token.to_string();
+        Ok(())
+    }
+
+    /// Multiply the page width and height by a device pixel ratio, e.g. 2 for retina displays,
+    /// keeping the viewBox in logical pixels
+    pub fn set_device_pixel_ratio(&mut self, ratio: f32) -> Result<(), RenderError> {
+        if ratio <= 0.0 || !ratio.is_finite() {
+            return Err(RenderError::SvgError(format!(
+                "Device pixel ratio must be positive, got {}",
+                ratio
+            )));
+        }
+
+        self.device_pixel_ratio = ratio;
+        self.update_dimensions();
+        Ok(())
+    }
+
+    /// Set the `shape-rendering` hint for every shape on the page, e.g. "crispEdges" for
+    /// sharp thin lines. "auto" leaves it to the viewer.
+    pub fn set_shape_rendering(&mut self, hint: &str) -> Result<(), RenderError> {
+        match hint {
+            "auto" => {
+                self.document.get_attributes_mut().remove("shape-rendering");
+            }
+            "optimizeSpeed" | "crispEdges" | "geometricPrecision" => {
+                self.document
+                    .get_attributes_mut()
+                    .insert("shape-rendering".to_string(), Value::from(hint));
+            }
+            _ => {
+                return Err(RenderError::SvgError(format!(
+                    "Unknown shape-rendering hint: {}",
+                    hint
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Size used for BOX commands when neither the command nor its theme give a width or height
+    pub fn set_default_box_size(&mut self, width: f32, height: f32) {
+        self.default_box_size = (width, height);
+    }
+
+    /// Label every box and pin with its computed coordinates, for debugging layouts
+    pub fn set_debug_labels(&mut self, enabled: bool) {
+        self.debug_labels = enabled;
+    }
+
+    /// Warn when a drawn box overlaps one drawn before it
+    pub fn set_overlap_warnings(&mut self, enabled: bool) {
+        self.warn_overlaps = enabled;
+    }
+
+    /// Outline the empty cells of unpacked pin sets faintly, so the table grid stays visible
+    pub fn set_empty_cell_outlines(&mut self, enabled: bool) {
+        self.empty_cell_outlines = enabled;
+    }
+
+    /// Draw boxes and pins black on white with solid borders, whatever the themes say, for
+    /// grayscale printing
+    pub fn set_high_contrast(&mut self, enabled: bool) {
+        self.high_contrast = enabled;
+    }
+
+    /// Define each distinct leader and pin type marker once as a `<symbol>`, and draw every
+    /// pin with `<use>` references to them. Shrinks large headers of identical pins.
+    pub fn set_pin_symbols(&mut self, enabled: bool) {
+        self.pin_symbols = enabled;
+    }
+
+    /// Draw a light outline of `color` and `width` behind box and message text, so dark text
+    /// stays readable over busy images
+    pub fn set_text_halo(&mut self, color: &str, width: f32) {
+        self.text_halo = Some((color.to_string(), width));
+    }
+
+    /// Largest IMAGE or ICON file, in bytes, that may be embedded, and largest PINDATA file
+    /// that may be read. Unlimited by default.
+    pub fn set_max_embed_size(&mut self, bytes: u64) {
+        self.max_embed_size = Some(bytes);
+    }
+
+    /// Export the page turned a quarter turn clockwise, e.g. a landscape layout as portrait
+    pub fn set_rotated(&mut self, rotated: bool) {
+        self.rotated = rotated;
+        self.update_dimensions();
+    }
+
+    /// Only draw commands on these layers, or on no layer at all. Every layer is drawn by default.
+    pub fn set_enabled_layers<I, S>(&mut self, layers: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.enabled_layers = Some(layers.into_iter().map(Into::into).collect());
+    }
+
+    fn layer_enabled(&self) -> bool {
+        match (&self.current_layer, &self.enabled_layers) {
+            (Some(layer), Some(enabled)) => enabled.contains(layer),
+            _ => true,
+        }
+    }
+
+    /// Every defined pin group with the pins drawn in it so far, in definition order
+    pub fn group_summary(&self) -> Vec<GroupSummary> {
+        self.group_pins
+            .iter()
+            .map(|(name, pins)| GroupSummary {
+                name: name.clone(),
+                color: self.get_theme(
+                    &format!("GROUP_{}", name),
+                    "FILL COLOR",
+                    "black".to_string(),
+                ),
+                pins: pins.clone(),
+            })
+            .collect()
+    }
+
+    /// Decoded size in bytes of every image, icon and font embedded so far, before base64
+    pub fn embedded_bytes(&self) -> usize {
+        self.embedded_bytes
+    }
+
+    /// Number of pins drawn so far, from PIN, PINDATA and PINTEXT rows
+    pub fn pin_count(&self) -> usize {
+        self.pin_count
+    }
+
+    /// Extents of every box drawn so far, in drawing order
+    pub fn box_extents(&self) -> &[Extent] {
+        &self.box_extents
+    }
+
+    /// Bounding rectangle of every box drawn so far as a JSON array, in drawing order.
+    /// Ids are `box-N`, where N is the box's index in `box_extents`.
+    pub fn bounds_json(&self) -> String {
+        let number = |value: f32| {
+            if value.is_finite() {
+                value.to_string()
+            } else {
+                "null".to_string()
+            }
+        };
+
+        let boxes: Vec<String> = self
+            .box_extents
+            .iter()
+            .enumerate()
+            .map(|(index, extent)| {
+                format!(
+                    r#"{{"id":"box-{}","x":{},"y":{},"width":{},"height":{}}}"#,
+                    index,
+                    number(extent.x),
+                    number(extent.y),
+                    number(extent.width),
+                    number(extent.height)
+                )
+            })
+            .collect();
+
+        format!("[{}]", boxes.join(","))
+    }
+
+    /// The document drawn so far, for exporters that walk it
+    pub(crate) fn document(&self) -> &Document {
+        &self.document
+    }
+
+    /// Page size in pixels and in millimeters as drawn, a rotated export swaps its sides
+    pub(crate) fn page_size(&self) -> ((u32, u32), (f32, f32)) {
+        if self.rotated {
+            (
+                (self.page_resolution.1, self.page_resolution.0),
+                (self.page_dimensions.1, self.page_dimensions.0),
+            )
+        } else {
+            (self.page_resolution, self.page_dimensions)
+        }
+    }
+
+    /// Warnings collected while rendering
+    pub fn warnings(&self) -> &[RenderWarning] {
+        &self.warnings
+    }
+
+    /// True when rendering collected any diagnostics at all
+    pub fn has_issues(&self) -> bool {
+        !self.warnings.is_empty()
+    }
+
+    /// Counts of the collected diagnostics, by kind
+    pub fn issue_summary(&self) -> IssueSummary {
+        let mut summary = IssueSummary::default();
+        for warning in &self.warnings {
+            match warning.issue_kind() {
+                IssueKind::Error => summary.errors += 1,
+                IssueKind::Warning => summary.warnings += 1,
+                IssueKind::Overflow => summary.overflows += 1,
+            }
+        }
+        summary
+    }
+
+    /// Set the position of this renderer in a multi-sheet sequence (1 based)
+    pub fn set_sheet(&mut self, number: usize, count: usize) {
+        self.sheet_number = number;
+        self.sheet_count = count;
+    }
+
     /// Save the SVG document to a file
     pub fn save_to_file(&self, path: &str) -> Result<(), RenderError> {
         use std::fs::File;
@@ -2215,6 +5116,95 @@ impl SvgRenderer {
         Ok(())
     }
 
+    /// Save the SVG gzip compressed, as read natively by browsers from `.svgz` files
+    pub fn save_to_svgz(&self, path: &str) -> Result<(), RenderError> {
+        use flate2::Compression;
+        use flate2::write::GzEncoder;
+        use std::fs::File;
+        use std::io::Write;
+
+        let mut encoder = GzEncoder::new(File::create(path)?, Compression::best());
+        encoder.write_all(self.to_string().as_bytes())?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// Rasterize the page to a PNG of `scale` pixels per page pixel, so a scale of 1 gives the
+    /// page's size at the configured DPI
+    #[cfg(feature = "png")]
+    pub fn save_png(&self, path: &str, scale: f32) -> Result<(), RenderError> {
+        use resvg::{tiny_skia, usvg};
+
+        let raster_error = |message: String| RenderError::RasterError(message);
+
+        // The viewBox is the page in pixels at the DPI, also when the page is rotated
+        let view_box = self
+            .document
+            .get_attributes()
+            .get("viewBox")
+            .map(|value| value.to_string())
+            .unwrap_or_default();
+        let (width, height) = match view_box
+            .split_whitespace()
+            .map(|number| number.parse::<f32>())
+            .collect::<Result<Vec<_>, _>>()
+            .as_deref()
+        {
+            Ok([_, _, width, height]) => (width * scale, height * scale),
+            _ => return Err(raster_error(format!("Invalid viewBox: {}", view_box))),
+        };
+
+        let mut options = usvg::Options::default();
+        options.fontdb_mut().load_system_fonts();
+        let tree = usvg::Tree::from_str(&self.to_string(), &options)
+            .map_err(|e| raster_error(e.to_string()))?;
+
+        let mut pixmap = tiny_skia::Pixmap::new(width.round() as u32, height.round() as u32)
+            .ok_or_else(|| raster_error(format!("Invalid PNG size {}x{}", width, height)))?;
+        let transform = tiny_skia::Transform::from_scale(
+            width / tree.size().width(),
+            height / tree.size().height(),
+        );
+        resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+        pixmap
+            .save_png(path)
+            .map_err(|e| raster_error(format!("Failed to write {}: {}", path, e)))
+    }
+
+    /// Render the document as a responsive SVG for embedding in web components
+    ///
+    /// The absolute `width`/`height` are dropped in favour of `width="100%"`, while
+    /// the viewBox is kept so the diagram scales with its container.
+    pub fn to_component_svg(&self) -> String {
+        let mut document = self.document.clone();
+        let attributes = document.get_attributes_mut();
+        attributes.remove("height");
+        attributes.insert("width".to_string(), Value::from("100%"));
+        document.to_string()
+    }
+
+    /// Export the rectangle at (x, y) of size w x h, in page pixels, as its own SVG. The
+    /// definitions are kept along with every top level element that intersects it.
+    pub fn export_region(&self, x: f32, y: f32, w: f32, h: f32) -> String {
+        let region = Extent::new(x, y, w, h);
+
+        let mut document = Document::new()
+            .set("viewBox", (x, y, w, h))
+            .set("width", w)
+            .set("height", h);
+
+        for node in self.document.get_children() {
+            let keep = node.get_name() == "defs"
+                || node_extent(node.as_ref()).is_some_and(|extent| extent.intersects(&region));
+            if keep {
+                document = document.add(node.clone());
+            }
+        }
+
+        document.to_string()
+    }
+
     /// Print the content of all themes for debugging
     pub fn print_themes(&self) {
         println!("=== THEMES CONTENT ===");
@@ -2324,6 +5314,197 @@ impl SvgRenderer {
     // Helper methods
 }
 
+impl fmt::Display for SvgRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+/// Approximate page area covered by a node and its children, moved by a leading translate
+/// but ignoring rotation and skew. None for elements without a known geometry.
+fn node_extent(node: &dyn svg::Node) -> Option<Extent> {
+    let attributes = node.get_attributes()?;
+    let number = |name: &str| {
+        attributes
+            .get(name)
+            .and_then(|value| value.parse::<f32>().ok())
+    };
+
+    let extent = match node.get_name() {
+        "rect" | "image" => Extent::new(
+            number("x").unwrap_or(0.0),
+            number("y").unwrap_or(0.0),
+            number("width")?,
+            number("height")?,
+        ),
+        "circle" => {
+            let r = number("r")?;
+            Extent::new(number("cx")? - r, number("cy")? - r, r * 2.0, r * 2.0)
+        }
+        "text" => Extent::new(number("x")?, number("y")?, 0.0, 0.0),
+        "polygon" | "polyline" => {
+            let coords: Vec<f32> = attributes
+                .get("points")?
+                .split([' ', ','])
+                .filter_map(|v| v.parse().ok())
+                .collect();
+            coords
+                .chunks_exact(2)
+                .map(|p| Extent::new(p[0], p[1], 0.0, 0.0))
+                .reduce(|a, b| a.union(&b))?
+        }
+        "g" => node
+            .get_children()?
+            .iter()
+            .filter_map(|child| node_extent(child.as_ref()))
+            .reduce(|a, b| a.union(&b))?,
+        _ => return None,
+    };
+
+    // Only the leading translate of a transform moves the area
+    let (dx, dy) = attributes
+        .get("transform")
+        .and_then(|transform| {
+            let args = transform.strip_prefix("translate(")?;
+            let args = &args[..args.find(')')?];
+            let mut values = args.split([' ', ',']).filter_map(|v| v.parse::<f32>().ok());
+            Some((values.next()?, values.next().unwrap_or(0.0)))
+        })
+        .unwrap_or((0.0, 0.0));
+
+    Some(Extent::new(
+        extent.x + dx,
+        extent.y + dy,
+        extent.width,
+        extent.height,
+    ))
+}
+
+/// Bounds of an extent moved right by `shift`, then turned by `angle` degrees around `center`
+fn turn_extent(extent: Extent, center: (f32, f32), angle: f32, shift: f32) -> Extent {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let corners = [
+        (extent.x, extent.y),
+        (extent.x + extent.width, extent.y),
+        (extent.x, extent.y + extent.height),
+        (extent.x + extent.width, extent.y + extent.height),
+    ];
+    corners
+        .iter()
+        .map(|(x, y)| {
+            let (dx, dy) = (x + shift - center.0, y - center.1);
+            let x = center.0 + dx * cos - dy * sin;
+            let y = center.1 + dx * sin + dy * cos;
+            Extent::new(x, y, 0.0, 0.0)
+        })
+        .reduce(|a, b| a.union(&b))
+        .unwrap_or(extent)
+}
+
+/// Map an angle into -90..=90 degrees so rotated text stays upright
+fn upright_angle(angle: f32) -> f32 {
+    let angle = (angle + 180.0).rem_euclid(360.0) - 180.0;
+    if angle > 90.0 {
+        angle - 180.0
+    } else if angle < -90.0 {
+        angle + 180.0
+    } else {
+        angle
+    }
+}
+
+/// A ground symbol hanging below `(x, y)`: a short drop, then three bars narrowing from
+/// `width` down to a third of it
+fn ground_symbol(x: f32, y: f32, width: f32, color: &str, thickness: f32, opacity: f32) -> Group {
+    let step = width / 4.0;
+    let mut ground = Group::new()
+        .set("class", "pin-ground")
+        .set("fill", "none")
+        .set("stroke", color)
+        .set("stroke-width", thickness)
+        .set("opacity", opacity)
+        .add(Polyline::new().set("points", format!("{},{} {},{}", x, y, x, y + step)));
+
+    for bar in 0..3 {
+        let half = width * (3 - bar) as f32 / 6.0;
+        let bar_y = y + step * (bar + 1) as f32;
+        ground = ground.add(Polyline::new().set(
+            "points",
+            format!("{},{} {},{}", x - half, bar_y, x + half, bar_y),
+        ));
+    }
+
+    ground
+}
+
+/// Decoded size of the base64 data URLs in a stylesheet
+fn data_url_bytes(css: &str) -> usize {
+    css.split("base64,")
+        .skip(1)
+        .filter_map(|rest| {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || "+/=".contains(c)))
+                .unwrap_or(rest.len());
+            general_purpose::STANDARD.decode(&rest[..end]).ok()
+        })
+        .map(|data| data.len())
+        .sum()
+}
+
+/// Opacities are fractions between 0.0 and 1.0 throughout the themes, the parser turns
+/// `50%` into `0.5`. Anything outside that range is clamped to it.
+fn normalize_opacity(opacity: f32) -> f32 {
+    opacity.clamp(0.0, 1.0)
+}
+
+/// Outline of a w x h box centered on the origin, with corners cut at 45 degrees. The shape is
+/// a box theme SHAPE, "CHAMFER" cuts every corner and "NOTCH <corner>" only the one named.
+fn cut_box_path(w: f32, h: f32, cut: f32, shape: &str) -> String {
+    let (left, top, right, bottom) = (-w / 2.0, -h / 2.0, w / 2.0, h / 2.0);
+    let is_cut = |corner: Corner| {
+        shape == BoxShape::Chamfered.to_string() || shape == BoxShape::Notched(corner).to_string()
+    };
+
+    // Clockwise from the top left corner, each corner either one point or two around its cut
+    let mut points = Vec::new();
+    for (corner, (x, y), (dx, dy)) in [
+        (Corner::TopLeft, (left, top), (1.0, 1.0)),
+        (Corner::TopRight, (right, top), (-1.0, 1.0)),
+        (Corner::BottomRight, (right, bottom), (-1.0, -1.0)),
+        (Corner::BottomLeft, (left, bottom), (1.0, -1.0)),
+    ] {
+        let along_y = (x, y + dy * cut);
+        let along_x = (x + dx * cut, y);
+        match corner {
+            _ if !is_cut(corner) => points.push((x, y)),
+            Corner::TopLeft | Corner::BottomRight => points.extend([along_y, along_x]),
+            Corner::TopRight | Corner::BottomLeft => points.extend([along_x, along_y]),
+        }
+    }
+
+    let path: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+    format!("M{}Z", path.join(" L"))
+}
+
+/// Parse a `#rrggbb` or `#rgb` color into its channels
+fn parse_hex_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.trim().strip_prefix('#')?;
+    let channel = |digits: &str| u8::from_str_radix(digits, 16).ok();
+
+    match hex.len() {
+        6 => Some([
+            channel(&hex[0..2])?,
+            channel(&hex[2..4])?,
+            channel(&hex[4..6])?,
+        ]),
+        3 => {
+            let short = |i: usize| channel(&hex[i..i + 1]).map(|c| c * 17);
+            Some([short(0)?, short(1)?, short(2)?])
+        }
+        _ => None,
+    }
+}
+
 fn get_size(size: Option<f32>, max_size: f32, default: Option<f32>) -> f32 {
     match size {
         None => match default {
@@ -2342,7 +5523,16 @@ fn get_size(size: Option<f32>, max_size: f32, default: Option<f32>) -> f32 {
 
 /// Generate SVG file from commands
 pub fn generate_svg(commands: &[Command], output_path: &str) -> Result<(), RenderError> {
-    let mut renderer = SvgRenderer::new();
+    generate_svg_with_options(commands, output_path, &RenderOptions::default())
+}
+
+/// Generate SVG file from commands, with the renderer set up from `options`
+pub fn generate_svg_with_options(
+    commands: &[Command],
+    output_path: &str,
+    options: &RenderOptions,
+) -> Result<(), RenderError> {
+    let mut renderer = SvgRenderer::new_with_options(options)?;
     renderer.process_commands(commands)?;
 
     // Print themes for debugging (you can comment this out in production)
@@ -2352,6 +5542,53 @@ pub fn generate_svg(commands: &[Command], output_path: &str) -> Result<(), Rende
     Ok(())
 }
 
+/// Render commands split by PAGEBREAK into one renderer per sheet
+///
+/// Every sheet repeats the setup phase, followed by its own share of the draw commands.
+pub fn render_sheets(commands: &[Command]) -> Result<Vec<SvgRenderer>, RenderError> {
+    let draw_start = commands
+        .iter()
+        .position(|cmd| matches!(cmd, Command::Draw))
+        .map_or(commands.len(), |index| index + 1);
+    let (setup, draw) = commands.split_at(draw_start);
+
+    let sheets: Vec<&[Command]> = draw
+        .split(|cmd| matches!(cmd, Command::PageBreak))
+        .collect();
+    let sheet_count = sheets.len();
+
+    let mut renderers = Vec::with_capacity(sheet_count);
+    for (index, sheet) in sheets.into_iter().enumerate() {
+        let mut renderer = SvgRenderer::new();
+        renderer.set_sheet(index + 1, sheet_count);
+
+        let sheet_commands: Vec<Command> = setup.iter().chain(sheet).cloned().collect();
+        renderer.process_commands(&sheet_commands)?;
+
+        renderers.push(renderer);
+    }
+
+    Ok(renderers)
+}
+
+/// Generate SVG file from commands, drawing only the given LAYER names and unlayered commands
+pub fn generate_svg_with_layers(
+    commands: &[Command],
+    output_path: &str,
+    enabled_layers: &[&str],
+) -> Result<(), RenderError> {
+    let options = RenderOptions {
+        enabled_layers: Some(
+            enabled_layers
+                .iter()
+                .map(|layer| layer.to_string())
+                .collect(),
+        ),
+        ..RenderOptions::default()
+    };
+    generate_svg_with_options(commands, output_path, &options)
+}
+
 /// Generate SVG file from commands with optional theme debugging
 pub fn generate_svg_with_debug(
     commands: &[Command],
@@ -2368,3 +5605,3613 @@ pub fn generate_svg_with_debug(
     renderer.save_to_file(output_path)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(commands: &[Command]) -> String {
+        let mut renderer = SvgRenderer::new();
+        renderer.process_commands(commands).unwrap();
+        renderer.to_string()
+    }
+
+    fn pin_set(side: Side, leader_offset: f32) -> Command {
+        Command::PinSet {
+            side,
+            packed: true,
+            justify_x: JustifyX::Center,
+            justify_y: JustifyY::Center,
+            line_step: 25.0,
+            pin_width: 10.0,
+            group_width: 40.0,
+            leader_offset,
+            column_gap: 5.0,
+            column_gap_percent: false,
+            leader_h_step: 0.0,
+            angle: None,
+            units: None,
+            advance: None,
+            first_number: None,
+            box_gap: None,
+            frame: None,
+            title: None,
+        }
+    }
+
+    fn wire(wire_type: WireType) -> Command {
+        Command::Wire {
+            wire_type,
+            color: "black".to_string(),
+            opacity: 1.0,
+            thickness: 5.0,
+            amplitude: None,
+            terminal: None,
+            start_gap: None,
+            stroke_opacity: None,
+            dash: None,
+        }
+    }
+
+    fn pin(wire: Option<WireType>, pin_type: Option<PinType>, group: Option<&str>) -> Command {
+        Command::Pin {
+            wire,
+            pin_type,
+            group: group.map(|g| g.to_string()),
+            attributes: Vec::new(),
+            leader: None,
+        }
+    }
+
+    #[test]
+    fn test_to_component_svg() {
+        let mut renderer = SvgRenderer::new();
+        renderer.process_commands(&[]).unwrap();
+
+        let svg = renderer.to_component_svg();
+
+        assert!(svg.contains(r#"viewBox="0 0 3507 2480""#), "{}", svg);
+        assert!(svg.contains(r#"width="100%""#), "{}", svg);
+        assert!(!svg.contains("mm\""), "Fixed dimensions left in {}", svg);
+        assert!(!svg.contains("height="), "Fixed height left in {}", svg);
+    }
+
+    #[test]
+    fn test_wire_amplitude() {
+        let pwm = |custom| {
+            let mut cmd = wire(WireType::Pwm);
+            if let Command::Wire { amplitude, .. } = &mut cmd {
+                *amplitude = custom;
+            }
+            cmd
+        };
+        let draw = |wire_cmd| {
+            render(&[
+                wire_cmd,
+                Command::Draw,
+                Command::Anchor { x: 100.0, y: 100.0 },
+                pin_set(Side::Left, 40.0),
+                pin(Some(WireType::Pwm), None, None),
+            ])
+        };
+
+        // Default amplitude is half the group width
+        let svg = draw(pwm(None));
+        assert!(svg.contains("10,-20 20,-20 20,20 30,20"), "{}", svg);
+
+        let svg = draw(pwm(Some(7.0)));
+        assert!(svg.contains("10,-7 20,-7 20,7 30,7"), "{}", svg);
+    }
+
+    #[test]
+    fn test_wire_terminal() {
+        let mut digital = wire(WireType::Digital);
+        if let Command::Wire { terminal, .. } = &mut digital {
+            *terminal = Some(Terminal::Dot);
+        }
+
+        let svg = render(&[
+            digital,
+            Command::Draw,
+            Command::Anchor { x: 100.0, y: 100.0 },
+            pin_set(Side::Left, 40.0),
+            pin(Some(WireType::Digital), None, None),
+        ]);
+
+        // Pin center is at 100 - 20, the leader starts at the group edge and runs 40px left
+        assert!(
+            svg.contains(
+                r#"<circle cx="20" cy="112.5" data-wire="DIGITAL" fill="black" opacity="1" r="7.5""#
+            ),
+            "{}",
+            svg
+        );
+    }
+
+    #[test]
+    fn test_leaders_beneath_boxes() {
+        let named_pin = |name: &str| Command::Pin {
+            wire: Some(WireType::Digital),
+            pin_type: None,
+            group: None,
+            attributes: vec![name.to_string()],
+            leader: None,
+        };
+
+        let svg = render(&[
+            Command::Labels {
+                default: "DEFAULT".to_string(),
+                pin_type: None,
+                group: None,
+                labels: vec!["Name".to_string()],
+            },
+            wire(WireType::Digital),
+            Command::Draw,
+            pin_set(Side::Left, 20.0),
+            named_pin("D1"),
+            named_pin("D2"),
+        ]);
+
+        // Both leaders come before the first box, even though D2 is drawn after it
+        let last_leader = svg.rfind("<polyline").unwrap();
+        let first_box = svg.find("<rect").unwrap();
+        assert!(last_leader < first_box, "{}", svg);
+        assert_eq!(svg.matches("<polyline").count(), 2);
+    }
+
+    #[test]
+    fn test_wire_start_gap() {
+        let draw = |start: Option<f32>| {
+            let mut digital = wire(WireType::Digital);
+            if let Command::Wire { start_gap, .. } = &mut digital {
+                *start_gap = start;
+            }
+
+            render(&[
+                digital,
+                Command::Draw,
+                Command::Anchor { x: 100.0, y: 100.0 },
+                pin_set(Side::Right, 40.0),
+                pin(Some(WireType::Digital), None, None),
+            ])
+        };
+
+        // Pin center is at 100 + 20, the leader starts at the group edge
+        let svg = draw(None);
+        assert!(
+            svg.contains(r#"points="0,0 40,0" stroke="black" stroke-width="5" transform="translate(140,112.5)""#),
+            "{}",
+            svg
+        );
+
+        // The gap pushes the start out while the far end stays at 180
+        let svg = draw(Some(10.0));
+        assert!(
+            svg.contains(r#"points="0,0 30,0" stroke="black" stroke-width="5" transform="translate(150,112.5)""#),
+            "{}",
+            svg
+        );
+    }
+
+    #[test]
+    fn test_wire_stroke_opacity() {
+        let mut digital = wire(WireType::Digital);
+        if let Command::Wire { stroke_opacity, .. } = &mut digital {
+            *stroke_opacity = Some(0.5);
+        }
+
+        let svg = render(&[
+            digital,
+            Command::Draw,
+            pin_set(Side::Right, 40.0),
+            pin(Some(WireType::Digital), None, None),
+        ]);
+
+        let polyline = svg
+            .lines()
+            .find(|line| line.starts_with("<polyline"))
+            .unwrap();
+        assert!(polyline.contains(r#"opacity="1""#), "{}", polyline);
+        assert!(polyline.contains(r#"stroke-opacity="0.5""#), "{}", polyline);
+    }
+
+    #[test]
+    fn test_opacity_convention() {
+        let draw = |opacity: f32| {
+            render(&[
+                Command::Opacity {
+                    default: opacity,
+                    pin_type: None,
+                    group: None,
+                    opacities: Vec::new(),
+                },
+                Command::Group {
+                    name: "X".to_string(),
+                    color: "red".to_string(),
+                    opacity,
+                    shape: None,
+                },
+                Command::Draw,
+                Command::Box {
+                    theme: "STD".to_string(),
+                    x: 10.0,
+                    y: 10.0,
+                    box_width: Some(50.0),
+                    box_height: Some(20.0),
+                    x_justify: None,
+                    y_justify: None,
+                    message: None,
+                    text_anchor: None,
+                },
+                Command::Anchor { x: 100.0, y: 100.0 },
+                pin_set(Side::Left, 0.0),
+                pin(None, None, Some("X")),
+            ])
+        };
+
+        // Box fill and group circle share the same alpha, clamped to a fraction
+        for (opacity, expected) in [(0.5, "0.5"), (1.5, "1"), (-0.5, "0")] {
+            let svg = draw(opacity);
+            let attribute = format!(r#"fill-opacity="{}""#, expected);
+            assert_eq!(svg.matches(&attribute).count(), 2, "{}", svg);
+        }
+    }
+
+    #[test]
+    fn test_sheet_info() {
+        let commands = [
+            Command::Draw,
+            Command::SheetInfo { x: 10.0, y: 10.0 },
+            Command::PageBreak,
+            Command::SheetInfo { x: 10.0, y: 10.0 },
+            Command::PageBreak,
+            Command::SheetInfo { x: 10.0, y: 10.0 },
+        ];
+
+        let sheets = render_sheets(&commands).unwrap();
+        assert_eq!(sheets.len(), 3);
+
+        let svg = sheets[1].to_string();
+        assert!(svg.contains("Sheet 2 of 3"), "{}", svg);
+    }
+
+    #[test]
+    fn test_rotated_pin_set_labels() {
+        let draw = |angle| {
+            let mut set = pin_set(Side::Right, 40.0);
+            if let Command::PinSet {
+                angle: set_angle, ..
+            } = &mut set
+            {
+                *set_angle = Some(angle);
+            }
+            render(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                Command::Draw,
+                Command::Anchor { x: 100.0, y: 100.0 },
+                set,
+                Command::Pin {
+                    wire: Some(WireType::Digital),
+                    pin_type: None,
+                    group: None,
+                    attributes: vec!["GPIO1".to_string()],
+                    leader: None,
+                },
+            ])
+        };
+
+        let svg = draw(30.0);
+        assert!(
+            svg.contains("rotate(30 0 0)"),
+            "Leader not rotated: {}",
+            svg
+        );
+        assert!(svg.contains(r#"transform="rotate(30)""#), "{}", svg);
+
+        // Leaders pointing backwards flip the label to stay readable
+        let svg = draw(150.0);
+        assert!(
+            svg.contains("rotate(150 0 0)"),
+            "Leader not rotated: {}",
+            svg
+        );
+        assert!(svg.contains(r#"transform="rotate(-30)""#), "{}", svg);
+    }
+
+    #[test]
+    fn test_custom_command_handler() {
+        let mut renderer = SvgRenderer::new();
+        renderer.register_handler("MYCMD", |record| {
+            let marker = Circle::new()
+                .set("id", record.get(1).unwrap_or("").to_string())
+                .set("r", 3);
+            Ok(vec![Box::new(marker) as Box<dyn svg::Node>])
+        });
+        renderer.set_source_tracking(true);
+
+        renderer
+            .process_commands(&[
+                Command::Draw,
+                Command::Custom {
+                    name: "MYCMD".to_string(),
+                    fields: vec!["tap".to_string()],
+                },
+            ])
+            .unwrap();
+
+        let svg = renderer.to_string();
+        assert!(svg.contains(r#"<circle id="tap" r="3"/>"#), "{}", svg);
+        // The handler's own id is kept and recorded for its command
+        assert_eq!(renderer.source_map()[&1], ["tap"]);
+    }
+
+    #[test]
+    fn test_inkscape_layers() {
+        let commands = [
+            Command::Labels {
+                default: "DEFAULT".to_string(),
+                pin_type: None,
+                group: None,
+                labels: vec!["GPIO".to_string()],
+            },
+            Command::Group {
+                name: "PWR".to_string(),
+                color: "red".to_string(),
+                opacity: 1.0,
+                shape: None,
+            },
+            Command::Draw,
+            pin_set(Side::Left, 20.0),
+            Command::Pin {
+                wire: None,
+                pin_type: None,
+                group: Some("PWR".to_string()),
+                attributes: vec!["D1".to_string()],
+                leader: None,
+            },
+        ];
+
+        let mut renderer = SvgRenderer::new();
+        renderer.set_inkscape_layers(true);
+        renderer.process_commands(&commands).unwrap();
+        let svg = renderer.to_string();
+
+        assert!(svg.contains(r#"xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape""#));
+        assert!(
+            svg.contains(r#"<g inkscape:groupmode="layer" inkscape:label="GPIO">"#),
+            "{}",
+            svg
+        );
+        assert!(svg.contains(r#"<g inkscape:groupmode="layer" inkscape:label="GROUP_PWR">"#));
+
+        // Layers are only emitted when asked for
+        assert!(!render(&commands).contains("inkscape:groupmode"));
+    }
+
+    #[test]
+    fn test_default_box_size() {
+        let draw_box = |box_width| Command::Box {
+            theme: "DEFAULT".to_string(),
+            x: 10.0,
+            y: 10.0,
+            box_width,
+            box_height: None,
+            x_justify: None,
+            y_justify: None,
+            message: None,
+            text_anchor: None,
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer.set_default_box_size(60.0, 30.0);
+        renderer
+            .process_commands(&[Command::Draw, draw_box(None)])
+            .unwrap();
+
+        assert_eq!(
+            renderer.box_extents(),
+            &[Extent::new(10.0, 10.0, 60.0, 30.0)]
+        );
+        assert_eq!(
+            renderer.warnings(),
+            &[RenderWarning::DefaultBoxSize {
+                theme: "DEFAULT".to_string(),
+                width: 60.0,
+                height: 30.0,
+            }]
+        );
+        assert!(renderer.to_string().contains(r#"height="30""#));
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[Command::Draw, draw_box(Some(0.0))])
+            .unwrap();
+        assert!(renderer.warnings().contains(&RenderWarning::ZeroSizeBox {
+            theme: "DEFAULT".to_string()
+        }));
+    }
+
+    #[test]
+    fn test_box_shapes() {
+        let draw = |shape| {
+            render(&[
+                Command::BoxTheme {
+                    name: "BODY".to_string(),
+                    border_color: "black".to_string(),
+                    border_opacity: 1.0,
+                    fill_color: "white".to_string(),
+                    fill_opacity: 1.0,
+                    line_width: 1.0,
+                    box_width: 100.0,
+                    box_height: 40.0,
+                    box_cr_x: 10.0,
+                    box_cr_y: 0.0,
+                    box_skew: 0.0,
+                    box_skew_offset: 0.0,
+                    shape,
+                },
+                Command::Draw,
+                Command::Box {
+                    theme: "BODY".to_string(),
+                    x: 0.0,
+                    y: 0.0,
+                    box_width: None,
+                    box_height: None,
+                    x_justify: None,
+                    y_justify: None,
+                    message: None,
+                    text_anchor: None,
+                },
+            ])
+        };
+
+        let svg = draw(None);
+        assert!(svg.contains("<rect"), "{}", svg);
+        assert!(!svg.contains("<path"), "{}", svg);
+
+        // Pin 1 notch cut off the top left corner
+        let svg = draw(Some(BoxShape::Notched(Corner::TopLeft)));
+        assert!(!svg.contains("<rect"), "{}", svg);
+        assert!(
+            svg.contains(r#"d="M-50,-10 L-40,-20 L50,-20 L50,20 L-50,20Z""#),
+            "{}",
+            svg
+        );
+
+        let svg = draw(Some(BoxShape::Chamfered));
+        assert!(
+            svg.contains(r#"d="M-50,-10 L-40,-20 L40,-20 L50,-10 L50,10 L40,20 L-40,20 L-50,10Z""#),
+            "{}",
+            svg
+        );
+    }
+
+    #[test]
+    fn test_debug_labels() {
+        let draw_box = |x, y| Command::Box {
+            theme: "PLAIN".to_string(),
+            x,
+            y,
+            box_width: Some(40.0),
+            box_height: Some(20.0),
+            x_justify: None,
+            y_justify: None,
+            message: None,
+            text_anchor: None,
+        };
+        let commands = [Command::Draw, draw_box(10.0, 10.0), draw_box(60.0, 35.5)];
+
+        let mut renderer = SvgRenderer::new();
+        renderer.set_debug_labels(true);
+        renderer.process_commands(&commands).unwrap();
+        let svg = renderer.to_string();
+
+        assert_eq!(svg.matches(r#"class="debug-label""#).count(), 2, "{}", svg);
+        assert!(svg.contains("\n10,10\n</text>"), "{}", svg);
+        assert!(svg.contains("\n60,35.5\n</text>"), "{}", svg);
+
+        // Off by default
+        assert!(!render(&commands).contains("debug-label"));
+    }
+
+    #[test]
+    fn test_box_overlap_warning() {
+        let draw_box = |x, y| Command::Box {
+            theme: "DEFAULT".to_string(),
+            x,
+            y,
+            box_width: Some(100.0),
+            box_height: Some(50.0),
+            x_justify: None,
+            y_justify: None,
+            message: None,
+            text_anchor: None,
+        };
+        let commands = [
+            Command::Draw,
+            draw_box(10.0, 10.0),
+            draw_box(60.0, 40.0),
+            draw_box(110.0, 10.0),
+        ];
+
+        let mut renderer = SvgRenderer::new();
+        renderer.set_overlap_warnings(true);
+        renderer.process_commands(&commands).unwrap();
+
+        assert_eq!(
+            renderer.warnings(),
+            &[
+                RenderWarning::BoxOverlap {
+                    first: Extent::new(10.0, 10.0, 100.0, 50.0),
+                    second: Extent::new(60.0, 40.0, 100.0, 50.0),
+                },
+                RenderWarning::BoxOverlap {
+                    first: Extent::new(60.0, 40.0, 100.0, 50.0),
+                    second: Extent::new(110.0, 10.0, 100.0, 50.0),
+                },
+            ]
+        );
+        assert_eq!(
+            renderer.warnings()[0].to_string(),
+            "Box at (10,10) overlaps box at (60,40)"
+        );
+
+        // Off by default
+        let mut renderer = SvgRenderer::new();
+        renderer.process_commands(&commands).unwrap();
+        assert!(renderer.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_pin_set_mm_units() {
+        let line_step_at = |dpi| {
+            let mut set = pin_set(Side::Left, 20.0);
+            if let Command::PinSet {
+                line_step, units, ..
+            } = &mut set
+            {
+                *line_step = 2.54;
+                *units = Some(Unit::Mm);
+            }
+
+            let mut renderer = SvgRenderer::new();
+            renderer
+                .process_commands(&[Command::Dpi { dpi }, Command::Draw, set])
+                .unwrap();
+            renderer
+                .line_settings
+                .get("LINESTEP")
+                .unwrap()
+                .parse::<f32>()
+                .unwrap()
+        };
+
+        assert!((line_step_at(300) - 30.0).abs() < 1e-3);
+        assert!((line_step_at(600) - 60.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_color_scale() {
+        let pin = |voltage: &str| Command::Pin {
+            wire: None,
+            pin_type: None,
+            group: None,
+            attributes: vec!["D1".to_string(), voltage.to_string()],
+            leader: None,
+        };
+
+        let svg = render(&[
+            Command::Labels {
+                default: "DEFAULT".to_string(),
+                pin_type: None,
+                group: None,
+                labels: vec!["Name".to_string(), "Volts".to_string()],
+            },
+            Command::ColorScale {
+                min: 0.0,
+                max: 5.0,
+                color_low: "#0000ff".to_string(),
+                color_high: "#ff0000".to_string(),
+                label: Some("Volts".to_string()),
+            },
+            Command::Draw,
+            pin_set(Side::Left, 20.0),
+            pin("2.5"),
+            pin("12"),
+            pin("n/a"),
+        ]);
+
+        // Halfway between blue and red, then clamped to the top of the scale
+        assert!(svg.contains(r##"fill="#800080""##), "{}", svg);
+        assert_eq!(svg.matches(r##"fill="#ff0000""##).count(), 1, "{}", svg);
+
+        // Other columns and non-numeric values keep the theme fill
+        assert_eq!(svg.matches(r#"fill="blue""#).count(), 4, "{}", svg);
+    }
+
+    #[test]
+    fn test_pin_count() {
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                Command::Draw,
+                pin_set(Side::Left, 20.0),
+                pin(None, None, None),
+                pin(Some(WireType::Digital), None, None),
+                Command::PinText {
+                    wire: None,
+                    pin_type: None,
+                    pin_group: None,
+                    msg_theme: "DEFAULT".to_string(),
+                    label: None,
+                    message: "Reserved".to_string(),
+                    color: None,
+                },
+            ])
+            .unwrap();
+
+        assert_eq!(renderer.pin_count(), 3);
+    }
+
+    #[test]
+    fn test_match_rule_theme() {
+        let pin = |name: &str| Command::Pin {
+            wire: None,
+            pin_type: None,
+            group: None,
+            attributes: vec![name.to_string(), "X".to_string()],
+            leader: None,
+        };
+
+        let svg = render(&[
+            Command::Labels {
+                default: "DEFAULT".to_string(),
+                pin_type: None,
+                group: None,
+                labels: vec!["Name".to_string(), "Func".to_string(), "GPIO".to_string()],
+            },
+            Command::FillColor {
+                default: "white".to_string(),
+                pin_type: None,
+                group: None,
+                colors: vec!["grey".to_string(), "grey".to_string(), "green".to_string()],
+            },
+            Command::Match {
+                pattern: "^GPIO".to_string(),
+                theme: "GPIO".to_string(),
+            },
+            Command::Match {
+                pattern: "5$".to_string(),
+                theme: "Name".to_string(),
+            },
+            Command::Draw,
+            pin_set(Side::Left, 20.0),
+            pin("GPIO5"),
+            pin("VCC"),
+        ]);
+
+        // The first matching rule wins, unmatched pins keep their column themes
+        assert_eq!(svg.matches(r#"fill="green""#).count(), 2, "{}", svg);
+        assert_eq!(svg.matches(r#"fill="grey""#).count(), 2, "{}", svg);
+    }
+
+    #[test]
+    fn test_zebra_rows() {
+        let pin = |name: &str| Command::Pin {
+            wire: None,
+            pin_type: None,
+            group: None,
+            attributes: vec![name.to_string()],
+            leader: None,
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                Command::Draw,
+                pin_set(Side::Left, 20.0),
+                Command::Zebra {
+                    color: "#eeeeee".to_string(),
+                },
+                pin("D1"),
+                pin("D2"),
+                pin("D3"),
+                pin("D4"),
+            ])
+            .unwrap();
+
+        let stripes: Vec<&Box<dyn svg::Node>> = renderer
+            .document
+            .get_children()
+            .iter()
+            .filter(|node| node.to_string().contains(r##"fill="#eeeeee""##))
+            .collect();
+        assert_eq!(stripes.len(), 2);
+
+        // Stripes sit behind rows 2 and 4 only
+        let extents = renderer.box_extents();
+        for (stripe, row) in stripes.iter().zip([1, 3]) {
+            let stripe = stripe.to_string();
+            assert!(stripe.starts_with("<rect"), "{}", stripe);
+            assert!(
+                stripe.contains(&format!(r#"y="{}""#, extents[row].y)),
+                "{}",
+                stripe
+            );
+        }
+        for row in [0, 2] {
+            let y = format!(r#"y="{}""#, extents[row].y);
+            assert!(!stripes.iter().any(|s| s.to_string().contains(&y)));
+        }
+    }
+
+    #[test]
+    fn test_header_boxes() {
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string(), "Port".to_string()],
+                },
+                Command::Draw,
+                pin_set(Side::Left, 20.0),
+                Command::Header { theme: None },
+                Command::Pin {
+                    wire: None,
+                    pin_type: None,
+                    group: None,
+                    attributes: vec!["D1".to_string(), "PB1".to_string()],
+                    leader: None,
+                },
+            ])
+            .unwrap();
+
+        // One header box per label, above the pin boxes of the same column
+        let extents = renderer.box_extents();
+        assert_eq!(extents.len(), 4);
+        for column in 0..2 {
+            let (header, pin) = (extents[column], extents[column + 2]);
+            assert_eq!(header.x, pin.x);
+            assert!(header.y < pin.y);
+        }
+        assert!(renderer.to_string().contains("Port"));
+    }
+
+    #[test]
+    fn test_header_needs_pin_set() {
+        let mut renderer = SvgRenderer::new();
+        assert!(
+            renderer
+                .process_commands(&[Command::Draw, Command::Header { theme: None }])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_bounds_json() {
+        let boxed = |x: f32| Command::Box {
+            theme: "PLAIN".to_string(),
+            x,
+            y: 5.0,
+            box_width: Some(40.0),
+            box_height: Some(20.0),
+            x_justify: None,
+            y_justify: None,
+            message: Some("Box".to_string()),
+            text_anchor: None,
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[Command::Draw, boxed(10.0), boxed(60.5)])
+            .unwrap();
+
+        assert_eq!(
+            renderer.bounds_json(),
+            concat!(
+                r#"[{"id":"box-0","x":10,"y":5,"width":40,"height":20},"#,
+                r#"{"id":"box-1","x":60.5,"y":5,"width":40,"height":20}]"#
+            )
+        );
+        assert_eq!(SvgRenderer::new().bounds_json(), "[]");
+    }
+
+    #[test]
+    fn test_pin_set_advance() {
+        let pin = |name: &str| Command::Pin {
+            wire: None,
+            pin_type: None,
+            group: None,
+            attributes: vec![name.to_string()],
+            leader: None,
+        };
+        let advancing = |gap: f32| {
+            let mut set = pin_set(Side::Right, 20.0);
+            if let Command::PinSet { advance, .. } = &mut set {
+                *advance = Some(gap);
+            }
+            set
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                Command::BoxTheme {
+                    name: "SKEWED".to_string(),
+                    border_color: "black".to_string(),
+                    border_opacity: 1.0,
+                    fill_color: "white".to_string(),
+                    fill_opacity: 1.0,
+                    line_width: 1.0,
+                    box_width: 50.0,
+                    box_height: 20.0,
+                    box_cr_x: 0.0,
+                    box_cr_y: 0.0,
+                    box_skew: 0.0,
+                    box_skew_offset: 0.0,
+                    shape: None,
+                },
+                Command::Draw,
+                Command::Anchor { x: 100.0, y: 100.0 },
+                advancing(15.0),
+                pin("D1"),
+                pin("D2"),
+                pin_set(Side::Right, 20.0),
+                pin("D3"),
+            ])
+            .unwrap();
+
+        // The first set spans from its anchor to the right edge of its boxes
+        let extents = renderer.box_extents();
+        let width = extents[0].x + extents[0].width - 100.0;
+        assert_eq!(extents[2].x, extents[0].x + width + 15.0);
+        assert_eq!(extents[2].y, extents[0].y);
+    }
+
+    #[test]
+    fn test_mirror_pin_set() {
+        let pin = |name: &str| Command::Pin {
+            wire: None,
+            pin_type: None,
+            group: None,
+            attributes: vec![name.to_string()],
+            leader: None,
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                Command::BoxTheme {
+                    name: "SKEWED".to_string(),
+                    border_color: "black".to_string(),
+                    border_opacity: 1.0,
+                    fill_color: "white".to_string(),
+                    fill_opacity: 1.0,
+                    line_width: 1.0,
+                    box_width: 50.0,
+                    box_height: 20.0,
+                    box_cr_x: 0.0,
+                    box_cr_y: 0.0,
+                    box_skew: 0.0,
+                    box_skew_offset: 0.0,
+                    shape: None,
+                },
+                Command::Draw,
+                Command::Anchor { x: 100.0, y: 100.0 },
+                pin_set(Side::Left, 20.0),
+                pin("D1"),
+                pin("D2"),
+                Command::Mirror { offset: 10.0 },
+            ])
+            .unwrap();
+
+        // Same pins again, reflected across x = 110 and on the same rows
+        let extents = renderer.box_extents();
+        assert_eq!(extents.len(), 4);
+        assert_eq!(renderer.pin_count(), 4);
+        for row in 0..2 {
+            let (original, mirrored) = (extents[row], extents[row + 2]);
+            assert_eq!(mirrored.x + mirrored.width, 220.0 - original.x);
+            assert_eq!(mirrored.y, original.y);
+        }
+        assert_eq!(renderer.to_string().matches("\nD1\n").count(), 2);
+
+        // A set after an advancing one is mirrored from where the advance put it
+        let mut advancing = pin_set(Side::Left, 20.0);
+        if let Command::PinSet { advance, .. } = &mut advancing {
+            *advance = Some(15.0);
+        }
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                Command::Draw,
+                Command::Anchor { x: 100.0, y: 100.0 },
+                advancing,
+                pin("D1"),
+                pin_set(Side::Left, 20.0),
+                pin("D2"),
+                pin("D3"),
+                Command::Mirror { offset: 10.0 },
+            ])
+            .unwrap();
+
+        let extents = renderer.box_extents();
+        assert_eq!(extents.len(), 5);
+        let first = extents[0];
+        let origin_x = (first.x + first.width).max(100.0) - first.x.min(100.0) + 115.0;
+        for row in 1..3 {
+            let (original, mirrored) = (extents[row], extents[row + 2]);
+            assert_eq!(
+                mirrored.x + mirrored.width,
+                (origin_x + 10.0) * 2.0 - original.x
+            );
+            assert_eq!(mirrored.y, original.y);
+        }
+    }
+
+    #[test]
+    fn test_mirror_needs_pin_set() {
+        let mut renderer = SvgRenderer::new();
+        assert!(
+            renderer
+                .process_commands(&[Command::Draw, Command::Mirror { offset: 10.0 }])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_empty_cell_outlines() {
+        let draw = |enabled: bool| {
+            let mut unpacked = pin_set(Side::Right, 20.0);
+            if let Command::PinSet { packed, .. } = &mut unpacked {
+                *packed = false;
+            }
+
+            let mut renderer = SvgRenderer::new();
+            renderer.set_empty_cell_outlines(enabled);
+            renderer
+                .process_commands(&[
+                    Command::Labels {
+                        default: "DEFAULT".to_string(),
+                        pin_type: None,
+                        group: None,
+                        labels: vec!["Name".to_string(), "Alt".to_string()],
+                    },
+                    Command::Draw,
+                    unpacked,
+                    Command::Pin {
+                        wire: None,
+                        pin_type: None,
+                        group: None,
+                        attributes: vec!["D1".to_string(), String::new()],
+                        leader: None,
+                    },
+                ])
+                .unwrap();
+            renderer.to_string()
+        };
+
+        assert!(!draw(false).contains("empty-cell"));
+
+        let svg = draw(true);
+        let outlines: Vec<&str> = svg.lines().filter(|l| l.contains("empty-cell")).collect();
+        assert_eq!(outlines.len(), 1, "{}", svg);
+        assert!(outlines[0].starts_with("<rect"), "{}", outlines[0]);
+        assert!(outlines[0].contains(r#"fill="none""#), "{}", outlines[0]);
+        assert!(
+            outlines[0].contains(r#"stroke-opacity="0.3""#),
+            "{}",
+            outlines[0]
+        );
+    }
+
+    #[test]
+    fn test_group_summary() {
+        let group = |name: &str, color: &str| Command::Group {
+            name: name.to_string(),
+            color: color.to_string(),
+            opacity: 1.0,
+            shape: None,
+        };
+        let pin = |group: &str, name: &str| Command::Pin {
+            wire: None,
+            pin_type: None,
+            group: Some(group.to_string()),
+            attributes: vec![name.to_string()],
+            leader: None,
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                group("PORTA", "red"),
+                group("PORTB", "blue"),
+                Command::Draw,
+                pin_set(Side::Left, 20.0),
+                pin("PORTA", "PA0"),
+                pin("PORTB", "PB0"),
+                pin("PORTA", "PA1"),
+                Command::GroupTable { x: 10.0, y: 10.0 },
+            ])
+            .unwrap();
+
+        assert_eq!(
+            renderer.group_summary(),
+            vec![
+                GroupSummary {
+                    name: "PORTA".to_string(),
+                    color: "red".to_string(),
+                    pins: vec!["PA0".to_string(), "PA1".to_string()],
+                },
+                GroupSummary {
+                    name: "PORTB".to_string(),
+                    color: "blue".to_string(),
+                    pins: vec!["PB0".to_string()],
+                },
+            ]
+        );
+
+        let svg = renderer.to_string();
+        assert!(svg.contains("PORTA: PA0, PA1"), "{}", svg);
+        assert!(svg.contains("PORTB: PB0"), "{}", svg);
+    }
+
+    #[test]
+    fn test_image_rotation_pivot() {
+        let path = std::env::temp_dir().join("pinout_image_pivot.png");
+        image::RgbImage::new(20, 10).save(&path).unwrap();
+
+        let draw = |pivot_x: Option<f32>, pivot_y: Option<f32>| {
+            render(&[
+                Command::Draw,
+                Command::Image {
+                    name: path.to_str().unwrap().to_string(),
+                    x: Some(100.0),
+                    y: Some(50.0),
+                    w: None,
+                    h: None,
+                    cx: None,
+                    cy: None,
+                    cw: None,
+                    ch: None,
+                    rot: Some(90.0),
+                    pivot_x,
+                    pivot_y,
+                    anchor: None,
+                },
+            ])
+        };
+
+        // Centered by default, otherwise around the pivot, falling back per axis
+        assert!(draw(None, None).contains(r#"transform="rotate(90 100 50)""#));
+        assert!(draw(Some(10.0), Some(20.0)).contains(r#"transform="rotate(90 10 20)""#));
+        assert!(draw(Some(10.0), None).contains(r#"transform="rotate(90 10 50)""#));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_layers() {
+        let boxed = |message: &str| Command::Box {
+            theme: "PLAIN".to_string(),
+            x: 10.0,
+            y: 10.0,
+            box_width: Some(40.0),
+            box_height: Some(20.0),
+            x_justify: None,
+            y_justify: None,
+            message: Some(message.to_string()),
+            text_anchor: None,
+        };
+        let commands = [
+            Command::Draw,
+            boxed("Always"),
+            Command::Layer {
+                name: Some("NOTES".to_string()),
+            },
+            boxed("Note"),
+            Command::Layer { name: None },
+            boxed("Footer"),
+        ];
+
+        let draw = |enabled: Option<&[&str]>| {
+            let mut renderer = SvgRenderer::new();
+            if let Some(enabled) = enabled {
+                renderer.set_enabled_layers(enabled.iter().copied());
+            }
+            renderer.process_commands(&commands).unwrap();
+            renderer.to_string()
+        };
+
+        // Every layer is drawn unless layers are chosen
+        let svg = draw(None);
+        assert!(svg.contains("Note"), "{}", svg);
+
+        let svg = draw(Some(&["NOTES"]));
+        assert!(svg.contains("Note"), "{}", svg);
+
+        let svg = draw(Some(&[]));
+        assert!(!svg.contains("Note"), "{}", svg);
+        assert!(svg.contains("Always") && svg.contains("Footer"), "{}", svg);
+
+        let path = std::env::temp_dir().join("pinout_layers.svg");
+        generate_svg_with_layers(&commands, path.to_str().unwrap(), &["OTHER"]).unwrap();
+        let svg = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(!svg.contains("Note"), "{}", svg);
+    }
+
+    #[test]
+    fn test_rotated_export() {
+        let draw = |rotated: bool| {
+            let mut renderer = SvgRenderer::new();
+            renderer.set_rotated(rotated);
+            renderer
+                .process_commands(&[
+                    Command::Page {
+                        page_name: "A4-L".to_string(),
+                        size: None,
+                    },
+                    Command::Draw,
+                    Command::SheetInfo { x: 10.0, y: 20.0 },
+                ])
+                .unwrap();
+            renderer
+        };
+
+        let landscape = draw(false);
+        let portrait = draw(true);
+        let attribute = |renderer: &SvgRenderer, name: &str| {
+            renderer.document.get_attributes()[name].to_string()
+        };
+
+        assert_eq!(
+            attribute(&portrait, "width"),
+            attribute(&landscape, "height")
+        );
+        assert_eq!(
+            attribute(&portrait, "height"),
+            attribute(&landscape, "width")
+        );
+        let (width, height) = landscape.page_resolution;
+        assert_eq!(
+            attribute(&portrait, "viewBox"),
+            format!("0 0 {} {}", height, width)
+        );
+
+        // Content sits in one group turned onto the portrait page, definitions stay outside
+        let children = portrait.document.get_children();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].get_name(), "defs");
+        let content = children[1].to_string();
+        assert!(
+            content.starts_with(&format!(
+                r#"<g transform="translate({} 0) rotate(90)">"#,
+                height
+            )),
+            "{}",
+            content
+        );
+        assert!(content.contains("Sheet 1 of 1"), "{}", content);
+    }
+
+    #[test]
+    fn test_max_embed_size() {
+        let path = std::env::temp_dir().join("pinout_max_embed_size.png");
+        image::RgbImage::new(20, 10).save(&path).unwrap();
+        let size = std::fs::metadata(&path).unwrap().len();
+
+        let draw = |max: u64| {
+            let mut renderer = SvgRenderer::new();
+            renderer.set_max_embed_size(max);
+            renderer.process_commands(&[
+                Command::Draw,
+                Command::Image {
+                    name: path.to_str().unwrap().to_string(),
+                    x: Some(100.0),
+                    y: Some(50.0),
+                    w: None,
+                    h: None,
+                    cx: None,
+                    cy: None,
+                    cw: None,
+                    ch: None,
+                    rot: None,
+                    pivot_x: None,
+                    pivot_y: None,
+                    anchor: None,
+                },
+            ])
+        };
+        let fits = draw(size);
+        let too_big = draw(size - 1);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(fits.is_ok());
+        assert!(matches!(too_big, Err(RenderError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn test_pin_numbers() {
+        let numbered = |side| {
+            let mut set = pin_set(side, 40.0);
+            if let Command::PinSet { first_number, .. } = &mut set {
+                *first_number = Some(5);
+            }
+            set
+        };
+
+        let svg = render(&[
+            wire(WireType::Digital),
+            Command::Draw,
+            Command::Anchor { x: 100.0, y: 100.0 },
+            numbered(Side::Right),
+            pin(Some(WireType::Digital), None, None),
+            pin(None, None, None),
+            Command::Anchor { x: 400.0, y: 100.0 },
+            numbered(Side::Left),
+            pin(Some(WireType::Digital), None, None),
+        ]);
+
+        let numbers: Vec<&str> = svg
+            .lines()
+            .filter(|line| line.contains("pin-number"))
+            .collect();
+        assert_eq!(numbers.len(), 3, "{}", svg);
+
+        // The leader runs from the group edge at 140 to 180, one row after another
+        assert!(numbers[0].contains(r#"x="160""#), "{}", numbers[0]);
+        assert!(numbers[0].contains(r#"y="110.5""#), "{}", numbers[0]);
+        assert!(numbers[1].contains(r#"y="135.5""#), "{}", numbers[1]);
+
+        // On the left the leader runs from 360 out to 320
+        assert!(numbers[2].contains(r#"x="340""#), "{}", numbers[2]);
+        assert!(svg.contains("\n5\n</text>"), "{}", svg);
+        assert!(svg.contains("\n6\n</text>"), "{}", svg);
+    }
+
+    #[test]
+    fn test_message_font_theme() {
+        let message = |font: &str, font_size: Option<f32>| Command::Message {
+            x: Some(10.0),
+            y: Some(10.0),
+            line_step: None,
+            font: Some(font.to_string()),
+            font_size,
+            x_justify: None,
+            y_justify: None,
+            tab_stops: None,
+            max_height: None,
+        };
+        let draw = |message: Command| {
+            render(&[
+                Command::TextFont {
+                    theme_name: "TITLE".to_string(),
+                    font: "Serif".to_string(),
+                    size: 30.0,
+                    outline_color: "red".to_string(),
+                    color: "blue".to_string(),
+                    slant: FontSlant::Italic,
+                    bold: FontBoldness::Bold,
+                    stretch: FontStretch::Condensed,
+                },
+                Command::Draw,
+                message,
+                Command::Text {
+                    edge_color: String::new(),
+                    color: String::new(),
+                    message: "Hello".to_string(),
+                    new_line: false,
+                },
+            ])
+        };
+
+        // By name or by theme, every property of the font theme applies
+        for font in ["TITLE", "FONT_TITLE"] {
+            let svg = draw(message(font, None));
+            for attribute in [
+                r#"font-family="Serif""#,
+                r#"font-size="30""#,
+                r#"font-style="italic""#,
+                r#"font-weight="bold""#,
+                r#"font-stretch="condensed""#,
+                r#"stroke="red""#,
+            ] {
+                assert!(svg.contains(attribute), "{} {}", attribute, svg);
+            }
+        }
+
+        // An inline size wins, and a plain family name is used as is
+        assert!(draw(message("TITLE", Some(12.0))).contains(r#"font-size="12""#));
+        assert!(draw(message("Courier", None)).contains(r#"font-family="Courier""#));
+    }
+
+    #[test]
+    fn test_box_text_anchor_override() {
+        let svg = render(&[
+            Command::Draw,
+            Command::Box {
+                theme: "PLAIN".to_string(),
+                x: 10.0,
+                y: 10.0,
+                box_width: Some(100.0),
+                box_height: Some(20.0),
+                x_justify: Some(JustifyX::Left),
+                y_justify: None,
+                message: Some("Label".to_string()),
+                text_anchor: Some(JustifyX::Center),
+            },
+        ]);
+
+        // Text keeps the left justified position but is centered on it
+        assert!(svg.contains(r#"text-anchor="middle""#), "{}", svg);
+        assert!(svg.contains(r#"x="-50""#), "{}", svg);
+    }
+
+    struct MockFetcher;
+
+    impl FontFetcher for MockFetcher {
+        fn fetch(&self, url: &str) -> Result<Vec<u8>, RenderError> {
+            match url {
+                "https://fonts.googleapis.com/css2?family=Roboto" => Ok(
+                    b"@font-face { font-family: 'Roboto'; src: url(https://fonts.gstatic.com/roboto.woff2) format('woff2'); }"
+                        .to_vec(),
+                ),
+                "https://fonts.gstatic.com/roboto.woff2" => Ok(b"wOF2font".to_vec()),
+                _ => Err(RenderError::MissingData(url.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    fn test_google_font_embedded() {
+        let google_font = |link: &str| {
+            let mut renderer = SvgRenderer::new();
+            renderer.set_font_fetcher(MockFetcher);
+            renderer
+                .process_commands(&[
+                    Command::Draw,
+                    Command::GoogleFont {
+                        link: link.to_string(),
+                    },
+                ])
+                .unwrap();
+            renderer
+        };
+
+        let renderer = google_font("https://fonts.googleapis.com/css2?family=Roboto");
+        let svg = renderer.to_string();
+        let data = general_purpose::STANDARD.encode("wOF2font");
+        assert!(
+            svg.contains(&format!(
+                "url(data:font/woff2;base64,{}) format('woff2')",
+                data
+            )),
+            "{}",
+            svg
+        );
+        assert!(renderer.warnings().is_empty());
+        assert_eq!(renderer.embedded_bytes(), "wOF2font".len());
+
+        // Failed downloads warn and leave the font to the viewer
+        let renderer = google_font("https://fonts.googleapis.com/css2?family=Missing");
+        assert!(!renderer.to_string().contains("base64"));
+        assert!(matches!(
+            renderer.warnings(),
+            [RenderWarning::FontFetch { .. }]
+        ));
+    }
+
+    #[test]
+    fn test_pixel_dimensions() {
+        let mut renderer = SvgRenderer::new();
+        renderer.set_pixel_dimensions(true);
+        renderer
+            .process_commands(&[
+                Command::Page {
+                    page_name: "A4-P".to_string(),
+                    size: None,
+                },
+                Command::Dpi { dpi: 150 },
+                Command::Draw,
+            ])
+            .unwrap();
+
+        let (width, height) = renderer.page_resolution;
+        assert_eq!((width, height), (1240, 1753));
+
+        let svg = renderer.to_string();
+        assert!(svg.contains(r#"width="1240px""#), "{}", svg);
+        assert!(svg.contains(r#"height="1753px""#), "{}", svg);
+        assert!(svg.contains(r#"viewBox="0 0 1240 1753""#), "{}", svg);
+    }
+
+    #[test]
+    fn test_device_pixel_ratio() {
+        let mut renderer = SvgRenderer::new();
+        renderer.set_pixel_dimensions(true);
+        renderer.set_device_pixel_ratio(2.0).unwrap();
+        renderer
+            .process_commands(&[
+                Command::Page {
+                    page_name: "A4-P".to_string(),
+                    size: None,
+                },
+                Command::Dpi { dpi: 150 },
+                Command::Draw,
+            ])
+            .unwrap();
+
+        let svg = renderer.to_string();
+        assert!(svg.contains(r#"width="2480px""#), "{}", svg);
+        assert!(svg.contains(r#"height="3506px""#), "{}", svg);
+        assert!(svg.contains(r#"viewBox="0 0 1240 1753""#), "{}", svg);
+
+        assert!(renderer.set_device_pixel_ratio(0.0).is_err());
+    }
+
+    #[test]
+    fn test_pin_data_file() {
+        let path = std::env::temp_dir().join("pinout_pin_data.csv");
+        std::fs::write(
+            &path,
+            "# Wire, Type, Group, Name\nDIGITAL,IO,,D1\nPWM,,,D2\n",
+        )
+        .unwrap();
+
+        let commands = [
+            Command::Labels {
+                default: "DEFAULT".to_string(),
+                pin_type: None,
+                group: None,
+                labels: vec!["Name".to_string()],
+            },
+            Command::Draw,
+            pin_set(Side::Left, 20.0),
+            Command::PinData {
+                path: path.to_str().unwrap().to_string(),
+                delimiter: b',',
+                decimal_separator: '.',
+                max_rows: None,
+            },
+        ];
+        let mut renderer = SvgRenderer::new();
+        let result = renderer.process_commands(&commands);
+        // Data files are held to the embed limit, like images
+        let mut limited = SvgRenderer::new();
+        limited.set_max_embed_size(10);
+        let limited = limited.process_commands(&commands);
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+        assert!(matches!(limited, Err(RenderError::LimitExceeded(_))));
+
+        // One pin row, and one name box, per data row
+        assert_eq!(renderer.pin_row, 2);
+        assert_eq!(renderer.box_extents().len(), 2);
+        assert_eq!(renderer.offset_y, 50.0);
+    }
+
+    #[test]
+    fn test_pin_data_options() {
+        let dir = std::env::temp_dir().join("pinout_pin_data_options");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("pinout.csv"),
+            "LABELS;DEFAULT;TYPE;GROUP;Name\nDRAW\nPINSET;LEFT;PACKED;CENTER;CENTER;25;10;20;40;5;0\nPINDATA;pins.csv\n",
+        )
+        .unwrap();
+        let rows: String = (2..=5).map(|pin| format!("PWM;;;D{}\n", pin)).collect();
+        std::fs::write(
+            dir.join("pins.csv"),
+            format!("ANALOG:60,5;IO;;D1\n{}", rows),
+        )
+        .unwrap();
+
+        // The data file is found beside the file naming it and read with its options
+        let path = dir.join("pinout.csv");
+        let parse = |max_commands| {
+            let options = crate::parser::csv::ParseOptions {
+                max_commands,
+                ..crate::parser::csv::ParseOptions::comma_decimal()
+            };
+            crate::parser::csv::parse_csv_file_with_options(path.to_str().unwrap(), &options)
+                .unwrap()
+        };
+        let mut renderer = SvgRenderer::new();
+        let result = renderer.process_commands(&parse(None));
+        let mut limited = SvgRenderer::new();
+        let limited = limited.process_commands(&parse(Some(4)));
+        std::fs::remove_dir_all(&dir).unwrap();
+        result.unwrap();
+
+        assert_eq!(renderer.pin_row, 5);
+        let leaders = renderer.to_string();
+        let points = leaders
+            .lines()
+            .find(|line| line.starts_with("<polyline"))
+            .unwrap();
+        assert!(points.contains(" 60.5,0\""), "{}", leaders);
+
+        // A data file holds no more rows than its parent may hold commands
+        assert!(matches!(limited, Err(RenderError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn test_zero_width_box_warning() {
+        let labels = Command::Labels {
+            default: "DEFAULT".to_string(),
+            pin_type: None,
+            group: None,
+            labels: vec!["Name".to_string()],
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[labels.clone(), Command::Draw])
+            .unwrap();
+        assert_eq!(
+            renderer.warnings(),
+            &[RenderWarning::ZeroWidthBox {
+                label: "Name".to_string(),
+                theme: "SKEWED".to_string(),
+            }]
+        );
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                labels,
+                Command::BoxTheme {
+                    name: "SKEWED".to_string(),
+                    border_color: "black".to_string(),
+                    border_opacity: 1.0,
+                    fill_color: "white".to_string(),
+                    fill_opacity: 1.0,
+                    line_width: 1.0,
+                    box_width: 80.0,
+                    box_height: 20.0,
+                    box_cr_x: 0.0,
+                    box_cr_y: 0.0,
+                    box_skew: 0.0,
+                    box_skew_offset: 0.0,
+                    shape: None,
+                },
+                Command::Draw,
+            ])
+            .unwrap();
+        assert!(renderer.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_source_map() {
+        let mut renderer = SvgRenderer::new();
+        renderer.set_source_tracking(true);
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                Command::Draw,
+                pin_set(Side::Left, 20.0),
+                Command::Pin {
+                    wire: Some(WireType::Digital),
+                    pin_type: Some(PinType::IO),
+                    group: None,
+                    attributes: vec!["D1".to_string()],
+                    leader: None,
+                },
+            ])
+            .unwrap();
+
+        let source_map = renderer.source_map();
+        assert_eq!(source_map.len(), 1);
+
+        // Pin type indicator, leader and name box all come from the PIN command
+        let ids = &source_map[&3];
+        assert_eq!(ids, &["cmd3-0", "cmd3-1", "cmd3-2"]);
+
+        let svg = renderer.to_string();
+        for id in ids {
+            assert!(svg.contains(&format!(r#"id="{}""#, id)), "{}", svg);
+        }
+    }
+
+    #[test]
+    fn test_source_map_insertions() {
+        let mut renderer = SvgRenderer::new();
+        renderer.set_source_tracking(true);
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                Command::BoxTheme {
+                    name: "SHELL".to_string(),
+                    border_color: "black".to_string(),
+                    border_opacity: 1.0,
+                    fill_color: "grey".to_string(),
+                    fill_opacity: 1.0,
+                    line_width: 2.0,
+                    box_width: 0.0,
+                    box_height: 0.0,
+                    box_cr_x: 8.0,
+                    box_cr_y: 8.0,
+                    box_skew: 0.0,
+                    box_skew_offset: 0.0,
+                    shape: None,
+                },
+                Command::Draw,
+                Command::SheetInfo { x: 10.0, y: 10.0 },
+                Command::Watermark {
+                    text: "DRAFT".to_string(),
+                    opacity: 0.2,
+                    angle: None,
+                },
+                pin_set(Side::Left, 20.0),
+                Command::Shell {
+                    theme: "SHELL".to_string(),
+                    padding: None,
+                },
+                Command::Pin {
+                    wire: None,
+                    pin_type: None,
+                    group: None,
+                    attributes: vec!["D1".to_string()],
+                    leader: None,
+                },
+                Command::Anchor { x: 200.0, y: 100.0 },
+            ])
+            .unwrap();
+
+        // Elements inserted beneath earlier ones belong to the command that drew them, the
+        // shell to SHELL although it is drawn when the next ANCHOR ends the pin set
+        let source_map = renderer.source_map();
+        assert_eq!(source_map[&3], ["cmd3-0"]);
+        assert_eq!(source_map[&4], ["cmd4-0"]);
+        assert_eq!(source_map[&6], ["cmd6-0"]);
+        assert_eq!(source_map[&7], ["cmd7-0"]);
+        assert!(!source_map.contains_key(&8), "{:?}", source_map);
+
+        let svg = renderer.to_string();
+        for (id, tag) in [
+            ("cmd3-0", "<text"),
+            ("cmd4-0", "<text"),
+            ("cmd6-0", "<rect"),
+        ] {
+            let element = &svg[..svg.find(&format!(r#"id="{}""#, id)).unwrap()];
+            assert!(element.rfind('<') == element.rfind(tag), "{}", svg);
+        }
+    }
+
+    #[test]
+    fn test_group_shape() {
+        let draw = |shape| {
+            render(&[
+                Command::Group {
+                    name: "X".to_string(),
+                    color: "red".to_string(),
+                    opacity: 1.0,
+                    shape,
+                },
+                Command::Draw,
+                Command::Anchor { x: 100.0, y: 100.0 },
+                pin_set(Side::Left, 20.0),
+                pin(None, None, Some("X")),
+            ])
+        };
+
+        let svg = draw(Some(GroupShape::Square));
+        assert!(
+            svg.contains(r#"<rect data-group="X" fill="red" fill-opacity="1" height="40" stroke="black" stroke-opacity="1" stroke-width="2" width="40" x="60" y="92.5"/>"#),
+            "{}",
+            svg
+        );
+        assert!(!svg.contains("<circle"), "{}", svg);
+
+        let svg = draw(Some(GroupShape::Diamond));
+        assert!(
+            svg.contains(r#"<polygon data-group="X" fill="red""#),
+            "{}",
+            svg
+        );
+
+        // Circles stay the default
+        let svg = draw(None);
+        assert!(
+            svg.contains(r#"<circle cx="80" cy="112.5" data-group="X" fill="red""#),
+            "{}",
+            svg
+        );
+    }
+
+    #[test]
+    fn test_duplicate_theme_warning() {
+        let group = |color: &str| Command::Group {
+            name: "X".to_string(),
+            color: color.to_string(),
+            opacity: 1.0,
+            shape: None,
+        };
+
+        let mut dashed = wire(WireType::Pwm);
+        if let Command::Wire { dash, .. } = &mut dashed {
+            *dash = Some("6 3".to_string());
+        }
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                group("red"),
+                group("blue"),
+                dashed,
+                wire(WireType::Pwm),
+                Command::Draw,
+            ])
+            .unwrap();
+
+        assert_eq!(
+            renderer.warnings(),
+            &[
+                RenderWarning::DuplicateTheme {
+                    theme: "GROUP_X".to_string(),
+                },
+                RenderWarning::DuplicateTheme {
+                    theme: "PINWIRE_PWM".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            renderer.get_theme("GROUP_X", "FILL COLOR", String::new()),
+            "blue"
+        );
+        // A dash only the first definition gave is not kept
+        assert_eq!(renderer.get_theme("PINWIRE_PWM", "DASH", String::new()), "");
+    }
+
+    #[test]
+    fn test_export_region() {
+        let draw_box = |x, y, text: &str| Command::Box {
+            theme: "PLAIN".to_string(),
+            x,
+            y,
+            box_width: Some(100.0),
+            box_height: Some(50.0),
+            x_justify: None,
+            y_justify: None,
+            message: Some(text.to_string()),
+            text_anchor: None,
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Draw,
+                draw_box(500.0, 500.0, "Near"),
+                draw_box(2000.0, 1500.0, "Far"),
+            ])
+            .unwrap();
+
+        let svg = renderer.export_region(400.0, 425.0, 300.0, 200.0);
+        assert!(svg.contains(r#"viewBox="400 425 300 200""#), "{}", svg);
+        assert!(svg.contains("Near"), "{}", svg);
+        assert!(!svg.contains("Far"), "{}", svg);
+    }
+
+    #[test]
+    fn test_pin_attribute_values_stacked() {
+        let svg = render(&[
+            Command::Labels {
+                default: "DEFAULT".to_string(),
+                pin_type: None,
+                group: None,
+                labels: vec!["Name".to_string()],
+            },
+            Command::Draw,
+            pin_set(Side::Left, 20.0),
+            Command::Pin {
+                wire: None,
+                pin_type: None,
+                group: None,
+                attributes: vec!["TX|GPIO1".to_string()],
+                leader: None,
+            },
+        ]);
+
+        assert!(svg.contains(r#"<tspan dy="0" x="0">TX</tspan>"#), "{}", svg);
+        assert!(
+            svg.contains(r#"<tspan dy="10" x="0">GPIO1</tspan>"#),
+            "{}",
+            svg
+        );
+        assert!(!svg.contains("\nTX|GPIO1\n"));
+    }
+
+    #[test]
+    fn test_shape_rendering() {
+        let mut renderer = SvgRenderer::new();
+        renderer.set_shape_rendering("crispEdges").unwrap();
+        renderer.process_commands(&[Command::Draw]).unwrap();
+
+        // Set on the root, so every rect and line inherits it
+        let svg = renderer.to_string();
+        let root = svg.lines().find(|line| line.starts_with("<svg")).unwrap();
+        assert!(root.contains(r#"shape-rendering="crispEdges""#), "{}", root);
+
+        renderer.set_shape_rendering("auto").unwrap();
+        assert!(!renderer.to_string().contains("shape-rendering"));
+
+        assert!(renderer.set_shape_rendering("jagged").is_err());
+    }
+
+    #[test]
+    fn test_shell_encloses_pin_set() {
+        let pin = |name: &str| Command::Pin {
+            wire: Some(WireType::Digital),
+            pin_type: Some(PinType::IO),
+            group: None,
+            attributes: vec![name.to_string()],
+            leader: None,
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                Command::BoxTheme {
+                    name: "SHELL".to_string(),
+                    border_color: "black".to_string(),
+                    border_opacity: 1.0,
+                    fill_color: "grey".to_string(),
+                    fill_opacity: 1.0,
+                    line_width: 2.0,
+                    box_width: 0.0,
+                    box_height: 0.0,
+                    box_cr_x: 8.0,
+                    box_cr_y: 8.0,
+                    box_skew: 0.0,
+                    box_skew_offset: 0.0,
+                    shape: None,
+                },
+                Command::Draw,
+                Command::Anchor { x: 500.0, y: 100.0 },
+                pin_set(Side::Left, 20.0),
+                pin("D1"),
+                pin("D2"),
+                pin("D3"),
+                Command::Shell {
+                    theme: "SHELL".to_string(),
+                    padding: Some(5.0),
+                },
+            ])
+            .unwrap();
+
+        // The shell is drawn first after the definitions, behind the pin set
+        let children = &renderer.document.get_children()[1..];
+        let shell = node_extent(children[0].as_ref()).unwrap();
+        assert!(children[0].to_string().contains(r#"fill="grey""#));
+
+        let pins = children[1..]
+            .iter()
+            .filter_map(|node| node_extent(node.as_ref()))
+            .reduce(|a, b| a.union(&b))
+            .unwrap();
+        assert_eq!(shell.x, pins.x - 5.0);
+        assert_eq!(shell.y, pins.y - 5.0);
+        assert_eq!(shell.x + shell.width, pins.x + pins.width + 5.0);
+        assert_eq!(shell.y + shell.height, pins.y + pins.height + 5.0);
+    }
+
+    #[test]
+    fn test_pin_text_color_override() {
+        let draw = |color: Option<&str>| {
+            render(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                Command::FontColor {
+                    default: "black".to_string(),
+                    pin_type: None,
+                    group: None,
+                    colors: Vec::new(),
+                },
+                Command::Draw,
+                pin_set(Side::Left, 20.0),
+                Command::PinText {
+                    wire: None,
+                    pin_type: None,
+                    pin_group: None,
+                    msg_theme: "DEFAULT".to_string(),
+                    label: None,
+                    message: "Reserved".to_string(),
+                    color: color.map(|c| c.to_string()),
+                },
+            ])
+        };
+
+        let svg = draw(Some("red"));
+        assert!(svg.contains(r#"fill="red""#), "{}", svg);
+
+        let svg = draw(None);
+        assert!(svg.contains(r#"fill="black""#), "{}", svg);
+    }
+
+    #[cfg(feature = "outline-text")]
+    #[test]
+    fn test_text_outlines() {
+        let commands = [
+            Command::Draw,
+            Command::Box {
+                theme: "PLAIN".to_string(),
+                x: 10.0,
+                y: 10.0,
+                box_width: Some(100.0),
+                box_height: Some(50.0),
+                x_justify: None,
+                y_justify: None,
+                message: Some("Pin A & B".to_string()),
+                text_anchor: None,
+            },
+            Command::Message {
+                x: Some(10.0),
+                y: Some(100.0),
+                line_step: None,
+                font: None,
+                font_size: None,
+                x_justify: None,
+                y_justify: None,
+                tab_stops: None,
+                max_height: None,
+            },
+            Command::Text {
+                edge_color: String::new(),
+                color: "red".to_string(),
+                message: "Hello".to_string(),
+                new_line: true,
+            },
+            Command::Text {
+                edge_color: String::new(),
+                color: String::new(),
+                message: "World".to_string(),
+                new_line: false,
+            },
+            Command::EndMessage,
+        ];
+
+        // Editable text unless an outline font is set
+        assert!(render(&commands).contains("<text"));
+
+        assert!(
+            SvgRenderer::new()
+                .set_text_outline_font(b"not a font".to_vec())
+                .is_err()
+        );
+
+        // Every printable character of the test font is a plain box
+        let font = include_bytes!("../../tests/fixtures/boxes.ttf");
+        let mut renderer = SvgRenderer::new();
+        renderer.set_text_outline_font(font.to_vec()).unwrap();
+        renderer.process_commands(&commands).unwrap();
+        let svg = renderer.to_string();
+
+        assert!(!svg.contains("<text"), "{}", svg);
+        assert!(!svg.contains("<tspan"), "{}", svg);
+        assert_eq!(svg.matches("<path").count(), 3, "{}", svg);
+        assert!(svg.contains(r#"<path d="M"#), "{}", svg);
+        // One closed box for each character that is not a space
+        assert_eq!(
+            svg.matches('Z').count(),
+            "PinA&BHelloWorld".len(),
+            "{}",
+            svg
+        );
+    }
+
+    #[test]
+    fn test_message_tab_stops() {
+        let text = |message: &str, new_line: bool| Command::Text {
+            edge_color: String::new(),
+            color: "black".to_string(),
+            message: message.to_string(),
+            new_line,
+        };
+
+        let svg = render(&[
+            Command::Draw,
+            Command::Message {
+                x: Some(10.0),
+                y: Some(100.0),
+                line_step: None,
+                font: None,
+                font_size: None,
+                x_justify: Some(JustifyX::Left),
+                y_justify: None,
+                tab_stops: Some(vec![50.0, 120.0]),
+                max_height: None,
+            },
+            text("Name:\tVCC", true),
+            text(r"Volts:\t3.3\tmax", false),
+            Command::EndMessage,
+        ]);
+
+        assert!(
+            svg.contains(r#"<tspan fill="black" stroke="none" x="60">VCC</tspan>"#),
+            "{}",
+            svg
+        );
+
+        // Each line starts again from the first stop
+        assert!(
+            svg.contains(r#"<tspan fill="black" stroke="none" x="60">3.3</tspan>"#),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(r#"<tspan fill="black" stroke="none" x="130">max</tspan>"#),
+            "{}",
+            svg
+        );
+
+        // A later message without stops does not inherit them
+        let svg = render(&[
+            Command::Draw,
+            Command::Message {
+                x: Some(10.0),
+                y: Some(100.0),
+                line_step: None,
+                font: None,
+                font_size: None,
+                x_justify: Some(JustifyX::Left),
+                y_justify: None,
+                tab_stops: Some(vec![50.0]),
+                max_height: None,
+            },
+            text("Name:\tVCC", true),
+            Command::Message {
+                x: Some(10.0),
+                y: Some(200.0),
+                line_step: None,
+                font: None,
+                font_size: None,
+                x_justify: Some(JustifyX::Left),
+                y_justify: None,
+                tab_stops: None,
+                max_height: None,
+            },
+            text("Name:\tGND", true),
+            Command::EndMessage,
+        ]);
+
+        assert!(
+            svg.contains(r#"<tspan fill="black" stroke="none">GND</tspan>"#),
+            "{}",
+            svg
+        );
+    }
+
+    #[test]
+    fn test_message_max_height() {
+        let line = |message: &str| Command::Text {
+            edge_color: String::new(),
+            color: "black".to_string(),
+            message: message.to_string(),
+            new_line: true,
+        };
+        let draw = |max_height| {
+            render(&[
+                Command::Draw,
+                Command::Message {
+                    x: Some(10.0),
+                    y: Some(100.0),
+                    line_step: Some(20.0),
+                    font: None,
+                    font_size: Some(12.0),
+                    x_justify: Some(JustifyX::Left),
+                    y_justify: None,
+                    tab_stops: None,
+                    max_height,
+                },
+                line("One"),
+                line("Two"),
+                line("Three"),
+                line("Four"),
+                Command::EndMessage,
+            ])
+        };
+
+        // Four lines fit in 80
+        let svg = draw(Some(80.0));
+        assert!(svg.contains(r#"font-size="12""#), "{}", svg);
+        assert!(svg.contains(r#"x="10" y="120">Two"#), "{}", svg);
+
+        // Squeezed into 40 the line step halves along with the font size
+        let svg = draw(Some(40.0));
+        assert!(svg.contains(r#"font-size="6""#), "{}", svg);
+        assert!(svg.contains(r#"x="10" y="110">Two"#), "{}", svg);
+        assert!(svg.contains(r#"x="10" y="130">Four"#), "{}", svg);
+
+        // The limit carries over to later messages until a max height of 0 lifts it
+        let svg = render(&[
+            Command::Draw,
+            Command::Message {
+                x: Some(10.0),
+                y: Some(100.0),
+                line_step: Some(20.0),
+                font: None,
+                font_size: Some(12.0),
+                x_justify: Some(JustifyX::Left),
+                y_justify: None,
+                tab_stops: None,
+                max_height: Some(20.0),
+            },
+            line("One"),
+            line("Two"),
+            Command::Message {
+                x: Some(10.0),
+                y: Some(200.0),
+                line_step: None,
+                font: None,
+                font_size: None,
+                x_justify: None,
+                y_justify: None,
+                tab_stops: None,
+                max_height: None,
+            },
+            line("Three"),
+            line("Four"),
+            Command::Message {
+                x: Some(10.0),
+                y: Some(300.0),
+                line_step: None,
+                font: None,
+                font_size: None,
+                x_justify: None,
+                y_justify: None,
+                tab_stops: None,
+                max_height: Some(0.0),
+            },
+            line("Five"),
+            line("Six"),
+            Command::EndMessage,
+        ]);
+        assert!(svg.contains(r#"x="10" y="210">Four"#), "{}", svg);
+        assert!(svg.contains(r#"x="10" y="320">Six"#), "{}", svg);
+    }
+
+    #[test]
+    fn test_watermark() {
+        let svg = render(&[
+            Command::Draw,
+            Command::Box {
+                theme: "PLAIN".to_string(),
+                x: 10.0,
+                y: 10.0,
+                box_width: Some(100.0),
+                box_height: Some(50.0),
+                x_justify: None,
+                y_justify: None,
+                message: None,
+                text_anchor: None,
+            },
+            Command::Watermark {
+                text: "DRAFT".to_string(),
+                opacity: 0.1,
+                angle: Some(-45.0),
+            },
+        ]);
+
+        // A4 landscape at 300 DPI is 3507 x 2480 pixels
+        let watermark = svg
+            .split('\n')
+            .skip_while(|line| !line.starts_with("<defs"))
+            .nth(1)
+            .unwrap();
+        assert!(watermark.starts_with("<text"), "{}", svg);
+        assert!(watermark.contains(r#"opacity="0.1""#), "{}", watermark);
+        assert!(
+            watermark.contains(r#"transform="rotate(-45 1753.5 1240)""#),
+            "{}",
+            watermark
+        );
+        assert!(
+            watermark.contains(r#"x="1753.5" y="1240""#),
+            "{}",
+            watermark
+        );
+    }
+
+    #[test]
+    fn test_save_to_svgz() {
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[Command::Draw, Command::SheetInfo { x: 10.0, y: 10.0 }])
+            .unwrap();
+
+        let path = std::env::temp_dir().join("pinout_test.svgz");
+        renderer.save_to_svgz(path.to_str().unwrap()).unwrap();
+
+        let mut svg = String::new();
+        flate2::read::GzDecoder::new(File::open(&path).unwrap())
+            .read_to_string(&mut svg)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(svg, renderer.to_string());
+    }
+
+    #[test]
+    fn test_issue_summary() {
+        let group = || Command::Group {
+            name: "X".to_string(),
+            color: "red".to_string(),
+            opacity: 1.0,
+            shape: None,
+        };
+        let draw_box = |x| Command::Box {
+            theme: "DEFAULT".to_string(),
+            x,
+            y: 10.0,
+            box_width: Some(100.0),
+            box_height: Some(50.0),
+            x_justify: None,
+            y_justify: None,
+            message: None,
+            text_anchor: None,
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer.set_overlap_warnings(true);
+        renderer
+            .process_commands(&[group(), Command::Draw, draw_box(10.0), draw_box(200.0)])
+            .unwrap();
+        assert!(!renderer.has_issues());
+        assert_eq!(renderer.issue_summary(), IssueSummary::default());
+
+        let mut renderer = SvgRenderer::new();
+        renderer.set_overlap_warnings(true);
+        renderer
+            .process_commands(&[
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string()],
+                },
+                group(),
+                group(),
+                Command::Draw,
+                draw_box(10.0),
+                draw_box(80.0),
+                draw_box(150.0),
+            ])
+            .unwrap();
+        assert!(renderer.has_issues());
+        assert_eq!(
+            renderer.issue_summary(),
+            IssueSummary {
+                errors: 1,
+                warnings: 1,
+                overflows: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_percentage_column_gap() {
+        let draw = |gap: f32, percent: bool| {
+            let mut set = pin_set(Side::Right, 20.0);
+            if let Command::PinSet {
+                column_gap,
+                column_gap_percent,
+                ..
+            } = &mut set
+            {
+                *column_gap = gap;
+                *column_gap_percent = percent;
+            }
+
+            let mut renderer = SvgRenderer::new();
+            renderer
+                .process_commands(&[
+                    Command::Labels {
+                        default: "DEFAULT".to_string(),
+                        pin_type: None,
+                        group: None,
+                        labels: vec!["Name".to_string(), "Alt".to_string(), "Func".to_string()],
+                    },
+                    Command::BoxTheme {
+                        name: "SKEWED".to_string(),
+                        border_color: "black".to_string(),
+                        border_opacity: 1.0,
+                        fill_color: "white".to_string(),
+                        fill_opacity: 1.0,
+                        line_width: 1.0,
+                        box_width: 100.0,
+                        box_height: 20.0,
+                        box_cr_x: 0.0,
+                        box_cr_y: 0.0,
+                        box_skew: 0.0,
+                        box_skew_offset: 0.0,
+                        shape: None,
+                    },
+                    Command::Draw,
+                    set,
+                    Command::Pin {
+                        wire: None,
+                        pin_type: None,
+                        group: None,
+                        attributes: vec!["D1".to_string(), "TX".to_string(), "SDA".to_string()],
+                        leader: None,
+                    },
+                ])
+                .unwrap();
+            renderer.box_extents().to_vec()
+        };
+
+        // "50%" as parsed by the CSV reader, half the 100 wide boxes
+        let extents = draw(50.0, true);
+        assert_eq!(extents.len(), 3);
+        for pair in extents.windows(2) {
+            assert!(
+                (pair[1].x - pair[0].x - 150.0).abs() < 1e-3,
+                "{:?}",
+                extents
+            );
+        }
+
+        // A gap below 1 without % is still a distance
+        let extents = draw(0.5, false);
+        for pair in extents.windows(2) {
+            assert!(
+                (pair[1].x - pair[0].x - 100.5).abs() < 1e-3,
+                "{:?}",
+                extents
+            );
+        }
+    }
+
+    #[test]
+    fn test_high_contrast() {
+        let colors = |names: &[&str]| names.iter().map(|name| name.to_string()).collect();
+        let draw = |enabled: bool| {
+            let mut renderer = SvgRenderer::new();
+            renderer.set_high_contrast(enabled);
+            renderer
+                .process_commands(&[
+                    Command::Labels {
+                        default: "DEFAULT".to_string(),
+                        pin_type: None,
+                        group: None,
+                        labels: vec!["Name".to_string()],
+                    },
+                    Command::BorderColor {
+                        default: "teal".to_string(),
+                        pin_type: None,
+                        group: None,
+                        colors: colors(&["teal"]),
+                    },
+                    Command::FillColor {
+                        default: "navy".to_string(),
+                        pin_type: None,
+                        group: None,
+                        colors: colors(&["navy"]),
+                    },
+                    Command::FontColor {
+                        default: "yellow".to_string(),
+                        pin_type: None,
+                        group: None,
+                        colors: colors(&["yellow"]),
+                    },
+                    Command::Wire {
+                        wire_type: WireType::Digital,
+                        color: "orange".to_string(),
+                        opacity: 1.0,
+                        thickness: 2.0,
+                        amplitude: None,
+                        terminal: None,
+                        start_gap: None,
+                        stroke_opacity: None,
+                        dash: None,
+                    },
+                    Command::Draw,
+                    pin_set(Side::Right, 20.0),
+                    Command::Pin {
+                        wire: Some(WireType::Digital),
+                        pin_type: Some(PinType::IO),
+                        group: None,
+                        attributes: vec!["D1".to_string()],
+                        leader: None,
+                    },
+                ])
+                .unwrap();
+            renderer.to_string()
+        };
+
+        let themed = draw(false);
+        for color in ["teal", "navy", "yellow", "orange"] {
+            assert!(themed.contains(color), "{} missing from {}", color, themed);
+        }
+
+        let svg = draw(true);
+        for color in ["teal", "navy", "yellow", "orange"] {
+            assert!(!svg.contains(color), "{} left in {}", color, svg);
+        }
+        assert!(svg.contains(r#"fill="white""#), "{}", svg);
+        assert!(svg.contains(r#"fill="black""#), "{}", svg);
+        assert!(svg.contains(r#"stroke="black""#), "{}", svg);
+    }
+
+    #[test]
+    fn test_auto_orient() {
+        let orient = |page: &str, width: f32, height: f32| {
+            let mut renderer = SvgRenderer::new();
+            renderer
+                .process_commands(&[
+                    Command::Page {
+                        page_name: page.to_string(),
+                        size: None,
+                    },
+                    Command::AutoOrient,
+                    Command::Draw,
+                    Command::Box {
+                        theme: "PLAIN".to_string(),
+                        x: 100.0,
+                        y: 100.0,
+                        box_width: Some(width),
+                        box_height: Some(height),
+                        x_justify: None,
+                        y_justify: None,
+                        message: None,
+                        text_anchor: None,
+                    },
+                ])
+                .unwrap();
+            renderer.page_size().1
+        };
+
+        assert_eq!(orient("A4-P", 2000.0, 300.0), (297.0, 210.0));
+        assert_eq!(orient("A4-L", 300.0, 2000.0), (210.0, 297.0));
+        assert_eq!(orient("A3-P", 3000.0, 2000.0), (420.0, 297.0));
+    }
+
+    #[test]
+    fn test_pin_data_attributes() {
+        let svg = render(&[
+            Command::Labels {
+                default: "DEFAULT".to_string(),
+                pin_type: None,
+                group: None,
+                labels: vec!["Name".to_string()],
+            },
+            Command::Group {
+                name: "PORTA".to_string(),
+                color: "red".to_string(),
+                opacity: 1.0,
+                shape: None,
+            },
+            wire(WireType::Pwm),
+            Command::Draw,
+            pin_set(Side::Right, 20.0),
+            Command::Pin {
+                wire: Some(WireType::Pwm),
+                pin_type: Some(PinType::Output),
+                group: Some("PORTA".to_string()),
+                attributes: vec!["D5".to_string()],
+                leader: None,
+            },
+        ]);
+
+        // The group indicator, type indicator, leader and name box all carry the pin's data
+        let tagged: Vec<&str> = svg
+            .lines()
+            .filter(|line| line.contains(r#"data-group="PORTA""#))
+            .collect();
+        assert_eq!(tagged.len(), 4, "{}", svg);
+        for line in tagged {
+            assert!(line.contains(r#"data-wire="PWM""#), "{}", line);
+            assert!(line.contains(r#"data-type="OUTPUT""#), "{}", line);
+            assert!(line.contains(r#"data-pin="D5""#), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_render_options() {
+        let commands = [
+            wire(WireType::Digital),
+            Command::Draw,
+            Command::Anchor { x: 100.0, y: 100.0 },
+            pin_set(Side::Left, 20.0),
+            pin(Some(WireType::Digital), Some(PinType::IO), None),
+        ];
+        let draw = |options: &RenderOptions| {
+            let mut renderer = SvgRenderer::new_with_options(options).unwrap();
+            renderer.process_commands(&commands).unwrap();
+            renderer.to_string()
+        };
+
+        let plain = render(&commands);
+        assert_eq!(draw(&RenderOptions::default()), plain);
+
+        // Debug labels add their labels and nothing else
+        let labelled = draw(&RenderOptions {
+            debug_labels: true,
+            ..RenderOptions::default()
+        });
+        assert_ne!(labelled, plain);
+        let start = labelled.find("<text class=\"debug-label\"").unwrap();
+        let end = start + labelled[start..].find("</text>\n").unwrap() + "</text>\n".len();
+        assert_eq!(format!("{}{}", &labelled[..start], &labelled[end..]), plain);
+
+        assert!(
+            SvgRenderer::new_with_options(&RenderOptions {
+                device_pixel_ratio: 0.0,
+                ..RenderOptions::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_note() {
+        let svg = render(&[
+            Command::Draw,
+            Command::Anchor { x: 100.0, y: 50.0 },
+            Command::Note {
+                text: "check boot pins".to_string(),
+            },
+        ]);
+
+        assert!(
+            svg.contains(r#"<text class="note" fill="grey" font-family="sans-serif" font-size="10" font-style="italic" x="100" y="50">"#),
+            "{}",
+            svg
+        );
+        assert!(svg.contains("\ncheck boot pins\n</text>"), "{}", svg);
+    }
+
+    #[test]
+    fn test_embedded_bytes() {
+        let icons = [
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10"/>"#,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="24" height="24"><circle r="5"/></svg>"#,
+        ];
+        let icon = |index: usize| {
+            let path = std::env::temp_dir().join(format!("pinout_embedded_{}.svg", index));
+            std::fs::write(&path, icons[index]).unwrap();
+            path
+        };
+        let paths = [icon(0), icon(1)];
+
+        let mut renderer = SvgRenderer::new();
+        assert_eq!(renderer.embedded_bytes(), 0);
+        let result = renderer.process_commands(
+            &std::iter::once(Command::Draw)
+                .chain(paths.iter().map(|path| Command::Icon {
+                    name: path.to_str().unwrap().to_string(),
+                    x: Some(10.0),
+                    y: Some(10.0),
+                    w: None,
+                    h: None,
+                    rot: None,
+                }))
+                .collect::<Vec<_>>(),
+        );
+        for path in &paths {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        result.unwrap();
+        assert_eq!(renderer.embedded_bytes(), icons[0].len() + icons[1].len());
+    }
+
+    #[test]
+    fn test_pin_leader_length() {
+        let analog = |leader| Command::Pin {
+            wire: Some(WireType::Analog),
+            pin_type: None,
+            group: None,
+            attributes: Vec::new(),
+            leader,
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                wire(WireType::Analog),
+                Command::Draw,
+                Command::Anchor { x: 100.0, y: 100.0 },
+                pin_set(Side::Right, 40.0),
+                analog(None),
+                analog(Some(80.0)),
+            ])
+            .unwrap();
+
+        // Each waveform runs from x = 0 to its own leader length
+        let widths: Vec<f32> = renderer
+            .to_string()
+            .lines()
+            .filter(|line| line.starts_with("<polyline"))
+            .map(|line| {
+                let points = line.split(r#"points=""#).nth(1).unwrap();
+                points[..points.find('"').unwrap()]
+                    .split(' ')
+                    .filter_map(|point| point.split(',').next()?.parse::<f32>().ok())
+                    .fold(0.0, f32::max)
+            })
+            .collect();
+        assert_eq!(widths.len(), 2);
+        assert!((widths[0] - 40.0).abs() < 0.5, "{:?}", widths);
+        assert!((widths[1] - 80.0).abs() < 0.5, "{:?}", widths);
+
+        // Only the leader changes, the row's boxes stay in the pin set's column
+        let first_box_x = |leader| {
+            let mut renderer = SvgRenderer::new();
+            renderer
+                .process_commands(&[
+                    Command::Labels {
+                        default: "DEFAULT".to_string(),
+                        pin_type: None,
+                        group: None,
+                        labels: vec!["Name".to_string()],
+                    },
+                    wire(WireType::Analog),
+                    Command::Draw,
+                    Command::Anchor { x: 100.0, y: 100.0 },
+                    pin_set(Side::Right, 40.0),
+                    Command::Pin {
+                        wire: Some(WireType::Analog),
+                        pin_type: None,
+                        group: None,
+                        attributes: vec!["D1".to_string()],
+                        leader,
+                    },
+                ])
+                .unwrap();
+            renderer.box_extents()[0].x
+        };
+        assert_eq!(first_box_x(Some(80.0)), first_box_x(None));
+        assert_eq!(first_box_x(Some(10.0)), first_box_x(None));
+    }
+
+    #[test]
+    fn test_pin_set_box_gap() {
+        let first_box_x = |side: Side, gap: Option<f32>| {
+            let mut set = pin_set(side, 20.0);
+            if let Command::PinSet { box_gap, .. } = &mut set {
+                *box_gap = gap;
+            }
+
+            let mut renderer = SvgRenderer::new();
+            renderer
+                .process_commands(&[
+                    Command::Labels {
+                        default: "DEFAULT".to_string(),
+                        pin_type: None,
+                        group: None,
+                        labels: vec!["Name".to_string()],
+                    },
+                    Command::Draw,
+                    Command::Anchor { x: 200.0, y: 100.0 },
+                    set,
+                    Command::Pin {
+                        wire: None,
+                        pin_type: None,
+                        group: None,
+                        attributes: vec!["D1".to_string()],
+                        leader: None,
+                    },
+                ])
+                .unwrap();
+            renderer.box_extents()[0].x
+        };
+
+        assert_eq!(
+            first_box_x(Side::Right, Some(15.0)),
+            first_box_x(Side::Right, None) + 15.0
+        );
+        assert_eq!(
+            first_box_x(Side::Left, Some(15.0)),
+            first_box_x(Side::Left, None) - 15.0
+        );
+    }
+
+    #[test]
+    fn test_image_anchor() {
+        let path = std::env::temp_dir().join("pinout_image_anchor.png");
+        image::RgbImage::new(20, 10).save(&path).unwrap();
+
+        let draw = |anchor: Option<(JustifyX, JustifyY)>| {
+            render(&[
+                Command::Draw,
+                Command::Image {
+                    name: path.to_str().unwrap().to_string(),
+                    x: Some(100.0),
+                    y: Some(50.0),
+                    w: None,
+                    h: None,
+                    cx: None,
+                    cy: None,
+                    cw: None,
+                    ch: None,
+                    rot: None,
+                    pivot_x: None,
+                    pivot_y: None,
+                    anchor,
+                },
+            ])
+        };
+        let centered = draw(None);
+        let top_left = draw(Some((JustifyX::Left, JustifyY::Top)));
+        let bottom_right = draw(Some((JustifyX::Right, JustifyY::Bottom)));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(centered.contains(r#"x="90" y="45""#), "{}", centered);
+        assert!(top_left.contains(r#"x="100" y="50""#), "{}", top_left);
+        assert!(
+            bottom_right.contains(r#"x="80" y="40""#),
+            "{}",
+            bottom_right
+        );
+    }
+
+    #[test]
+    fn test_text_halo() {
+        let draw = |halo: bool| {
+            let mut renderer = SvgRenderer::new();
+            if halo {
+                renderer.set_text_halo("white", 3.0);
+            }
+            renderer
+                .process_commands(&[
+                    Command::Draw,
+                    Command::Box {
+                        theme: "STD".to_string(),
+                        x: 10.0,
+                        y: 10.0,
+                        box_width: Some(50.0),
+                        box_height: Some(20.0),
+                        x_justify: None,
+                        y_justify: None,
+                        message: Some("Halo".to_string()),
+                        text_anchor: None,
+                    },
+                ])
+                .unwrap();
+            renderer.to_string()
+        };
+
+        let plain = draw(false);
+        assert!(!plain.contains(r#"class="halo""#), "{}", plain);
+        assert_eq!(plain.matches("Halo").count(), 1, "{}", plain);
+
+        // The stroked copy comes first so the real text is drawn over it
+        let svg = draw(true);
+        assert_eq!(svg.matches("Halo").count(), 2, "{}", svg);
+        let halo = svg.find(r#"class="halo""#).expect("halo text");
+        let halo_text = &svg[svg[..halo].rfind("<text").unwrap()..];
+        let halo_text = &halo_text[..halo_text.find("</text>").unwrap()];
+        assert!(halo_text.contains(r#"stroke="white""#), "{}", halo_text);
+        assert!(halo_text.contains(r#"stroke-width="3""#), "{}", halo_text);
+        assert!(
+            halo_text.contains(r#"stroke-linejoin="round""#),
+            "{}",
+            halo_text
+        );
+        assert_eq!(svg[halo..].matches("Halo").count(), 2, "{}", svg);
+    }
+
+    #[test]
+    fn test_pin_set_frame() {
+        let mut set = pin_set(Side::Left, 20.0);
+        if let Command::PinSet { frame, title, .. } = &mut set {
+            *frame = Some("teal".to_string());
+            *title = Some("J1 Header".to_string());
+        }
+        let svg = render(&[
+            Command::Labels {
+                default: "DEFAULT".to_string(),
+                pin_type: None,
+                group: None,
+                labels: vec!["Name".to_string()],
+            },
+            Command::Draw,
+            Command::Anchor { x: 200.0, y: 100.0 },
+            set,
+            Command::Pin {
+                wire: None,
+                pin_type: None,
+                group: None,
+                attributes: vec!["D1".to_string()],
+                leader: None,
+            },
+        ]);
+
+        // The frame goes behind the set, the title on top of everything
+        let frame = svg.find(r#"class="pinset-frame""#).expect("frame");
+        let title = svg.find(r#"class="pinset-title""#).expect("title");
+        assert!(frame < svg.find("<g data-pin").unwrap(), "{}", svg);
+        assert!(title > svg.rfind("</g>").unwrap(), "{}", svg);
+        assert!(svg[title..].contains("J1 Header"), "{}", svg);
+
+        let frame = &svg[frame..svg[frame..].find("/>").unwrap() + frame];
+        assert!(frame.contains(r#"stroke="teal""#), "{}", frame);
+        assert!(frame.contains(r#"fill="none""#), "{}", frame);
+        // From the box at x 140 to the anchor the pins start at, plus the padding each side
+        assert!(frame.contains(r#"x="130""#), "{}", frame);
+        assert!(frame.contains(r#"width="80""#), "{}", frame);
+
+        let plain = render(&[
+            Command::Draw,
+            Command::Anchor { x: 200.0, y: 100.0 },
+            pin_set(Side::Left, 20.0),
+            pin(None, None, None),
+        ]);
+        assert!(!plain.contains("pinset-frame"), "{}", plain);
+    }
+
+    #[test]
+    fn test_unused_anchor_warning() {
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Draw,
+                Command::Anchor { x: 10.0, y: 20.0 },
+                Command::Anchor { x: 30.0, y: 40.0 },
+                pin_set(Side::Left, 20.0),
+                Command::Anchor { x: 50.0, y: 60.0 },
+            ])
+            .unwrap();
+
+        // Only the anchor replaced straight away is reported, not the last one or one in use
+        assert_eq!(
+            renderer.warnings(),
+            &[RenderWarning::UnusedAnchor { x: 10.0, y: 20.0 }]
+        );
+    }
+
+    #[test]
+    fn test_scale_bar() {
+        let svg = render(&[
+            Command::Dpi { dpi: 300 },
+            Command::Draw,
+            Command::ScaleBar {
+                x: 10.0,
+                y: 50.0,
+                length_mm: 10.0,
+            },
+        ]);
+
+        // 10mm at 300 DPI is 118.11 pixels
+        let bar = &svg[svg.find(r#"class="scalebar""#).expect("scale bar")..];
+        let points = bar.split("points=\"").nth(1).unwrap();
+        let (start, end) = points[..points.find('"').unwrap()].split_once(' ').unwrap();
+        let x = |point: &str| point.split(',').next().unwrap().parse::<f32>().unwrap();
+        assert_eq!(start, "10,50");
+        assert!((x(end) - x(start) - 118.11).abs() < 0.01, "{}", bar);
+
+        assert_eq!(bar.matches("<polyline").count(), 3, "{}", bar);
+        assert!(bar.contains("10mm"), "{}", bar);
+    }
+
+    #[test]
+    fn test_google_font_import() {
+        let link = "https://fonts.googleapis.com/css2?family=Missing";
+        let mut renderer = SvgRenderer::new();
+        renderer.set_font_fetcher(MockFetcher);
+        renderer
+            .process_commands(&[
+                Command::Draw,
+                Command::GoogleFont {
+                    link: link.to_string(),
+                },
+                Command::GoogleFont {
+                    link: link.to_string(),
+                },
+                Command::SheetInfo { x: 10.0, y: 10.0 },
+            ])
+            .unwrap();
+        let svg = renderer.to_string();
+
+        // Imported once, ahead of the text that uses it
+        let import = format!("@import url('{}');", link);
+        assert_eq!(svg.matches(&import).count(), 1, "{}", svg);
+        assert!(
+            svg.find(&import).unwrap() < svg.find("<text").unwrap(),
+            "{}",
+            svg
+        );
+
+        // A link that could break out of the import, or leave Google Fonts, is refused
+        for link in [
+            "https://fonts.googleapis.com/css2?family=A');} text { display: none",
+            "file:///etc/passwd",
+        ] {
+            let mut renderer = SvgRenderer::new();
+            let result = renderer.process_commands(&[
+                Command::Draw,
+                Command::GoogleFont {
+                    link: link.to_string(),
+                },
+            ]);
+            assert!(matches!(result, Err(RenderError::SvgError(_))), "{}", link);
+        }
+    }
+
+    #[test]
+    fn test_process_commands_twice() {
+        let mut renderer = SvgRenderer::new();
+        renderer.set_font_fetcher(MockFetcher);
+        let commands = [
+            Command::Draw,
+            Command::GoogleFont {
+                link: "https://fonts.googleapis.com/css2?family=Roboto".to_string(),
+            },
+            Command::SheetInfo { x: 10.0, y: 10.0 },
+        ];
+        renderer.process_commands(&commands).unwrap();
+        renderer.process_commands(&commands).unwrap();
+
+        let svg = renderer.to_string();
+        assert_eq!(svg.matches("<defs").count(), 1, "{}", svg);
+        assert_eq!(svg.matches("<style").count(), 1, "{}", svg);
+        assert_eq!(svg.matches("Sheet 1 of 1").count(), 2, "{}", svg);
+
+        // Rotation and layers are applied once to everything drawn by both calls
+        let commands = [
+            Command::Draw,
+            Command::SheetInfo { x: 10.0, y: 10.0 },
+            Command::Box {
+                theme: "PLAIN".to_string(),
+                x: 10.0,
+                y: 40.0,
+                box_width: Some(40.0),
+                box_height: Some(20.0),
+                x_justify: None,
+                y_justify: None,
+                message: None,
+                text_anchor: None,
+            },
+        ];
+        let mut renderer = SvgRenderer::new();
+        renderer.set_rotated(true);
+        renderer.set_inkscape_layers(true);
+        renderer.process_commands(&commands).unwrap();
+        renderer.process_commands(&commands).unwrap();
+
+        let svg = renderer.to_string();
+        assert_eq!(svg.matches("rotate(90)").count(), 1, "{}", svg);
+        assert_eq!(
+            svg.matches(r#"inkscape:label="BOX_DEFAULT""#).count(),
+            1,
+            "{}",
+            svg
+        );
+        let children = renderer.document.get_children();
+        assert_eq!(children.len(), 2, "{}", svg);
+        let content = children[1].get_children().unwrap();
+        assert_eq!(content.len(), 3, "{}", svg);
+        assert_eq!(content[2].get_children().unwrap().len(), 2, "{}", svg);
+    }
+
+    #[test]
+    fn test_pin_symbols() {
+        let mut commands = vec![
+            wire(WireType::Analog),
+            Command::Draw,
+            Command::Anchor { x: 200.0, y: 100.0 },
+            pin_set(Side::Left, 40.0),
+        ];
+        commands.extend((0..10).map(|_| pin(Some(WireType::Analog), None, None)));
+
+        let mut renderer = SvgRenderer::new();
+        renderer.set_pin_symbols(true);
+        renderer.process_commands(&commands).unwrap();
+        let svg = renderer.to_string();
+
+        // One sine wave in the definitions, placed ten times
+        assert_eq!(svg.matches("<symbol").count(), 1, "{}", svg);
+        assert_eq!(svg.matches("<polyline").count(), 1, "{}", svg);
+        assert_eq!(svg.matches("<use").count(), 10, "{}", svg);
+        assert_eq!(svg.matches(r##"href="#pin-symbol-0""##).count(), 10);
+
+        assert_eq!(render(&commands).matches("<polyline").count(), 10);
+    }
+
+    #[test]
+    fn test_text_escaped_once() {
+        let text = "A & B < C";
+        let svg = render(&[
+            Command::Draw,
+            Command::Message {
+                x: Some(10.0),
+                y: Some(10.0),
+                line_step: Some(12.0),
+                font: None,
+                font_size: Some(10.0),
+                x_justify: None,
+                y_justify: None,
+                tab_stops: None,
+                max_height: None,
+            },
+            Command::Text {
+                edge_color: "none".to_string(),
+                color: "black".to_string(),
+                message: text.to_string(),
+                new_line: false,
+            },
+            Command::EndMessage,
+            Command::Box {
+                theme: "STD".to_string(),
+                x: 10.0,
+                y: 50.0,
+                box_width: Some(80.0),
+                box_height: Some(20.0),
+                x_justify: None,
+                y_justify: None,
+                message: Some(text.to_string()),
+                text_anchor: None,
+            },
+        ]);
+
+        // Text nodes escape their content when written, so it must not be escaped beforehand
+        assert_eq!(svg.matches("A &amp; B &lt; C").count(), 2, "{}", svg);
+        assert!(!svg.contains("&amp;amp;"), "{}", svg);
+        assert!(!svg.contains("A & B"), "{}", svg);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_save_png() {
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Draw,
+                Command::Box {
+                    theme: "STD".to_string(),
+                    x: 10.0,
+                    y: 10.0,
+                    box_width: Some(50.0),
+                    box_height: Some(20.0),
+                    x_justify: None,
+                    y_justify: None,
+                    message: None,
+                    text_anchor: None,
+                },
+            ])
+            .unwrap();
+
+        // A4 landscape at 300 DPI is 3507 x 2480 pixels
+        let path = std::env::temp_dir().join("pinout_test.png");
+        renderer.save_png(path.to_str().unwrap(), 0.1).unwrap();
+        assert_eq!(image::image_dimensions(&path).unwrap(), (351, 248));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            renderer.save_png(path.to_str().unwrap(), 0.0),
+            Err(RenderError::RasterError(_))
+        ));
+    }
+
+    #[test]
+    fn test_line_break_token() {
+        let draw = |token: Option<&str>, message: &str| {
+            let mut renderer = SvgRenderer::new();
+            if let Some(token) = token {
+                renderer.set_line_break(token).unwrap();
+            }
+            renderer
+                .process_commands(&[
+                    Command::Draw,
+                    Command::Box {
+                        theme: "STD".to_string(),
+                        x: 10.0,
+                        y: 10.0,
+                        box_width: Some(80.0),
+                        box_height: Some(40.0),
+                        x_justify: None,
+                        y_justify: None,
+                        message: Some(message.to_string()),
+                        text_anchor: None,
+                    },
+                ])
+                .unwrap();
+            renderer.to_string()
+        };
+
+        let svg = draw(None, "VCC\\nInput");
+        assert!(svg.contains("\nVCC\n</text>"), "{}", svg);
+        assert!(svg.contains("\nInput\n</text>"), "{}", svg);
+
+        // With a custom token a backslash n stays part of the text
+        let svg = draw(Some("|"), "C:\\new|Input");
+        assert!(svg.contains("\nC:\\new\n</text>"), "{}", svg);
+        assert!(svg.contains("\nInput\n</text>"), "{}", svg);
+
+        // A line break kept from a quoted CSV field breaks the text too
+        let svg = draw(None, "VCC\nInput");
+        assert!(svg.contains("\nVCC\n</text>"), "{}", svg);
+        assert!(svg.contains("\nInput\n</text>"), "{}", svg);
+
+        assert!(SvgRenderer::new().set_line_break("").is_err());
+    }
+
+    #[test]
+    fn test_missing_box_theme() {
+        let draw_box = Command::Box {
+            theme: "MISSING".to_string(),
+            x: 10.0,
+            y: 10.0,
+            box_width: None,
+            box_height: None,
+            x_justify: None,
+            y_justify: None,
+            message: Some("Pin".to_string()),
+            text_anchor: None,
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::BoxTheme {
+                    name: "DEFAULT".to_string(),
+                    border_color: "black".to_string(),
+                    border_opacity: 1.0,
+                    fill_color: "white".to_string(),
+                    fill_opacity: 1.0,
+                    line_width: 1.0,
+                    box_width: 100.0,
+                    box_height: 40.0,
+                    box_cr_x: 0.0,
+                    box_cr_y: 0.0,
+                    box_skew: 0.0,
+                    box_skew_offset: 0.0,
+                    shape: None,
+                },
+                Command::Draw,
+                draw_box.clone(),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            renderer.warnings(),
+            &[RenderWarning::MissingBoxTheme {
+                theme: "MISSING".to_string(),
+            }]
+        );
+        assert_eq!(
+            renderer.box_extents(),
+            &[Extent::new(10.0, 10.0, 100.0, 40.0)]
+        );
+        let svg = renderer.to_string();
+        assert!(svg.contains(r#"fill="white""#), "{}", svg);
+        assert!(!svg.contains(r#"fill="blue""#), "{}", svg);
+
+        // Without a BOX_DEFAULT the built-in colors are used
+        let svg = render(&[Command::Draw, draw_box]);
+        assert!(svg.contains(r#"fill="blue""#), "{}", svg);
+    }
+
+    #[test]
+    fn test_custom_page() {
+        let page = |width, height| Command::Page {
+            page_name: "CUSTOM".to_string(),
+            size: Some((width, height)),
+        };
+
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[page(254.0, 127.0), Command::Draw])
+            .unwrap();
+        // 254 x 127 mm at 300 DPI is 3000 x 1500 pixels
+        assert_eq!(renderer.page_size(), ((3000, 1500), (254.0, 127.0)));
+        assert!(renderer.to_string().contains(r#"viewBox="0 0 3000 1500""#));
+
+        assert!(matches!(
+            SvgRenderer::new().process_commands(&[page(0.0, 127.0)]),
+            Err(RenderError::SvgError(_))
+        ));
+        assert!(matches!(
+            SvgRenderer::new().process_commands(&[Command::Page {
+                page_name: "CUSTOM".to_string(),
+                size: None,
+            }]),
+            Err(RenderError::SvgError(_))
+        ));
+    }
+
+    #[test]
+    fn test_page_presets() {
+        let mut renderer = SvgRenderer::new();
+        renderer
+            .process_commands(&[
+                Command::Dpi { dpi: 100 },
+                Command::Page {
+                    page_name: "LETTER-L".to_string(),
+                    size: None,
+                },
+                Command::Draw,
+            ])
+            .unwrap();
+        // 11 x 8.5 inches at 100 DPI
+        assert_eq!(renderer.page_size(), ((1100, 850), (279.4, 215.9)));
+
+        let page = |page_name: &str| {
+            let mut renderer = SvgRenderer::new();
+            renderer
+                .process_commands(&[Command::Page {
+                    page_name: page_name.to_string(),
+                    size: None,
+                }])
+                .unwrap();
+            renderer.page_size().1
+        };
+        assert_eq!(page("LEGAL-P"), (215.9, 355.6));
+        assert_eq!(page("A5-L"), (210.0, 148.0));
+    }
+
+    #[test]
+    fn test_radial_pin_set() {
+        let svg = render(&[
+            wire(WireType::Digital),
+            Command::Draw,
+            Command::Anchor { x: 500.0, y: 400.0 },
+            pin_set(Side::Radial, 40.0),
+            pin(Some(WireType::Digital), Some(PinType::IO), None),
+            pin(Some(WireType::Digital), Some(PinType::IO), None),
+            pin(Some(WireType::Digital), Some(PinType::IO), None),
+            pin(Some(WireType::Digital), Some(PinType::IO), None),
+        ]);
+
+        // Four pins a quarter turn apart, each leader drawn outward from its pin
+        for angle in [0, 90, 180, 270] {
+            let placed = format!(r#"transform="rotate({} 500 400) translate("#, angle);
+            assert_eq!(svg.matches(&placed).count(), 2, "{}", svg);
+        }
+        assert_eq!(svg.matches("<polyline").count(), 4, "{}", svg);
+        assert!(!svg.contains(RADIAL_ROW), "{}", svg);
+    }
+
+    #[test]
+    fn test_render_options_from_vars() {
+        let vars = |dpi: &'static str| {
+            move |name: &str| match name {
+                DPI_ENV => Some(dpi.to_string()),
+                PAGE_ENV => Some("A4-P".to_string()),
+                _ => None,
+            }
+        };
+
+        let options = RenderOptions::from_vars(vars("100")).unwrap();
+        assert_eq!(options.dpi, Some(100));
+        assert_eq!(options.page.as_deref(), Some("A4-P"));
+
+        let mut renderer = SvgRenderer::new_with_options(&options).unwrap();
+        renderer.process_commands(&[Command::Draw]).unwrap();
+        // A4 portrait at 100 DPI
+        assert_eq!(renderer.page_size(), ((826, 1169), (210.0, 297.0)));
+
+        // A DPI command in the file wins over the variable
+        let mut renderer = SvgRenderer::new_with_options(&options).unwrap();
+        renderer
+            .process_commands(&[Command::Dpi { dpi: 200 }, Command::Draw])
+            .unwrap();
+        assert_eq!(renderer.page_size(), ((1653, 2338), (210.0, 297.0)));
+
+        assert!(matches!(
+            RenderOptions::from_vars(vars("many")),
+            Err(RenderError::SvgError(_))
+        ));
+
+        let options = RenderOptions::from_vars(|_| None).unwrap();
+        assert_eq!((options.dpi, options.page), (None, None));
+    }
+
+    #[test]
+    fn test_leader_h_step() {
+        let render_side = |side| {
+            let mut set = pin_set(side, 40.0);
+            if let Command::PinSet { leader_h_step, .. } = &mut set {
+                *leader_h_step = 15.0;
+            }
+            render(&[
+                wire(WireType::Digital),
+                Command::Draw,
+                Command::Anchor { x: 500.0, y: 100.0 },
+                set,
+                pin(Some(WireType::Digital), None, None),
+                pin(Some(WireType::Digital), None, None),
+                Command::Anchor { x: 500.0, y: 300.0 },
+                pin(Some(WireType::Digital), None, None),
+            ])
+        };
+
+        // Each pin steps out from the one above, a new anchor starts over
+        let svg = render_side(Side::Right);
+        assert!(
+            svg.contains(r#"transform="translate(540,112.5)""#),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(r#"transform="translate(555,137.5)""#),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(r#"transform="translate(540,312.5)""#),
+            "{}",
+            svg
+        );
+
+        let svg = render_side(Side::Left);
+        assert!(
+            svg.contains(r#"transform="translate(420,112.5)""#),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(r#"transform="translate(405,137.5)""#),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(r#"transform="translate(420,312.5)""#),
+            "{}",
+            svg
+        );
+    }
+
+    #[test]
+    fn test_dashed_wire() {
+        let dashed = |dash: Option<&str>| {
+            let mut cmd = wire(WireType::Digital);
+            if let Command::Wire {
+                dash: wire_dash, ..
+            } = &mut cmd
+            {
+                *wire_dash = dash.map(|dash| dash.to_string());
+            }
+            render(&[
+                cmd,
+                Command::Draw,
+                pin_set(Side::Left, 40.0),
+                pin(Some(WireType::Digital), None, None),
+            ])
+        };
+
+        let svg = dashed(Some("6 3"));
+        assert!(svg.contains(r#"stroke-dasharray="6 3""#), "{}", svg);
+        assert!(!dashed(None).contains("stroke-dasharray"));
+    }
+
+    #[test]
+    fn test_pin_type_arrows() {
+        let arrows = |side: Side, arrow: bool| {
+            let types = [PinType::Input, PinType::Output].map(|pin_type| Command::Type {
+                pin_type,
+                color: "green".to_string(),
+                opacity: 1.0,
+                arrow,
+            });
+            render(&[
+                types[0].clone(),
+                types[1].clone(),
+                wire(WireType::Digital),
+                Command::Draw,
+                pin_set(side, 40.0),
+                pin(Some(WireType::Digital), Some(PinType::Input), None),
+                pin(Some(WireType::Digital), Some(PinType::Output), None),
+                pin(Some(WireType::Digital), Some(PinType::IO), None),
+            ])
+        };
+
+        // The arrow sits on the pin's end of the leader, the start on the right side
+        let svg = arrows(Side::Right, true);
+        assert_eq!(svg.matches("<marker").count(), 2, "{}", svg);
+        assert!(
+            svg.contains(r#"marker-start="url(#pin-arrow-in)""#),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(r#"marker-start="url(#pin-arrow-out)""#),
+            "{}",
+            svg
+        );
+        assert_eq!(svg.matches("marker-start").count(), 2, "{}", svg);
+
+        let svg = arrows(Side::Left, true);
+        assert!(
+            svg.contains(r#"marker-end="url(#pin-arrow-in)""#),
+            "{}",
+            svg
+        );
+        assert!(
+            svg.contains(r#"marker-end="url(#pin-arrow-out)""#),
+            "{}",
+            svg
+        );
+        assert!(!svg.contains("marker-start"), "{}", svg);
+
+        assert!(!arrows(Side::Right, false).contains("marker"));
+    }
+
+    #[test]
+    fn test_bus_wire() {
+        let commands = crate::parser::csv::parse_csv_str(
+            "WIRE, BUS, purple, 1, 2\n\
+             DRAW\n\
+             PINSET, RIGHT, PACKED, CENTER, CENTER, 25, 10, 40, 40, 5, 0\n\
+             PIN, BUS, , , SDA\n",
+        )
+        .unwrap();
+        assert!(commands.contains(&Command::Pin {
+            wire: Some(WireType::Bus),
+            pin_type: None,
+            group: None,
+            attributes: vec!["SDA".to_string()],
+            leader: None,
+        }));
+
+        // Two rails of the wire's thickness, one thickness either side of the centre line
+        let svg = render(&commands);
+        assert_eq!(svg.matches(r#"stroke="purple""#).count(), 2, "{}", svg);
+        assert!(svg.contains(r#"transform="translate(0,-2)""#), "{}", svg);
+        assert!(svg.contains(r#"transform="translate(0,2)""#), "{}", svg);
+    }
+
+    #[test]
+    fn test_ground_wire() {
+        let mut cmd = wire(WireType::Gnd);
+        if let Command::Wire { color, .. } = &mut cmd {
+            *color = "green".to_string();
+        }
+        let svg = render(&[
+            cmd,
+            Command::Draw,
+            Command::Anchor { x: 100.0, y: 100.0 },
+            pin_set(Side::Right, 40.0),
+            pin(Some(WireType::Gnd), None, None),
+        ]);
+
+        // The leader runs from 140 to 180 on the row's centre line, the bars hang 5 apart
+        assert!(svg.contains(r#"class="pin-ground""#), "{}", svg);
+        for bar in [
+            "170,117.5 190,117.5",
+            "173.33333,122.5 186.66667,122.5",
+            "176.66667,127.5 183.33333,127.5",
+        ] {
+            assert!(svg.contains(&format!(r#"points="{}""#, bar)), "{}", svg);
+        }
+    }
+
+    #[test]
+    fn test_three_line_box() {
+        let svg = render(&[
+            Command::Draw,
+            Command::Box {
+                theme: "STD".to_string(),
+                x: 10.0,
+                y: 10.0,
+                box_width: Some(80.0),
+                box_height: Some(60.0),
+                x_justify: None,
+                y_justify: None,
+                message: Some("VCC\\nInput\\n3V3".to_string()),
+                text_anchor: None,
+            },
+        ]);
+
+        // A font size apart around the centre, the middle line where a single line would be
+        assert_eq!(svg.matches("<text").count(), 3, "{}", svg);
+        for (line, y) in [
+            ("VCC", "-6.666667"),
+            ("Input", "3.333333"),
+            ("3V3", "13.333333"),
+        ] {
+            let start = svg.find(&format!("y=\"{}\">", y)).unwrap();
+            let text = svg[start..].split("</text>").next().unwrap();
+            assert_eq!(text.trim_end().lines().last(), Some(line), "{}", svg);
+        }
+    }
+
+    #[test]
+    fn test_large_diagram() {
+        let render_pins = |count: usize| {
+            let mut commands = vec![
+                Command::Labels {
+                    default: "DEFAULT".to_string(),
+                    pin_type: None,
+                    group: None,
+                    labels: vec!["Name".to_string(), "GPIO".to_string()],
+                },
+                wire(WireType::Digital),
+                Command::Draw,
+                Command::Anchor { x: 100.0, y: 100.0 },
+                pin_set(Side::Right, 40.0),
+            ];
+            commands.extend((0..count).map(|index| Command::Pin {
+                wire: Some(WireType::Digital),
+                pin_type: None,
+                group: None,
+                attributes: vec![format!("P{}", index), format!("GPIO{}", index)],
+                leader: None,
+            }));
+
+            let start = std::time::Instant::now();
+            let svg = render(&commands);
+            let elapsed = start.elapsed();
+            assert!(svg.contains(&format!("\nGPIO{}\n</text>", count - 1)));
+            elapsed
+        };
+
+        // No pin may do work over the whole document, so four times the pins take about
+        // four times as long, where the document used to be copied for every element
+        render_pins(1000);
+        let small = render_pins(1000);
+        let large = render_pins(4000);
+        assert!(
+            large < small * 6,
+            "1000 pins took {:?}, 4000 took {:?}",
+            small,
+            large
+        );
+    }
+}